@@ -0,0 +1,1723 @@
+//! In-process harness for the vault program.
+//!
+//! Runs `anchor_vault` natively inside a `solana-program-test` bank next to the SPL Token,
+//! Token-2022 and Associated Token programs, so tests can drive `initialize`, `deposit`,
+//! `withdraw` and `close` without a validator.
+
+use anchor_lang::{
+    error::ERROR_CODE_OFFSET,
+    prelude::{AccountInfo, Pubkey},
+    solana_program::entrypoint::ProgramResult,
+    system_program, AccountDeserialize, Event, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::{
+            self, instruction::create_associated_token_account_idempotent,
+        },
+    },
+    token_2022::spl_token_2022::{
+        extension::StateWithExtensions,
+        instruction::{freeze_account, initialize_mint2, mint_to},
+        state::{Account as TokenAccount, Mint as MintState},
+    },
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use d1_anchor_vault::{
+    Allowlist, Config, Multisig, Pool, Recovery, Session, Stream, VaultError, VaultIndex,
+    VaultState,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    rent::Rent,
+    signature::{Keypair, Signer},
+    stake, system_instruction, sysvar,
+    transaction::{Transaction, TransactionError},
+    vote::{
+        instruction::{create_account_with_config, CreateVoteAccountConfig},
+        state::{VoteInit, VoteState},
+    },
+};
+use std::sync::Once;
+
+pub use anchor_spl::{token::ID as TOKEN_PROGRAM_ID, token_2022::ID as TOKEN_2022_PROGRAM_ID};
+
+/// Lamports every actor starts with.
+pub const ACTOR_LAMPORTS: u64 = 100 * LAMPORTS_PER_SOL;
+
+/// Id of the vault that helpers without an explicit id address.
+pub const MAIN_VAULT: u64 = 0;
+
+/// Anchor ties the account slice and the accounts to one lifetime, which the native
+/// processor signature does not, so hand it an owned copy of the slice instead.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    d1_anchor_vault::entry(program_id, accounts, data)
+}
+
+/// `solana-program-test` prints `sol_log_data` to stdout for native processors instead of
+/// logging it, so Anchor events never reach the transaction logs. This forwards the data to
+/// `sol_log`, which does log it, and delegates everything else to the stubs it wraps.
+struct EventLogStubs(Box<dyn SyscallStubs>);
+
+/// Placeholder while the program-test stubs are being wrapped.
+struct NoStubs;
+
+impl SyscallStubs for NoStubs {}
+
+impl SyscallStubs for EventLogStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message);
+    }
+
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units();
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data);
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.0
+            .sol_log(&format!("Program data: {}", fields.join(" ")));
+    }
+
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// A `ProgramTest` with the vault program loaded as a native processor.
+/// Where the upgradeable loader keeps the program's upgrade authority.
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[d1_anchor_vault::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new(
+        "d1_anchor_vault",
+        d1_anchor_vault::ID,
+        processor!(process_instruction),
+    );
+    program_test.prefer_bpf(false);
+    program_test
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mint {
+    pub address: Pubkey,
+    pub token_program: Pubkey,
+    pub decimals: u8,
+}
+
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub actors: Vec<Keypair>,
+    pub mints: Vec<Mint>,
+    slot: u64,
+    unix_timestamp: Option<i64>,
+}
+
+impl Harness {
+    /// Starts a bank with `actors` funded system accounts.
+    pub async fn start(program_test: ProgramTest, actors: usize) -> Self {
+        let mut harness = Self::start_unconfigured(program_test, actors).await;
+
+        // Like a deployment, the program config is created right away, with the payer as admin
+        let initialize_config = harness.initialize_config_instruction(harness.admin());
+        harness
+            .send(&[initialize_config], &[])
+            .await
+            .expect("initialize config");
+
+        harness
+    }
+
+    /// Starts the bank as it is right after deploying: the context payer is the program's upgrade
+    /// authority, but nobody has created the program config yet.
+    pub async fn start_unconfigured(mut program_test: ProgramTest, actors: usize) -> Self {
+        let actors: Vec<Keypair> = (0..actors).map(|_| Keypair::new()).collect();
+
+        for actor in &actors {
+            program_test.add_account(
+                actor.pubkey(),
+                Account {
+                    lamports: ACTOR_LAMPORTS,
+                    owner: system_program::ID,
+                    ..Account::default()
+                },
+            );
+        }
+
+        let mut context = program_test.start_with_context().await;
+
+        // Starting the first bank installs the program-test stubs, which are then wrapped once.
+        // Other tests wait on the `Once`, so none of them sends a transaction mid-swap.
+        static EVENT_LOGS: Once = Once::new();
+        EVENT_LOGS.call_once(|| {
+            let stubs = program_stubs::set_syscall_stubs(Box::new(NoStubs));
+            program_stubs::set_syscall_stubs(Box::new(EventLogStubs(stubs)));
+        });
+        let slot = context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .expect("clock sysvar")
+            .slot;
+
+        // The program runs as a builtin, so its `ProgramData` is written by hand
+        let program_data = Account::new_data(
+            LAMPORTS_PER_SOL,
+            &UpgradeableLoaderState::ProgramData {
+                slot,
+                upgrade_authority_address: Some(context.payer.pubkey()),
+            },
+            &bpf_loader_upgradeable::ID,
+        )
+        .expect("program data");
+        context.set_account(&program_data_address(), &program_data.into());
+
+        Self {
+            context,
+            actors,
+            mints: Vec::new(),
+            slot,
+            unix_timestamp: None,
+        }
+    }
+
+    pub fn actor(&self, index: usize) -> Pubkey {
+        self.actors[index].pubkey()
+    }
+
+    /// The program admin set up by [`Harness::start`], which is the context payer and the
+    /// program's upgrade authority.
+    pub fn admin(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// Sends `instructions` paid for by the context payer and signed by the given actors.
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[usize],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction(instructions, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Like [`Harness::send`], for signers that are not actors.
+    pub async fn send_with_keypairs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction_with_keypairs(instructions, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Like [`Harness::send`], returning the program logs of the successful transaction.
+    pub async fn send_with_logs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[usize],
+    ) -> Result<Vec<String>, BanksClientError> {
+        let transaction = self.transaction(instructions, signers).await;
+        let processed = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        processed.result?;
+
+        Ok(processed
+            .metadata
+            .map(|metadata| metadata.log_messages)
+            .unwrap_or_default())
+    }
+
+    /// Every transaction lands in a fresh slot so that retrying an identical instruction
+    /// is executed again instead of being rejected as already processed.
+    async fn transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[usize],
+    ) -> Transaction {
+        let signers: Vec<Keypair> = signers
+            .iter()
+            .map(|&signer| self.actors[signer].insecure_clone())
+            .collect();
+        self.transaction_with_keypairs(instructions, &signers.iter().collect::<Vec<_>>())
+            .await
+    }
+
+    async fn transaction_with_keypairs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Transaction {
+        self.slot += 1;
+        self.context
+            .warp_to_slot(self.slot)
+            .expect("warp to next slot");
+
+        if let Some(unix_timestamp) = self.unix_timestamp {
+            let mut clock = self.clock().await;
+            clock.unix_timestamp = unix_timestamp;
+            self.context.set_sysvar(&clock);
+        }
+
+        let mut keypairs = vec![&self.context.payer];
+        keypairs.extend_from_slice(signers);
+
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &keypairs,
+            self.context.last_blockhash,
+        )
+    }
+
+    /// Creates a mint owned by `token_program` with the context payer as mint and freeze
+    /// authority, returning its index in `mints`.
+    pub async fn create_mint(
+        &mut self,
+        token_program: Pubkey,
+        decimals: u8,
+    ) -> Result<usize, BanksClientError> {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        let rent = self.rent().await;
+
+        let instructions = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(MintState::LEN),
+                MintState::LEN as u64,
+                &token_program,
+            ),
+            initialize_mint2(
+                &token_program,
+                &mint.pubkey(),
+                &payer,
+                Some(&payer),
+                decimals,
+            )
+            .expect("initialize mint instruction"),
+        ];
+
+        self.send_with_keypairs(&instructions, &[&mint]).await?;
+
+        self.mints.push(Mint {
+            address: mint.pubkey(),
+            token_program,
+            decimals,
+        });
+        Ok(self.mints.len() - 1)
+    }
+
+    /// Creates `owner`'s associated token account for `mint` if it does not exist yet.
+    pub async fn create_ata(
+        &mut self,
+        owner: &Pubkey,
+        mint: usize,
+    ) -> Result<(), BanksClientError> {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+
+        let instruction = create_associated_token_account_idempotent(
+            &self.context.payer.pubkey(),
+            owner,
+            &address,
+            &token_program,
+        );
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Mints `amount` of `mint` into `owner`'s associated token account, creating it first.
+    pub async fn mint_to(
+        &mut self,
+        mint: usize,
+        owner: usize,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+        let owner = self.actor(owner);
+        let payer = self.context.payer.pubkey();
+
+        let instructions = [
+            create_associated_token_account_idempotent(&payer, &owner, &address, &token_program),
+            mint_to(
+                &token_program,
+                &address,
+                &self.ata_of(&owner, mint),
+                &payer,
+                &[],
+                amount,
+            )
+            .expect("mint to instruction"),
+        ];
+        self.send(&instructions, &[]).await
+    }
+
+    /// Freezes `account`, a token account of `mint`, with the mint's freeze authority.
+    pub async fn freeze(&mut self, mint: usize, account: Pubkey) -> Result<(), BanksClientError> {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+
+        let instruction = freeze_account(
+            &token_program,
+            &account,
+            &address,
+            &self.context.payer.pubkey(),
+            &[],
+        )
+        .expect("freeze account instruction");
+        self.send(&[instruction], &[]).await
+    }
+
+    pub fn ata(&self, owner: usize, mint: usize) -> Pubkey {
+        self.ata_of(&self.actor(owner), mint)
+    }
+
+    pub fn ata_of(&self, owner: &Pubkey, mint: usize) -> Pubkey {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+        get_associated_token_address_with_program_id(owner, &address, &token_program)
+    }
+
+    pub async fn clock(&mut self) -> Clock {
+        self.context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .expect("clock sysvar")
+    }
+
+    /// Pins the bank clock to `unix_timestamp` for every following transaction.
+    pub fn warp_to_timestamp(&mut self, unix_timestamp: i64) {
+        self.unix_timestamp = Some(unix_timestamp);
+    }
+
+    /// The state of the vault [`MAIN_VAULT`] created by `user`, which the single-vault helpers address.
+    pub fn state_address(&self, user: usize) -> Pubkey {
+        self.state_address_of(user, MAIN_VAULT)
+    }
+
+    pub fn state_address_of(&self, user: usize, id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"state", self.actor(user).as_ref(), &id.to_le_bytes()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn vault_address(&self, user: usize) -> Pubkey {
+        self.vault_address_of(user, MAIN_VAULT)
+    }
+
+    pub fn vault_address_of(&self, user: usize, id: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"vault", self.state_address_of(user, id).as_ref()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    /// The index listing all of `user`'s vaults.
+    pub fn index_address(&self, user: usize) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"vaults", self.actor(user).as_ref()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn initialize_instruction(&self, user: usize) -> Instruction {
+        self.locked_initialize_instruction(user, None)
+    }
+
+    /// Builds `initialize` with withdrawals time-locked until `unlock_at`.
+    pub fn locked_initialize_instruction(
+        &self,
+        user: usize,
+        unlock_at: Option<i64>,
+    ) -> Instruction {
+        self.initialize_vault_instruction(user, MAIN_VAULT, "main", unlock_at)
+    }
+
+    /// Builds `initialize` for `user`'s vault `id`, listed as `name` in their index.
+    pub fn initialize_vault_instruction(
+        &self,
+        user: usize,
+        id: u64,
+        name: &str,
+        unlock_at: Option<i64>,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::Initialize {
+                user: self.actor(user),
+                vault_state: self.state_address_of(user, id),
+                vault: self.vault_address_of(user, id),
+                index: self.index_address(user),
+                config: self.config_address(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::Initialize {
+                id,
+                name: name.to_string(),
+                unlock_at,
+            }
+            .data(),
+        }
+    }
+
+    pub fn deposit_instruction(&self, user: usize, amount: u64) -> Instruction {
+        self.deposit_for_instruction(user, user, amount)
+    }
+
+    /// A deposit paid by `depositor` into `owner`'s vault.
+    pub fn deposit_for_instruction(
+        &self,
+        depositor: usize,
+        owner: usize,
+        amount: u64,
+    ) -> Instruction {
+        self.deposit_into_instruction(depositor, owner, MAIN_VAULT, amount)
+    }
+
+    /// A deposit paid by `depositor` into `owner`'s vault `id`.
+    pub fn deposit_into_instruction(
+        &self,
+        depositor: usize,
+        owner: usize,
+        id: u64,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::Deposit {
+                depositor: self.actor(depositor),
+                owner: self.actor(owner),
+                vault: self.vault_address_of(owner, id),
+                vault_state: self.state_address_of(owner, id),
+                config: self.config_address(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::Deposit { amount }.data(),
+        }
+    }
+
+    pub fn withdraw_instruction(&self, user: usize, amount: u64) -> Instruction {
+        self.withdraw_from_instruction(user, MAIN_VAULT, amount)
+    }
+
+    pub fn withdraw_from_instruction(&self, user: usize, id: u64, amount: u64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::Operation {
+                user: self.actor(user),
+                vault: self.vault_address_of(user, id),
+                vault_state: self.state_address_of(user, id),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::Withdraw { amount }.data(),
+        }
+    }
+
+    pub fn close_instruction(&self, user: usize) -> Instruction {
+        self.close_vault_instruction(user, MAIN_VAULT)
+    }
+
+    pub fn close_vault_instruction(&self, user: usize, id: u64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::Close {
+                user: self.actor(user),
+                vault: self.vault_address_of(user, id),
+                vault_state: self.state_address_of(user, id),
+                index: self.index_address(user),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::Close {}.data(),
+        }
+    }
+
+    /// The state address `user`'s vault had before multiple vaults, still used once migrated.
+    pub fn legacy_state_address(&self, user: usize) -> Pubkey {
+        Pubkey::find_program_address(&[b"state", self.actor(user).as_ref()], &d1_anchor_vault::ID).0
+    }
+
+    pub fn migrate_instruction(&self, user: usize, name: &str) -> Instruction {
+        let vault_state = self.legacy_state_address(user);
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::Migrate {
+                user: self.actor(user),
+                vault_state,
+                vault: Pubkey::find_program_address(
+                    &[b"vault", vault_state.as_ref()],
+                    &d1_anchor_vault::ID,
+                )
+                .0,
+                index: self.index_address(user),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::Migrate {
+                name: name.to_string(),
+            }
+            .data(),
+        }
+    }
+
+    pub fn extend_lock_instruction(&self, user: usize, unlock_at: i64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::ExtendLock { unlock_at }.data(),
+        }
+    }
+
+    pub fn set_withdraw_cap_instruction(&self, user: usize, cap: u64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::SetWithdrawCap { cap }.data(),
+        }
+    }
+
+    pub fn propose_owner_instruction(&self, user: usize, new_owner: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::ProposeOwner { new_owner }.data(),
+        }
+    }
+
+    /// `new_owner` accepting `creator`'s vault `id`, currently owned by `owner`.
+    pub fn accept_owner_instruction(
+        &self,
+        new_owner: usize,
+        creator: usize,
+        id: u64,
+        owner: usize,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::AcceptOwner {
+                new_owner: self.actor(new_owner),
+                vault_state: self.state_address_of(creator, id),
+                previous_index: self.index_address(owner),
+                index: self.index_address(new_owner),
+                creator_index: (creator != owner && creator != new_owner)
+                    .then(|| self.index_address(creator)),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::AcceptOwner {}.data(),
+        }
+    }
+
+    pub fn set_guardians_instruction(
+        &self,
+        user: usize,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::SetGuardians {
+                guardians,
+                threshold,
+            }
+            .data(),
+        }
+    }
+
+    pub fn ping_instruction(&self, user: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::Ping {}.data(),
+        }
+    }
+
+    pub fn set_beneficiary_instruction(
+        &self,
+        user: usize,
+        beneficiary: Pubkey,
+        inactivity_period: i64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.configure_accounts(user),
+            data: d1_anchor_vault::instruction::SetBeneficiary {
+                beneficiary,
+                inactivity_period,
+            }
+            .data(),
+        }
+    }
+
+    /// Claims `user`'s vault for `beneficiary`.
+    pub fn claim_inheritance_instruction(&self, beneficiary: usize, user: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ClaimInheritance {
+                beneficiary: self.actor(beneficiary),
+                vault: self.vault_address(user),
+                vault_state: self.state_address(user),
+                stake_account: self.stake_address(user),
+                clock: sysvar::clock::ID,
+                stake_program: stake::program::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ClaimInheritance {}.data(),
+        }
+    }
+
+    fn configure_accounts(&self, user: usize) -> Vec<AccountMeta> {
+        d1_anchor_vault::accounts::Configure {
+            user: self.actor(user),
+            vault_state: self.state_address(user),
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn recovery_address(&self, user: usize) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"recovery", self.state_address(user).as_ref()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn propose_recovery_instruction(
+        &self,
+        guardian: usize,
+        user: usize,
+        new_owner: Pubkey,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ProposeRecovery {
+                guardian: self.actor(guardian),
+                vault_state: self.state_address(user),
+                recovery: self.recovery_address(user),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ProposeRecovery { new_owner }.data(),
+        }
+    }
+
+    pub fn approve_recovery_instruction(&self, guardian: usize, user: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ApproveRecovery {
+                guardian: self.actor(guardian),
+                vault_state: self.state_address(user),
+                recovery: self.recovery_address(user),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ApproveRecovery {}.data(),
+        }
+    }
+
+    pub fn execute_recovery_instruction(
+        &self,
+        user: usize,
+        new_owner: Pubkey,
+        proposer: usize,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ExecuteRecovery {
+                new_owner,
+                proposer: self.actor(proposer),
+                vault: self.vault_address(user),
+                vault_state: self.state_address(user),
+                index: self.index_address(user),
+                recovery: self.recovery_address(user),
+                stake_account: self.stake_address(user),
+                clock: sysvar::clock::ID,
+                stake_program: stake::program::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ExecuteRecovery {}.data(),
+        }
+    }
+
+    pub fn cancel_recovery_instruction(&self, user: usize, proposer: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CancelRecovery {
+                user: self.actor(user),
+                proposer: self.actor(proposer),
+                vault_state: self.state_address(user),
+                recovery: self.recovery_address(user),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CancelRecovery {}.data(),
+        }
+    }
+
+    /// The token vault holding `user`'s tokens of `mint`.
+    pub fn token_vault_address(&self, user: usize, mint: usize) -> Pubkey {
+        self.ata_of(&self.state_address(user), mint)
+    }
+
+    fn token_accounts(&self, user: usize, mint: usize) -> (Pubkey, Pubkey, Pubkey) {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+        (address, token_program, self.token_vault_address(user, mint))
+    }
+
+    pub fn deposit_tokens_instruction(&self, user: usize, mint: usize, amount: u64) -> Instruction {
+        let (mint_address, token_program, token_vault) = self.token_accounts(user, mint);
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::TokenDeposit {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                mint: mint_address,
+                user_ata: self.ata(user, mint),
+                token_vault,
+                config: self.config_address(),
+                token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::DepositTokens { amount }.data(),
+        }
+    }
+
+    pub fn withdraw_tokens_instruction(
+        &self,
+        user: usize,
+        mint: usize,
+        amount: u64,
+    ) -> Instruction {
+        let (mint_address, token_program, token_vault) = self.token_accounts(user, mint);
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::TokenWithdraw {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                mint: mint_address,
+                user_ata: self.ata(user, mint),
+                token_vault,
+                token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::WithdrawTokens { amount }.data(),
+        }
+    }
+
+    pub fn close_token_vault_instruction(&self, user: usize, mint: usize) -> Instruction {
+        let (mint_address, token_program, token_vault) = self.token_accounts(user, mint);
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CloseTokenVault {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                mint: mint_address,
+                token_vault,
+                token_program,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CloseTokenVault {}.data(),
+        }
+    }
+
+    /// Remaining accounts that sweep `user`'s token vaults for `mints` to `recipient`'s
+    /// associated token accounts, as `close` and `execute_recovery` expect them.
+    pub fn sweep_accounts(
+        &self,
+        user: usize,
+        recipient: &Pubkey,
+        mints: &[usize],
+    ) -> Vec<AccountMeta> {
+        mints
+            .iter()
+            .flat_map(|&mint| {
+                let (mint_address, token_program, token_vault) = self.token_accounts(user, mint);
+                [
+                    AccountMeta::new_readonly(mint_address, false),
+                    AccountMeta::new(token_vault, false),
+                    AccountMeta::new(self.ata_of(recipient, mint), false),
+                    AccountMeta::new_readonly(token_program, false),
+                ]
+            })
+            .collect()
+    }
+
+    pub fn multisig_address(&self, creator: usize, seed: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"multisig",
+                self.actor(creator).as_ref(),
+                &seed.to_le_bytes(),
+            ],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    /// The vault PDA that holds the lamports of `multisig`.
+    pub fn multisig_vault_address(&self, multisig: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault", multisig.as_ref()], &d1_anchor_vault::ID).0
+    }
+
+    pub fn proposal_address(&self, multisig: &Pubkey, index: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"proposal", multisig.as_ref(), &index.to_le_bytes()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn initialize_multisig_instruction(
+        &self,
+        creator: usize,
+        seed: u64,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Instruction {
+        let multisig = self.multisig_address(creator, seed);
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::InitializeMultisig {
+                creator: self.actor(creator),
+                multisig,
+                vault: self.multisig_vault_address(&multisig),
+                config: self.config_address(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::InitializeMultisig {
+                seed,
+                owners,
+                threshold,
+            }
+            .data(),
+        }
+    }
+
+    pub fn multisig_deposit_instruction(
+        &self,
+        depositor: usize,
+        multisig: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::MultisigDeposit {
+                depositor: self.actor(depositor),
+                vault: self.multisig_vault_address(&multisig),
+                multisig,
+                config: self.config_address(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::MultisigDeposit { amount }.data(),
+        }
+    }
+
+    pub fn propose_withdrawal_instruction(
+        &self,
+        proposer: usize,
+        multisig: Pubkey,
+        index: u64,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ProposeWithdrawal {
+                proposer: self.actor(proposer),
+                multisig,
+                proposal: self.proposal_address(&multisig, index),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ProposeWithdrawal {
+                destination,
+                amount,
+            }
+            .data(),
+        }
+    }
+
+    pub fn approve_proposal_instruction(
+        &self,
+        owner: usize,
+        multisig: Pubkey,
+        index: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ApproveProposal {
+                owner: self.actor(owner),
+                multisig,
+                proposal: self.proposal_address(&multisig, index),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ApproveProposal {}.data(),
+        }
+    }
+
+    pub fn execute_proposal_instruction(
+        &self,
+        multisig: Pubkey,
+        index: u64,
+        destination: Pubkey,
+        proposer: usize,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::ExecuteProposal {
+                destination,
+                proposer: self.actor(proposer),
+                vault: self.multisig_vault_address(&multisig),
+                multisig,
+                proposal: self.proposal_address(&multisig, index),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::ExecuteProposal {}.data(),
+        }
+    }
+
+    pub fn pool_address(&self, creator: usize, seed: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"pool", self.actor(creator).as_ref(), &seed.to_le_bytes()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    /// The vault PDA that holds the lamports of `pool`.
+    pub fn pool_vault_address(&self, pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"vault", pool.as_ref()], &d1_anchor_vault::ID).0
+    }
+
+    pub fn share_mint_address(&self, pool: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"shares", pool.as_ref()], &d1_anchor_vault::ID).0
+    }
+
+    /// `depositor`'s associated token account for the shares of `pool`.
+    pub fn shares_address(&self, depositor: usize, pool: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(
+            &self.actor(depositor),
+            &self.share_mint_address(pool),
+            &TOKEN_PROGRAM_ID,
+        )
+    }
+
+    pub fn initialize_pool_instruction(&self, creator: usize, seed: u64) -> Instruction {
+        let pool = self.pool_address(creator, seed);
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::InitializePool {
+                creator: self.actor(creator),
+                pool,
+                vault: self.pool_vault_address(&pool),
+                share_mint: self.share_mint_address(&pool),
+                config: self.config_address(),
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::InitializePool { seed }.data(),
+        }
+    }
+
+    pub fn pool_deposit_instruction(
+        &self,
+        depositor: usize,
+        pool: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::PoolDeposit {
+                depositor: self.actor(depositor),
+                pool,
+                vault: self.pool_vault_address(&pool),
+                share_mint: self.share_mint_address(&pool),
+                depositor_shares: self.shares_address(depositor, &pool),
+                config: self.config_address(),
+                token_program: TOKEN_PROGRAM_ID,
+                associated_token_program: spl_associated_token_account::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::PoolDeposit { amount }.data(),
+        }
+    }
+
+    pub fn pool_withdraw_instruction(
+        &self,
+        depositor: usize,
+        pool: Pubkey,
+        shares: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::PoolWithdraw {
+                depositor: self.actor(depositor),
+                pool,
+                vault: self.pool_vault_address(&pool),
+                share_mint: self.share_mint_address(&pool),
+                depositor_shares: self.shares_address(depositor, &pool),
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::PoolWithdraw { shares }.data(),
+        }
+    }
+
+    /// The Stake program's minimum delegation, which `create_stake` adds to the stake account's rent.
+    pub async fn minimum_delegation(&mut self) -> u64 {
+        let transaction = Transaction::new_signed_with_payer(
+            &[stake::instruction::get_minimum_delegation()],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer],
+            self.context.last_blockhash,
+        );
+        let simulation = self
+            .context
+            .banks_client
+            .simulate_transaction(transaction)
+            .await
+            .expect("simulate get_minimum_delegation");
+        let data = simulation
+            .simulation_details
+            .and_then(|details| details.return_data)
+            .expect("minimum delegation return data")
+            .data;
+        u64::from_le_bytes(data.try_into().expect("minimum delegation is a u64"))
+    }
+
+    /// Creates a vote account for a validator identified by actor `node`, which also holds its
+    /// voter and withdrawer authorities, and returns its address.
+    pub async fn create_vote_account(&mut self, node: usize) -> Result<Pubkey, BanksClientError> {
+        let vote = Keypair::new();
+        let node = self.actors[node].insecure_clone();
+        // Sized for the current vote state layout, the default still uses the legacy one
+        let config = CreateVoteAccountConfig {
+            space: VoteState::size_of() as u64,
+            ..CreateVoteAccountConfig::default()
+        };
+        let lamports = self.rent().await.minimum_balance(config.space as usize);
+
+        let instructions = create_account_with_config(
+            &self.context.payer.pubkey(),
+            &vote.pubkey(),
+            &VoteInit {
+                node_pubkey: node.pubkey(),
+                authorized_voter: node.pubkey(),
+                authorized_withdrawer: node.pubkey(),
+                commission: 0,
+            },
+            lamports,
+            config,
+        );
+
+        self.send_with_keypairs(&instructions, &[&vote, &node])
+            .await?;
+        Ok(vote.pubkey())
+    }
+
+    /// The stake account `user`'s vault stakes through.
+    pub fn stake_address(&self, user: usize) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"stake", self.state_address(user).as_ref()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn create_stake_instruction(&self, user: usize, amount: u64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CreateStake {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                vault: self.vault_address(user),
+                stake_account: self.stake_address(user),
+                rent: sysvar::rent::ID,
+                stake_program: stake::program::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CreateStake { amount }.data(),
+        }
+    }
+
+    pub fn delegate_stake_instruction(&self, user: usize, vote_account: Pubkey) -> Instruction {
+        #[allow(deprecated)]
+        let stake_config = stake::config::ID;
+
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::DelegateStake {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                vault: self.vault_address(user),
+                stake_account: self.stake_address(user),
+                vote_account,
+                clock: sysvar::clock::ID,
+                stake_history: sysvar::stake_history::ID,
+                stake_config,
+                stake_program: stake::program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::DelegateStake {}.data(),
+        }
+    }
+
+    pub fn deactivate_stake_instruction(&self, user: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::DeactivateStake {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                vault: self.vault_address(user),
+                stake_account: self.stake_address(user),
+                clock: sysvar::clock::ID,
+                stake_program: stake::program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::DeactivateStake {}.data(),
+        }
+    }
+
+    pub fn withdraw_stake_instruction(&self, user: usize) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::WithdrawStake {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                vault: self.vault_address(user),
+                stake_account: self.stake_address(user),
+                clock: sysvar::clock::ID,
+                stake_history: sysvar::stake_history::ID,
+                stake_program: stake::program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::WithdrawStake {}.data(),
+        }
+    }
+
+    pub fn allowlist_address(&self, user: usize) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"allowlist", self.state_address(user).as_ref()],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn initialize_allowlist_instruction(&self, user: usize, delay: i64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::InitializeAllowlist {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                allowlist: self.allowlist_address(user),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::InitializeAllowlist { delay }.data(),
+        }
+    }
+
+    fn manage_allowlist_accounts(&self, user: usize) -> Vec<AccountMeta> {
+        d1_anchor_vault::accounts::ManageAllowlist {
+            user: self.actor(user),
+            vault_state: self.state_address(user),
+            allowlist: self.allowlist_address(user),
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn add_destination_instruction(&self, user: usize, destination: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.manage_allowlist_accounts(user),
+            data: d1_anchor_vault::instruction::AddDestination { destination }.data(),
+        }
+    }
+
+    pub fn remove_destination_instruction(&self, user: usize, destination: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.manage_allowlist_accounts(user),
+            data: d1_anchor_vault::instruction::RemoveDestination { destination }.data(),
+        }
+    }
+
+    pub fn set_allowlist_delay_instruction(&self, user: usize, delay: i64) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.manage_allowlist_accounts(user),
+            data: d1_anchor_vault::instruction::SetAllowlistDelay { delay }.data(),
+        }
+    }
+
+    pub fn withdraw_to_instruction(
+        &self,
+        user: usize,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::WithdrawTo {
+                user: self.actor(user),
+                destination,
+                vault: self.vault_address(user),
+                vault_state: self.state_address(user),
+                allowlist: self.allowlist_address(user),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::WithdrawTo { amount }.data(),
+        }
+    }
+
+    pub fn stream_address(&self, user: usize, recipient: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"stream",
+                self.state_address(user).as_ref(),
+                recipient.as_ref(),
+            ],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn create_stream_instruction(
+        &self,
+        user: usize,
+        recipient: Pubkey,
+        amount_per_period: u64,
+        period: i64,
+        start: i64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CreateStream {
+                user: self.actor(user),
+                recipient,
+                vault_state: self.state_address(user),
+                stream: self.stream_address(user, &recipient),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CreateStream {
+                amount_per_period,
+                period,
+                start,
+            }
+            .data(),
+        }
+    }
+
+    /// Pays out `user`'s stream to `recipient`; whoever sends it only pays the fee.
+    pub fn crank_stream_instruction(&self, user: usize, recipient: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CrankStream {
+                recipient,
+                vault: self.vault_address(user),
+                vault_state: self.state_address(user),
+                stream: self.stream_address(user, &recipient),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CrankStream {}.data(),
+        }
+    }
+
+    pub fn cancel_stream_instruction(&self, user: usize, recipient: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CancelStream {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                stream: self.stream_address(user, &recipient),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CancelStream {}.data(),
+        }
+    }
+
+    pub fn config_address(&self) -> Pubkey {
+        Pubkey::find_program_address(&[b"config"], &d1_anchor_vault::ID).0
+    }
+
+    pub fn initialize_config_instruction(&self, admin: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::InitializeConfig {
+                admin,
+                program_data: program_data_address(),
+                config: self.config_address(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::InitializeConfig {}.data(),
+        }
+    }
+
+    fn administer_accounts(&self, admin: Pubkey) -> Vec<AccountMeta> {
+        d1_anchor_vault::accounts::Administer {
+            admin,
+            config: self.config_address(),
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn set_paused_instruction(&self, admin: Pubkey, paused: bool) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.administer_accounts(admin),
+            data: d1_anchor_vault::instruction::SetPaused { paused }.data(),
+        }
+    }
+
+    pub fn propose_admin_instruction(&self, admin: Pubkey, new_admin: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: self.administer_accounts(admin),
+            data: d1_anchor_vault::instruction::ProposeAdmin { new_admin }.data(),
+        }
+    }
+
+    pub fn accept_admin_instruction(&self, new_admin: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::AcceptAdmin {
+                new_admin,
+                config: self.config_address(),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::AcceptAdmin {}.data(),
+        }
+    }
+
+    pub fn session_address(&self, user: usize, session_key: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"session",
+                self.state_address(user).as_ref(),
+                session_key.as_ref(),
+            ],
+            &d1_anchor_vault::ID,
+        )
+        .0
+    }
+
+    pub fn create_session_instruction(
+        &self,
+        user: usize,
+        session_key: Pubkey,
+        expires_at: i64,
+        spend_limit: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::CreateSession {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                session: self.session_address(user, &session_key),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::CreateSession {
+                session_key,
+                expires_at,
+                spend_limit,
+            }
+            .data(),
+        }
+    }
+
+    pub fn revoke_session_instruction(&self, user: usize, session_key: Pubkey) -> Instruction {
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::RevokeSession {
+                user: self.actor(user),
+                vault_state: self.state_address(user),
+                session: self.session_address(user, &session_key),
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::RevokeSession {}.data(),
+        }
+    }
+
+    /// Withdraws `amount` from `user`'s vault to `user`, signed by the `session_key` actor.
+    pub fn session_withdraw_instruction(
+        &self,
+        session_key: usize,
+        user: usize,
+        amount: u64,
+    ) -> Instruction {
+        let key = self.actor(session_key);
+        Instruction {
+            program_id: d1_anchor_vault::ID,
+            accounts: d1_anchor_vault::accounts::SessionWithdraw {
+                session_key: key,
+                owner: self.actor(user),
+                vault: self.vault_address(user),
+                vault_state: self.state_address(user),
+                session: self.session_address(user, &key),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: d1_anchor_vault::instruction::SessionWithdraw { amount }.data(),
+        }
+    }
+
+    pub async fn initialize(&mut self, user: usize) -> Result<(), BanksClientError> {
+        let instruction = self.initialize_instruction(user);
+        self.send(&[instruction], &[user]).await
+    }
+
+    pub async fn deposit(&mut self, user: usize, amount: u64) -> Result<(), BanksClientError> {
+        let instruction = self.deposit_instruction(user, amount);
+        self.send(&[instruction], &[user]).await
+    }
+
+    pub async fn withdraw(&mut self, user: usize, amount: u64) -> Result<(), BanksClientError> {
+        let instruction = self.withdraw_instruction(user, amount);
+        self.send(&[instruction], &[user]).await
+    }
+
+    pub async fn close(&mut self, user: usize) -> Result<(), BanksClientError> {
+        let instruction = self.close_instruction(user);
+        self.send(&[instruction], &[user]).await
+    }
+
+    pub async fn account(&mut self, address: Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(address)
+            .await
+            .expect("get account")
+    }
+
+    /// Lamports held by `address`, or zero when the account does not exist.
+    pub async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.account(address)
+            .await
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub async fn vault_state(&mut self, user: usize) -> Option<VaultState> {
+        self.vault_state_of(user, MAIN_VAULT).await
+    }
+
+    pub async fn vault_state_of(&mut self, user: usize, id: u64) -> Option<VaultState> {
+        let account = self.account(self.state_address_of(user, id)).await?;
+        Some(VaultState::try_deserialize(&mut account.data.as_slice()).expect("vault state"))
+    }
+
+    /// Token balance of `address`, or zero when the account does not exist.
+    pub async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        match self.account(address).await {
+            Some(account) => {
+                StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                    .expect("token account")
+                    .base
+                    .amount
+            }
+            None => 0,
+        }
+    }
+
+    pub async fn multisig(&mut self, address: Pubkey) -> Option<Multisig> {
+        let account = self.account(address).await?;
+        Some(Multisig::try_deserialize(&mut account.data.as_slice()).expect("multisig"))
+    }
+
+    pub async fn pool(&mut self, address: Pubkey) -> Option<Pool> {
+        let account = self.account(address).await?;
+        Some(Pool::try_deserialize(&mut account.data.as_slice()).expect("pool"))
+    }
+
+    pub async fn allowlist(&mut self, user: usize) -> Option<Allowlist> {
+        let account = self.account(self.allowlist_address(user)).await?;
+        Some(Allowlist::try_deserialize(&mut account.data.as_slice()).expect("allowlist"))
+    }
+
+    pub async fn stream(&mut self, user: usize, recipient: &Pubkey) -> Option<Stream> {
+        let account = self.account(self.stream_address(user, recipient)).await?;
+        Some(Stream::try_deserialize(&mut account.data.as_slice()).expect("stream"))
+    }
+
+    pub async fn session(&mut self, user: usize, session_key: &Pubkey) -> Option<Session> {
+        let account = self
+            .account(self.session_address(user, session_key))
+            .await?;
+        Some(Session::try_deserialize(&mut account.data.as_slice()).expect("session"))
+    }
+
+    pub async fn recovery(&mut self, user: usize) -> Option<Recovery> {
+        let account = self.account(self.recovery_address(user)).await?;
+        Some(Recovery::try_deserialize(&mut account.data.as_slice()).expect("recovery"))
+    }
+
+    pub async fn config(&mut self) -> Option<Config> {
+        let account = self.account(self.config_address()).await?;
+        Some(Config::try_deserialize(&mut account.data.as_slice()).expect("config"))
+    }
+
+    pub async fn vault_index(&mut self, user: usize) -> Option<VaultIndex> {
+        let account = self.account(self.index_address(user)).await?;
+        Some(VaultIndex::try_deserialize(&mut account.data.as_slice()).expect("vault index"))
+    }
+
+    pub async fn rent(&mut self) -> Rent {
+        self.context
+            .banks_client
+            .get_rent()
+            .await
+            .expect("rent sysvar")
+    }
+}
+
+/// Every `E` event emitted in `logs`, in order.
+/// - Natively processed programs log events as `Program log: Program data: ...`, see
+///   [`EventLogStubs`].
+pub fn events<E: Event>(logs: &[String]) -> Vec<E> {
+    logs.iter()
+        .filter_map(|log| log.split_once("Program data: "))
+        .map(|(_, data)| data)
+        .filter_map(|data| STANDARD.decode(data).ok())
+        .filter_map(|data| {
+            let payload = data.strip_prefix(E::DISCRIMINATOR.as_slice())?;
+            E::deserialize(&mut &payload[..]).ok()
+        })
+        .collect()
+}
+
+/// What `custom_error` reports for a transaction the vault program failed with `code`.
+pub fn vault_error(code: VaultError) -> Option<u32> {
+    Some(ERROR_CODE_OFFSET + code as u32)
+}
+
+/// The custom program error a failed transaction ended with, if any.
+pub fn custom_error(error: &BanksClientError) -> Option<u32> {
+    match error {
+        BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => Some(*code),
+        _ => None,
+    }
+}
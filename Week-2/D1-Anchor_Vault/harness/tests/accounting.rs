@@ -0,0 +1,125 @@
+//! The vault keeps lifetime totals of what went in and out, and emits an event for every move.
+
+use d1_anchor_vault::{VaultClosed, VaultDeposited, VaultWithdrawn};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{events, program_test, Harness};
+
+const USER: usize = 0;
+const PAYROLL: usize = 1;
+const NOW: i64 = 1_700_000_000;
+const DAY: i64 = 24 * 60 * 60;
+
+async fn vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 2).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn deposits_and_withdrawals_are_totalled() {
+    let mut harness = vault().await;
+    let vault_state = harness.state_address(USER);
+
+    let deposit = harness.deposit_instruction(USER, 5 * LAMPORTS_PER_SOL);
+    let logs = harness.send_with_logs(&[deposit], &[USER]).await.unwrap();
+    assert_eq!(
+        events::<VaultDeposited>(&logs),
+        [VaultDeposited {
+            vault_state,
+            depositor: harness.actor(USER),
+            amount: 5 * LAMPORTS_PER_SOL,
+            total_deposited: 5 * LAMPORTS_PER_SOL,
+            timestamp: NOW,
+        }]
+    );
+
+    harness.warp_to_timestamp(NOW + DAY);
+    let deposit = harness.deposit_for_instruction(PAYROLL, USER, LAMPORTS_PER_SOL);
+    let logs = harness
+        .send_with_logs(&[deposit], &[PAYROLL])
+        .await
+        .unwrap();
+    let deposited = events::<VaultDeposited>(&logs);
+    assert_eq!(deposited[0].depositor, harness.actor(PAYROLL));
+    assert_eq!(deposited[0].total_deposited, 6 * LAMPORTS_PER_SOL);
+
+    harness.warp_to_timestamp(NOW + 2 * DAY);
+    let withdraw = harness.withdraw_instruction(USER, 2 * LAMPORTS_PER_SOL);
+    let logs = harness.send_with_logs(&[withdraw], &[USER]).await.unwrap();
+    assert_eq!(
+        events::<VaultWithdrawn>(&logs),
+        [VaultWithdrawn {
+            vault_state,
+            destination: harness.actor(USER),
+            amount: 2 * LAMPORTS_PER_SOL,
+            total_withdrawn: 2 * LAMPORTS_PER_SOL,
+            timestamp: NOW + 2 * DAY,
+        }]
+    );
+
+    let state = harness.vault_state(USER).await.unwrap();
+    assert_eq!(state.total_deposited, 6 * LAMPORTS_PER_SOL);
+    assert_eq!(state.total_withdrawn, 2 * LAMPORTS_PER_SOL);
+    assert_eq!(state.last_activity, NOW + 2 * DAY);
+
+    // The totals reconcile with the balance.
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        state.total_deposited - state.total_withdrawn
+    );
+}
+
+#[tokio::test]
+async fn close_reports_the_final_totals() {
+    let mut harness = vault().await;
+    harness.deposit(USER, 5 * LAMPORTS_PER_SOL).await.unwrap();
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    let close = harness.close_instruction(USER);
+    let logs = harness.send_with_logs(&[close], &[USER]).await.unwrap();
+    assert_eq!(
+        events::<VaultClosed>(&logs),
+        [VaultClosed {
+            vault_state: harness.state_address(USER),
+            owner: harness.actor(USER),
+            amount: 4 * LAMPORTS_PER_SOL,
+            total_deposited: 5 * LAMPORTS_PER_SOL,
+            total_withdrawn: 5 * LAMPORTS_PER_SOL,
+            timestamp: NOW,
+        }]
+    );
+
+    // An emptied vault still reports its close, with nothing left to sweep.
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    let close = harness.close_instruction(USER);
+    let logs = harness.send_with_logs(&[close], &[USER]).await.unwrap();
+    let closed = events::<VaultClosed>(&logs);
+    assert_eq!(closed[0].amount, 0);
+    assert_eq!(closed[0].total_withdrawn, LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn third_party_payouts_count_as_withdrawals() {
+    let mut harness = vault().await;
+    harness.deposit(USER, 5 * LAMPORTS_PER_SOL).await.unwrap();
+
+    let recipient = Pubkey::new_unique();
+    let create = harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, DAY, NOW);
+    harness.send(&[create], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + 2 * DAY);
+    let crank = harness.crank_stream_instruction(USER, recipient);
+    let logs = harness.send_with_logs(&[crank], &[]).await.unwrap();
+
+    let withdrawn = events::<VaultWithdrawn>(&logs);
+    assert_eq!(withdrawn[0].destination, recipient);
+    assert_eq!(withdrawn[0].amount, 2 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().total_withdrawn,
+        2 * LAMPORTS_PER_SOL
+    );
+}
@@ -0,0 +1,133 @@
+//! Withdrawals can go to third parties, but only to allowlisted destinations that have waited out the delay.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const OUTSIDER: usize = 1;
+const NOW: i64 = 1_700_000_000;
+const DELAY: i64 = 24 * 60 * 60;
+
+async fn vault_with_allowlist() -> Harness {
+    let mut harness = Harness::start(program_test(), 2).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 10 * LAMPORTS_PER_SOL).await.unwrap();
+
+    let initialize = harness.initialize_allowlist_instruction(USER, DELAY);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    harness
+}
+
+async fn add(harness: &mut Harness, destination: Pubkey) {
+    let add = harness.add_destination_instruction(USER, destination);
+    harness.send(&[add], &[USER]).await.unwrap();
+}
+
+#[tokio::test]
+async fn new_destinations_wait_out_the_delay() {
+    let mut harness = vault_with_allowlist().await;
+    let destination = Pubkey::new_unique();
+    add(&mut harness, destination).await;
+
+    let withdraw = harness.withdraw_to_instruction(USER, destination, LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::DestinationPending)
+    );
+
+    harness.warp_to_timestamp(NOW + DELAY);
+    let withdraw = harness.withdraw_to_instruction(USER, destination, LAMPORTS_PER_SOL);
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+
+    assert_eq!(harness.lamports(destination).await, LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        9 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn only_listed_destinations_receive_withdrawals() {
+    let mut harness = vault_with_allowlist().await;
+    let destination = Pubkey::new_unique();
+
+    let withdraw = harness.withdraw_to_instruction(USER, destination, LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::DestinationNotAllowed)
+    );
+
+    add(&mut harness, destination).await;
+    let again = harness.add_destination_instruction(USER, destination);
+    let err = harness.send(&[again], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::DestinationAlreadyAllowed)
+    );
+
+    // Removal applies immediately, even before the destination became usable.
+    let remove = harness.remove_destination_instruction(USER, destination);
+    harness.send(&[remove], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + DELAY);
+    let withdraw = harness.withdraw_to_instruction(USER, destination, LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::DestinationNotAllowed)
+    );
+    assert_eq!(harness.lamports(destination).await, 0);
+}
+
+#[tokio::test]
+async fn only_the_owner_manages_the_allowlist() {
+    let mut harness = vault_with_allowlist().await;
+
+    let mut add = harness.add_destination_instruction(USER, harness.actor(OUTSIDER));
+    add.accounts[0].pubkey = harness.actor(OUTSIDER);
+    let err = harness.send(&[add], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    assert!(harness
+        .allowlist(USER)
+        .await
+        .unwrap()
+        .destinations
+        .is_empty());
+}
+
+#[tokio::test]
+async fn shortening_the_delay_is_delayed() {
+    let mut harness = vault_with_allowlist().await;
+
+    let shorten = harness.set_allowlist_delay_instruction(USER, 0);
+    harness.send(&[shorten], &[USER]).await.unwrap();
+
+    let allowlist = harness.allowlist(USER).await.unwrap();
+    assert_eq!(allowlist.delay, DELAY);
+    assert_eq!(allowlist.pending_delay_at, NOW + DELAY);
+
+    // Until then, new destinations still get the full delay.
+    let early = Pubkey::new_unique();
+    add(&mut harness, early).await;
+    assert_eq!(
+        harness.allowlist(USER).await.unwrap().destinations[0].usable_at,
+        NOW + DELAY
+    );
+
+    harness.warp_to_timestamp(NOW + DELAY);
+    let late = Pubkey::new_unique();
+    add(&mut harness, late).await;
+    let withdraw = harness.withdraw_to_instruction(USER, late, LAMPORTS_PER_SOL);
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+    assert_eq!(harness.lamports(late).await, LAMPORTS_PER_SOL);
+
+    // Lengthening applies right away.
+    let lengthen = harness.set_allowlist_delay_instruction(USER, 2 * DELAY);
+    harness.send(&[lengthen], &[USER]).await.unwrap();
+    assert_eq!(harness.allowlist(USER).await.unwrap().delay, 2 * DELAY);
+}
@@ -0,0 +1,76 @@
+//! Anyone can fund a vault by naming its owner, while only the owner can take funds out.
+
+use anchor_lang::error::ErrorCode;
+use d1_anchor_vault::VaultError;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const OWNER: usize = 0;
+const PAYROLL: usize = 1;
+const OTHER_OWNER: usize = 2;
+
+async fn vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 3).await;
+    harness.initialize(OWNER).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn third_party_deposits_into_the_named_vault() {
+    let mut harness = vault().await;
+    let payroll_before = harness.lamports(harness.actor(PAYROLL)).await;
+
+    let deposit = harness.deposit_for_instruction(PAYROLL, OWNER, 2 * LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[PAYROLL]).await.unwrap();
+
+    assert_eq!(
+        harness.lamports(harness.vault_address(OWNER)).await,
+        2 * LAMPORTS_PER_SOL
+    );
+    assert_eq!(
+        harness.lamports(harness.actor(PAYROLL)).await,
+        payroll_before - 2 * LAMPORTS_PER_SOL
+    );
+
+    // The funds belong to the owner, who withdraws them as usual.
+    harness.withdraw(OWNER, 2 * LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(harness.lamports(harness.vault_address(OWNER)).await, 0);
+}
+
+#[tokio::test]
+async fn depositor_cannot_withdraw_what_it_deposited() {
+    let mut harness = vault().await;
+
+    let deposit = harness.deposit_for_instruction(PAYROLL, OWNER, 2 * LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[PAYROLL]).await.unwrap();
+
+    // Pointing a withdrawal at the owner's vault with the depositor as signer is refused.
+    let mut withdraw = harness.withdraw_instruction(OWNER, LAMPORTS_PER_SOL);
+    withdraw.accounts[0].pubkey = harness.actor(PAYROLL);
+    let err = harness.send(&[withdraw], &[PAYROLL]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    assert_eq!(
+        harness.lamports(harness.vault_address(OWNER)).await,
+        2 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn named_owner_must_match_the_vault() {
+    let mut harness = vault().await;
+
+    // Naming an owner without a vault finds no vault state to deposit into.
+    let deposit = harness.deposit_for_instruction(PAYROLL, OTHER_OWNER, LAMPORTS_PER_SOL);
+    let err = harness.send(&[deposit], &[PAYROLL]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(ErrorCode::AccountNotInitialized as u32)
+    );
+
+    // Naming a different owner than the vault state belongs to is refused.
+    let mut deposit = harness.deposit_for_instruction(PAYROLL, OWNER, LAMPORTS_PER_SOL);
+    deposit.accounts[1].pubkey = harness.actor(OTHER_OWNER);
+    let err = harness.send(&[deposit], &[PAYROLL]).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(ErrorCode::ConstraintHasOne as u32));
+}
@@ -0,0 +1,195 @@
+//! A named beneficiary can claim the vault once its owner has been inactive for long enough.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{
+    custom_error, program_test, vault_error, Harness, ACTOR_LAMPORTS, TOKEN_PROGRAM_ID,
+};
+
+const USER: usize = 0;
+const HEIR: usize = 1;
+const OUTSIDER: usize = 2;
+const NOW: i64 = 1_700_000_000;
+const YEAR: i64 = 365 * 24 * 60 * 60;
+
+async fn vault_with_heir() -> Harness {
+    let mut harness = Harness::start(program_test(), 3).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 5 * LAMPORTS_PER_SOL).await.unwrap();
+
+    let set = harness.set_beneficiary_instruction(USER, harness.actor(HEIR), YEAR);
+    harness.send(&[set], &[USER]).await.unwrap();
+    harness
+}
+
+async fn claim(harness: &mut Harness, claimant: usize) -> Option<u32> {
+    let claim = harness.claim_inheritance_instruction(claimant, USER);
+    match harness.send(&[claim], &[claimant]).await {
+        Ok(()) => None,
+        Err(err) => custom_error(&err),
+    }
+}
+
+#[tokio::test]
+async fn beneficiary_claims_after_the_inactivity_window() {
+    let mut harness = vault_with_heir().await;
+
+    harness.warp_to_timestamp(NOW + YEAR - 1);
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::OwnerStillActive)
+    );
+
+    harness.warp_to_timestamp(NOW + YEAR);
+    assert_eq!(claim(&mut harness, HEIR).await, None);
+
+    assert_eq!(
+        harness.lamports(harness.actor(HEIR)).await,
+        ACTOR_LAMPORTS + 5 * LAMPORTS_PER_SOL
+    );
+    assert!(harness.account(harness.vault_address(USER)).await.is_none());
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().total_withdrawn,
+        5 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn the_beneficiary_inherits_token_vaults() {
+    let mut harness = vault_with_heir().await;
+    let heir = harness.actor(HEIR);
+    let mint = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    harness.mint_to(mint, USER, 1_000).await.unwrap();
+    let deposit = harness.deposit_tokens_instruction(USER, mint, 1_000);
+    harness.send(&[deposit], &[USER]).await.unwrap();
+
+    // Tokens cannot be left behind the state PDA, where nobody could reach them.
+    harness.warp_to_timestamp(NOW + YEAR);
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::TokenVaultsMissing)
+    );
+
+    harness.create_ata(&heir, mint).await.unwrap();
+    let mut claim = harness.claim_inheritance_instruction(HEIR, USER);
+    claim
+        .accounts
+        .extend(harness.sweep_accounts(USER, &heir, &[mint]));
+    harness.send(&[claim], &[HEIR]).await.unwrap();
+
+    assert_eq!(harness.token_balance(harness.ata(HEIR, mint)).await, 1_000);
+    assert!(harness
+        .account(harness.token_vault_address(USER, mint))
+        .await
+        .is_none());
+    assert!(harness
+        .vault_state(USER)
+        .await
+        .unwrap()
+        .token_mints
+        .is_empty());
+}
+
+#[tokio::test]
+async fn a_claim_restarts_the_window() {
+    let mut harness = vault_with_heir().await;
+
+    harness.warp_to_timestamp(NOW + YEAR);
+    assert_eq!(claim(&mut harness, HEIR).await, None);
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().last_heartbeat,
+        NOW + YEAR
+    );
+
+    // What reaches the vault afterwards is not the beneficiary's straight away.
+    let deposit = harness.deposit_for_instruction(OUTSIDER, USER, LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[OUTSIDER]).await.unwrap();
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::OwnerStillActive)
+    );
+
+    harness.warp_to_timestamp(NOW + 2 * YEAR);
+    assert_eq!(claim(&mut harness, HEIR).await, None);
+    assert_eq!(
+        harness.lamports(harness.actor(HEIR)).await,
+        ACTOR_LAMPORTS + 6 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn owner_actions_reset_the_window() {
+    let mut harness = vault_with_heir().await;
+
+    // A ping is enough to show the owner is still around.
+    harness.warp_to_timestamp(NOW + YEAR - 1);
+    let ping = harness.ping_instruction(USER);
+    harness.send(&[ping], &[USER]).await.unwrap();
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().last_heartbeat,
+        NOW + YEAR - 1
+    );
+
+    harness.warp_to_timestamp(NOW + YEAR);
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::OwnerStillActive)
+    );
+
+    // So is any other owner action, like a withdrawal.
+    harness.warp_to_timestamp(NOW + 2 * YEAR - 2);
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + 2 * YEAR - 1);
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::OwnerStillActive)
+    );
+
+    // Deposits from others say nothing about the owner.
+    let deposit = harness.deposit_for_instruction(OUTSIDER, USER, LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[OUTSIDER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + 3 * YEAR - 2);
+    assert_eq!(claim(&mut harness, HEIR).await, None);
+    assert_eq!(
+        harness.lamports(harness.actor(HEIR)).await,
+        ACTOR_LAMPORTS + 5 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn only_the_beneficiary_can_claim() {
+    let mut harness = vault_with_heir().await;
+    harness.warp_to_timestamp(NOW + YEAR);
+
+    assert_eq!(
+        claim(&mut harness, OUTSIDER).await,
+        vault_error(VaultError::NotBeneficiary)
+    );
+
+    // Removing the beneficiary turns the switch off.
+    let remove = harness.set_beneficiary_instruction(USER, Default::default(), 0);
+    harness.send(&[remove], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + 3 * YEAR);
+    assert_eq!(
+        claim(&mut harness, HEIR).await,
+        vault_error(VaultError::NotBeneficiary)
+    );
+
+    let to_self = harness.set_beneficiary_instruction(USER, harness.actor(USER), YEAR);
+    let err = harness.send(&[to_self], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InvalidBeneficiary)
+    );
+
+    let no_window = harness.set_beneficiary_instruction(USER, harness.actor(HEIR), 0);
+    let err = harness.send(&[no_window], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InvalidBeneficiary)
+    );
+}
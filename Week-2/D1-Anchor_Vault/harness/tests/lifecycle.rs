@@ -0,0 +1,185 @@
+//! The basic life of a vault: `initialize`, `deposit`, `withdraw` and `close`.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const THIEF: usize = 1;
+const SESSION: usize = 1;
+const NOW: i64 = 1_700_000_000;
+const DAY: i64 = 24 * 60 * 60;
+
+#[tokio::test]
+async fn initialize_deposit_withdraw_close() {
+    let mut harness = Harness::start(program_test(), 1).await;
+    let user = harness.actor(USER);
+    let vault = harness.vault_address(USER);
+
+    harness.initialize(USER).await.unwrap();
+    let state = harness.vault_state(USER).await.unwrap();
+    assert_eq!(state.owner, user);
+    assert_eq!(state.creator, user);
+    // The vault PDA only comes into existence with the first deposit.
+    assert!(harness.account(vault).await.is_none());
+
+    harness.deposit(USER, 3 * LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(harness.lamports(vault).await, 3 * LAMPORTS_PER_SOL);
+
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(harness.lamports(vault).await, 2 * LAMPORTS_PER_SOL);
+
+    // Closing sweeps the rest of the vault and the state's rent back to the user.
+    let state_rent = harness.lamports(harness.state_address(USER)).await;
+    let before = harness.lamports(user).await;
+    harness.close(USER).await.unwrap();
+
+    assert!(harness.account(vault).await.is_none());
+    assert!(harness.vault_state(USER).await.is_none());
+    assert_eq!(
+        harness.lamports(user).await,
+        before + 2 * LAMPORTS_PER_SOL + state_rent
+    );
+}
+
+#[tokio::test]
+async fn initializing_twice_fails() {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    // The system program refuses to create the state account again (`AccountAlreadyInUse`).
+    let err = harness.initialize(USER).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(0));
+
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        LAMPORTS_PER_SOL
+    );
+    assert_eq!(harness.vault_index(USER).await.unwrap().vaults.len(), 1);
+}
+
+#[tokio::test]
+async fn other_users_cannot_withdraw_or_close() {
+    let mut harness = Harness::start(program_test(), 2).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 2 * LAMPORTS_PER_SOL).await.unwrap();
+
+    // The thief signs for the user's vault accounts.
+    let mut withdraw = harness.withdraw_instruction(USER, LAMPORTS_PER_SOL);
+    withdraw.accounts[0].pubkey = harness.actor(THIEF);
+    let err = harness.send(&[withdraw], &[THIEF]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    let mut close = harness.close_instruction(USER);
+    close.accounts[0].pubkey = harness.actor(THIEF);
+    let err = harness.send(&[close], &[THIEF]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    // Their own vault addresses are different PDAs, which hold nothing.
+    harness.initialize(THIEF).await.unwrap();
+    assert_ne!(harness.vault_address(THIEF), harness.vault_address(USER));
+    let err = harness.withdraw(THIEF, LAMPORTS_PER_SOL).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InsufficientFunds)
+    );
+
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        2 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn closing_an_empty_vault_only_returns_the_state_rent() {
+    let mut harness = Harness::start(program_test(), 1).await;
+    let user = harness.actor(USER);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    assert!(harness.account(harness.vault_address(USER)).await.is_none());
+
+    let state_rent = harness.lamports(harness.state_address(USER)).await;
+    let before = harness.lamports(user).await;
+    harness.close(USER).await.unwrap();
+
+    assert!(harness.vault_state(USER).await.is_none());
+    assert!(harness.vault_index(USER).await.unwrap().vaults.is_empty());
+    assert_eq!(harness.lamports(user).await, before + state_rent);
+
+    // The same vault can be opened again afterwards.
+    harness.initialize(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn a_reopened_vault_ignores_what_the_closed_one_left_behind() {
+    let mut harness = Harness::start(program_test(), 2).await;
+    harness.warp_to_timestamp(NOW);
+    let recipient = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let session_key = harness.actor(SESSION);
+
+    // A stream with a period already due, a session and an allowed destination, all left open.
+    harness.initialize(USER).await.unwrap();
+    assert_eq!(harness.vault_state(USER).await.unwrap().generation, 1);
+    let stream =
+        harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, DAY, NOW - DAY);
+    let session =
+        harness.create_session_instruction(USER, session_key, NOW + DAY, LAMPORTS_PER_SOL);
+    let allowlist = harness.initialize_allowlist_instruction(USER, 0);
+    let add = harness.add_destination_instruction(USER, destination);
+    harness
+        .send(&[stream, session, allowlist, add], &[USER])
+        .await
+        .unwrap();
+    harness.close(USER).await.unwrap();
+
+    // The same id derives the same addresses, but the new vault is another generation.
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 5 * LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(harness.vault_state(USER).await.unwrap().generation, 2);
+
+    let crank = harness.crank_stream_instruction(USER, recipient);
+    let err = harness.send(&[crank], &[]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StaleAccount));
+
+    let withdraw = harness.session_withdraw_instruction(SESSION, USER, LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[SESSION]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StaleAccount));
+
+    let withdraw = harness.withdraw_to_instruction(USER, destination, LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StaleAccount));
+
+    assert_eq!(harness.lamports(recipient).await, 0);
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        5 * LAMPORTS_PER_SOL
+    );
+
+    // The owner can set them up again for the new vault, starting from scratch.
+    let stream = harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, DAY, NOW);
+    let allowlist = harness.initialize_allowlist_instruction(USER, 0);
+    harness.send(&[stream, allowlist], &[USER]).await.unwrap();
+    assert_eq!(
+        harness.stream(USER, &recipient).await.unwrap().generation,
+        2
+    );
+    assert!(harness
+        .allowlist(USER)
+        .await
+        .unwrap()
+        .destinations
+        .is_empty());
+
+    // A stream still in use is not replaced.
+    let stream = harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, DAY, NOW);
+    let err = harness.send(&[stream], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::AccountInUse));
+
+    harness.warp_to_timestamp(NOW + DAY);
+    let crank = harness.crank_stream_instruction(USER, recipient);
+    harness.send(&[crank], &[]).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, LAMPORTS_PER_SOL);
+}
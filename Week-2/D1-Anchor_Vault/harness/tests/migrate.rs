@@ -0,0 +1,153 @@
+//! `migrate` upgrades vault states written before the account layout was versioned.
+
+use anchor_lang::{error::ErrorCode, AccountDeserialize, AnchorSerialize, Discriminator};
+use d1_anchor_vault::{LegacyVaultState, VaultError, VaultState, VAULT_STATE_VERSION};
+use solana_sdk::{
+    account::AccountSharedData, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, system_program,
+};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const OUTSIDER: usize = 1;
+
+fn legacy_vault_address(harness: &Harness) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"vault", harness.legacy_state_address(USER).as_ref()],
+        &d1_anchor_vault::ID,
+    )
+}
+
+/// Writes `USER`'s vault as it was before versioning: a two-bump state at the per-user address
+/// and a vault PDA holding `balance`.
+async fn legacy_vault(balance: u64) -> Harness {
+    let mut harness = Harness::start(program_test(), 2).await;
+    let (state, state_bump) = Pubkey::find_program_address(
+        &[b"state", harness.actor(USER).as_ref()],
+        &d1_anchor_vault::ID,
+    );
+    let (vault, vault_bump) = legacy_vault_address(&harness);
+
+    let legacy = LegacyVaultState {
+        vault_bump,
+        state_bump,
+    };
+    let mut data = VaultState::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data).unwrap();
+    assert_eq!(data.len(), LegacyVaultState::LEN);
+
+    let rent = harness.rent().await;
+    let mut account = AccountSharedData::new(
+        rent.minimum_balance(data.len()),
+        data.len(),
+        &d1_anchor_vault::ID,
+    );
+    account.set_data_from_slice(&data);
+    harness.context.set_account(&state, &account);
+
+    let account = AccountSharedData::new(balance, 0, &system_program::ID);
+    harness.context.set_account(&vault, &account);
+
+    harness
+}
+
+async fn migrated_state(harness: &mut Harness) -> VaultState {
+    let account = harness
+        .account(harness.legacy_state_address(USER))
+        .await
+        .unwrap();
+    VaultState::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn legacy_vault_migrates_in_place_and_keeps_working() {
+    let mut harness = legacy_vault(3 * LAMPORTS_PER_SOL).await;
+    let user = harness.actor(USER);
+    let state = harness.legacy_state_address(USER);
+    let (vault, vault_bump) = legacy_vault_address(&harness);
+
+    // Until migrated, the state cannot be read in the current layout.
+    let mut withdraw = harness.withdraw_instruction(USER, LAMPORTS_PER_SOL);
+    withdraw.accounts[1].pubkey = vault;
+    withdraw.accounts[2].pubkey = state;
+    let err = harness
+        .send(&[withdraw.clone()], &[USER])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(ErrorCode::AccountDidNotDeserialize as u32)
+    );
+
+    let migrate = harness.migrate_instruction(USER, "legacy");
+    harness.send(&[migrate], &[USER]).await.unwrap();
+
+    let account = harness.account(state).await.unwrap();
+    let rent = harness.rent().await;
+    assert_eq!(account.data.len(), VaultState::LEN);
+    assert!(rent.is_exempt(account.lamports, account.data.len()));
+
+    let migrated = migrated_state(&mut harness).await;
+    assert_eq!(migrated.version, VAULT_STATE_VERSION);
+    assert!(migrated.legacy_address);
+    assert_eq!(migrated.vault_bump, vault_bump);
+    assert_eq!(migrated.owner, user);
+    assert_eq!(migrated.creator, user);
+    assert_eq!(migrated.unlock_at, 0);
+    assert!(migrated.guardians.is_empty());
+    assert_eq!(migrated.total_deposited, 3 * LAMPORTS_PER_SOL);
+
+    let index = harness.vault_index(USER).await.unwrap();
+    assert_eq!(index.vaults.len(), 1);
+    assert_eq!(index.vaults[0].vault_state, state);
+    assert_eq!(index.vaults[0].name, "legacy");
+
+    // The seeds of every instruction still find the state at its legacy address.
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+    assert_eq!(harness.lamports(vault).await, 2 * LAMPORTS_PER_SOL);
+
+    let mut close = harness.close_instruction(USER);
+    close.accounts[1].pubkey = vault;
+    close.accounts[2].pubkey = state;
+    harness.send(&[close], &[USER]).await.unwrap();
+    assert!(harness.account(state).await.is_none());
+    assert!(harness.vault_index(USER).await.unwrap().vaults.is_empty());
+}
+
+#[tokio::test]
+async fn migrating_twice_fails() {
+    let mut harness = legacy_vault(LAMPORTS_PER_SOL).await;
+
+    let migrate = harness.migrate_instruction(USER, "legacy");
+    harness.send(&[migrate], &[USER]).await.unwrap();
+
+    let migrate = harness.migrate_instruction(USER, "again");
+    let err = harness.send(&[migrate], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::AlreadyMigrated));
+
+    // Vaults created since versioning need no migration either.
+    harness.initialize(USER).await.unwrap();
+    let mut migrate = harness.migrate_instruction(USER, "main");
+    migrate.accounts[1].pubkey = harness.state_address(USER);
+    migrate.accounts[2].pubkey = harness.vault_address(USER);
+    let err = harness.send(&[migrate], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::AlreadyMigrated));
+}
+
+#[tokio::test]
+async fn only_the_user_can_migrate_their_vault() {
+    let mut harness = legacy_vault(LAMPORTS_PER_SOL).await;
+    let (vault, _) = legacy_vault_address(&harness);
+
+    // The legacy layout has no owner, the signer's key has to derive the state address.
+    let mut migrate = harness.migrate_instruction(OUTSIDER, "stolen");
+    migrate.accounts[1].pubkey = harness.legacy_state_address(USER);
+    migrate.accounts[2].pubkey = vault;
+    let err = harness.send(&[migrate], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(ErrorCode::ConstraintSeeds as u32));
+
+    let account = harness
+        .account(harness.legacy_state_address(USER))
+        .await
+        .unwrap();
+    assert_eq!(account.data.len(), LegacyVaultState::LEN);
+}
@@ -0,0 +1,153 @@
+//! A multisig vault only pays out proposals approved by `threshold` of its owners.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const OWNERS: [usize; 3] = [0, 1, 2];
+const OUTSIDER: usize = 3;
+const SEED: u64 = 42;
+const BALANCE: u64 = 10 * LAMPORTS_PER_SOL;
+
+struct Setup {
+    harness: Harness,
+    multisig: Pubkey,
+    vault: Pubkey,
+}
+
+/// A funded 2-of-3 multisig created by the first owner.
+async fn multisig() -> Setup {
+    let mut harness = Harness::start(program_test(), 4).await;
+
+    let owners = OWNERS.iter().map(|&owner| harness.actor(owner)).collect();
+    let initialize = harness.initialize_multisig_instruction(OWNERS[0], SEED, owners, 2);
+    harness.send(&[initialize], &[OWNERS[0]]).await.unwrap();
+
+    let multisig = harness.multisig_address(OWNERS[0], SEED);
+    let vault = harness.multisig_vault_address(&multisig);
+
+    // Anyone can fund the vault.
+    let deposit = harness.multisig_deposit_instruction(OUTSIDER, multisig, BALANCE);
+    harness.send(&[deposit], &[OUTSIDER]).await.unwrap();
+
+    Setup {
+        harness,
+        multisig,
+        vault,
+    }
+}
+
+#[tokio::test]
+async fn withdrawal_executes_once_the_threshold_is_reached() {
+    let Setup {
+        mut harness,
+        multisig,
+        vault,
+    } = multisig().await;
+    let destination = Pubkey::new_unique();
+
+    let propose = harness.propose_withdrawal_instruction(
+        OWNERS[1],
+        multisig,
+        0,
+        destination,
+        LAMPORTS_PER_SOL,
+    );
+    harness.send(&[propose], &[OWNERS[1]]).await.unwrap();
+    assert_eq!(harness.multisig(multisig).await.unwrap().proposal_count, 1);
+
+    let execute = harness.execute_proposal_instruction(multisig, 0, destination, OWNERS[1]);
+    let err = harness.send(&[execute], &[]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::NotEnoughApprovals)
+    );
+
+    let approve = harness.approve_proposal_instruction(OWNERS[1], multisig, 0);
+    let err = harness.send(&[approve], &[OWNERS[1]]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::AlreadyApproved));
+
+    let approve = harness.approve_proposal_instruction(OWNERS[2], multisig, 0);
+    harness.send(&[approve], &[OWNERS[2]]).await.unwrap();
+
+    let execute = harness.execute_proposal_instruction(multisig, 0, destination, OWNERS[1]);
+    harness.send(&[execute], &[]).await.unwrap();
+
+    assert_eq!(harness.lamports(destination).await, LAMPORTS_PER_SOL);
+    assert_eq!(harness.lamports(vault).await, BALANCE - LAMPORTS_PER_SOL);
+    assert!(harness
+        .account(harness.proposal_address(&multisig, 0))
+        .await
+        .is_none());
+
+    // An executed proposal cannot be executed again.
+    let execute = harness.execute_proposal_instruction(multisig, 0, destination, OWNERS[1]);
+    assert!(harness.send(&[execute], &[]).await.is_err());
+    assert_eq!(harness.lamports(destination).await, LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn only_owners_propose_and_approve() {
+    let Setup {
+        mut harness,
+        multisig,
+        ..
+    } = multisig().await;
+    let destination = harness.actor(OUTSIDER);
+
+    let propose =
+        harness.propose_withdrawal_instruction(OUTSIDER, multisig, 0, destination, BALANCE);
+    let err = harness.send(&[propose], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotOwner));
+
+    let propose =
+        harness.propose_withdrawal_instruction(OWNERS[0], multisig, 0, destination, BALANCE);
+    harness.send(&[propose], &[OWNERS[0]]).await.unwrap();
+
+    let approve = harness.approve_proposal_instruction(OUTSIDER, multisig, 0);
+    let err = harness.send(&[approve], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotOwner));
+}
+
+#[tokio::test]
+async fn execution_pays_only_the_proposed_destination() {
+    let Setup {
+        mut harness,
+        multisig,
+        ..
+    } = multisig().await;
+    let destination = Pubkey::new_unique();
+
+    let propose =
+        harness.propose_withdrawal_instruction(OWNERS[0], multisig, 0, destination, BALANCE);
+    harness.send(&[propose], &[OWNERS[0]]).await.unwrap();
+    let approve = harness.approve_proposal_instruction(OWNERS[2], multisig, 0);
+    harness.send(&[approve], &[OWNERS[2]]).await.unwrap();
+
+    let thief = harness.actor(OUTSIDER);
+    let execute = harness.execute_proposal_instruction(multisig, 0, thief, OWNERS[0]);
+    assert!(harness.send(&[execute], &[]).await.is_err());
+
+    let execute = harness.execute_proposal_instruction(multisig, 0, destination, OWNERS[0]);
+    harness.send(&[execute], &[]).await.unwrap();
+    assert_eq!(harness.lamports(destination).await, BALANCE);
+}
+
+#[tokio::test]
+async fn owner_sets_are_validated() {
+    let mut harness = Harness::start(program_test(), 2).await;
+    let owner = harness.actor(0);
+
+    let invalid = [
+        (vec![owner], 0),
+        (vec![owner], 2),
+        (vec![owner, owner], 1),
+        ((0..11).map(|_| Pubkey::new_unique()).collect(), 1),
+    ];
+
+    for (owners, threshold) in invalid {
+        let initialize = harness.initialize_multisig_instruction(0, SEED, owners, threshold);
+        let err = harness.send(&[initialize], &[0]).await.unwrap_err();
+        assert_eq!(custom_error(&err), vault_error(VaultError::InvalidOwners));
+    }
+}
@@ -0,0 +1,119 @@
+//! A user can hold several named vaults side by side, listed in their vault index.
+
+use d1_anchor_vault::{VaultEntry, VaultError, MAX_NAME_LEN};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const RENT: u64 = 1;
+const SAVINGS: u64 = 2;
+
+async fn two_vaults() -> Harness {
+    let mut harness = Harness::start(program_test(), 1).await;
+
+    let rent = harness.initialize_vault_instruction(USER, RENT, "rent", None);
+    let savings = harness.initialize_vault_instruction(USER, SAVINGS, "savings", None);
+    harness.send(&[rent, savings], &[USER]).await.unwrap();
+    harness
+}
+
+fn entry(harness: &Harness, id: u64, name: &str) -> VaultEntry {
+    VaultEntry {
+        vault_state: harness.state_address_of(USER, id),
+        name: name.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn vaults_are_independent_and_indexed() {
+    let mut harness = two_vaults().await;
+
+    let index = harness.vault_index(USER).await.unwrap();
+    assert_eq!(index.user, harness.actor(USER));
+    assert_eq!(
+        index.vaults,
+        vec![
+            entry(&harness, RENT, "rent"),
+            entry(&harness, SAVINGS, "savings")
+        ]
+    );
+
+    let rent = harness.deposit_into_instruction(USER, USER, RENT, LAMPORTS_PER_SOL);
+    let savings = harness.deposit_into_instruction(USER, USER, SAVINGS, 5 * LAMPORTS_PER_SOL);
+    harness.send(&[rent, savings], &[USER]).await.unwrap();
+
+    let withdraw = harness.withdraw_from_instruction(USER, SAVINGS, 2 * LAMPORTS_PER_SOL);
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+
+    assert_eq!(
+        harness.lamports(harness.vault_address_of(USER, RENT)).await,
+        LAMPORTS_PER_SOL
+    );
+    assert_eq!(
+        harness
+            .lamports(harness.vault_address_of(USER, SAVINGS))
+            .await,
+        3 * LAMPORTS_PER_SOL
+    );
+
+    // Closing one vault leaves the other one, and its index entry, alone.
+    let close = harness.close_vault_instruction(USER, RENT);
+    harness.send(&[close], &[USER]).await.unwrap();
+
+    assert!(harness.vault_state_of(USER, RENT).await.is_none());
+    assert_eq!(
+        harness.vault_state_of(USER, SAVINGS).await.unwrap().id,
+        SAVINGS
+    );
+    assert_eq!(
+        harness.vault_index(USER).await.unwrap().vaults,
+        vec![entry(&harness, SAVINGS, "savings")]
+    );
+    assert_eq!(
+        harness
+            .lamports(harness.vault_address_of(USER, SAVINGS))
+            .await,
+        3 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn vault_ids_are_unique_per_user() {
+    let mut harness = two_vaults().await;
+
+    let again = harness.initialize_vault_instruction(USER, RENT, "rent again", None);
+    assert!(harness.send(&[again], &[USER]).await.is_err());
+
+    // A closed id is free to be used again.
+    let close = harness.close_vault_instruction(USER, RENT);
+    harness.send(&[close], &[USER]).await.unwrap();
+    let again = harness.initialize_vault_instruction(USER, RENT, "rent again", None);
+    harness.send(&[again], &[USER]).await.unwrap();
+
+    assert_eq!(
+        harness.vault_index(USER).await.unwrap().vaults,
+        vec![
+            entry(&harness, SAVINGS, "savings"),
+            entry(&harness, RENT, "rent again")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn vault_names_are_bounded() {
+    let mut harness = Harness::start(program_test(), 1).await;
+
+    let long_name = "x".repeat(MAX_NAME_LEN + 1);
+    for name in ["", long_name.as_str()] {
+        let initialize = harness.initialize_vault_instruction(USER, RENT, name, None);
+        let err = harness.send(&[initialize], &[USER]).await.unwrap_err();
+        assert_eq!(
+            custom_error(&err),
+            vault_error(VaultError::InvalidVaultName)
+        );
+    }
+
+    let longest = "x".repeat(MAX_NAME_LEN);
+    let initialize = harness.initialize_vault_instruction(USER, RENT, &longest, None);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+}
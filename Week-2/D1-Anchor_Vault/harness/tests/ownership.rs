@@ -0,0 +1,192 @@
+//! Vault ownership moves to a new wallet in two steps, without the vault changing address.
+
+use anchor_lang::error::ErrorCode;
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness, MAIN_VAULT};
+
+const CREATOR: usize = 0;
+const NEW_OWNER: usize = 1;
+const OUTSIDER: usize = 2;
+
+async fn funded_vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 3).await;
+    harness.initialize(CREATOR).await.unwrap();
+    harness
+        .deposit(CREATOR, 5 * LAMPORTS_PER_SOL)
+        .await
+        .unwrap();
+    harness
+}
+
+/// Hands the creator's vault over to `NEW_OWNER`.
+async fn hand_over(harness: &mut Harness) {
+    let propose = harness.propose_owner_instruction(CREATOR, harness.actor(NEW_OWNER));
+    harness.send(&[propose], &[CREATOR]).await.unwrap();
+
+    let accept = harness.accept_owner_instruction(NEW_OWNER, CREATOR, MAIN_VAULT, CREATOR);
+    harness.send(&[accept], &[NEW_OWNER]).await.unwrap();
+}
+
+#[tokio::test]
+async fn new_owner_takes_over_the_same_vault() {
+    let mut harness = funded_vault().await;
+    hand_over(&mut harness).await;
+
+    let state = harness.vault_state(CREATOR).await.unwrap();
+    assert_eq!(state.owner, harness.actor(NEW_OWNER));
+    assert_eq!(state.creator, harness.actor(CREATOR));
+    assert_eq!(state.pending_owner, Pubkey::default());
+
+    // The vault moved between the two indexes under its name.
+    assert!(harness
+        .vault_index(CREATOR)
+        .await
+        .unwrap()
+        .vaults
+        .is_empty());
+    let entries = harness.vault_index(NEW_OWNER).await.unwrap().vaults;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].vault_state, harness.state_address(CREATOR));
+    assert_eq!(entries[0].name, "main");
+
+    // The previous owner lost access, the new one withdraws from the unchanged vault address.
+    let err = harness
+        .withdraw(CREATOR, LAMPORTS_PER_SOL)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    let mut withdraw = harness.withdraw_instruction(CREATOR, LAMPORTS_PER_SOL);
+    withdraw.accounts[0].pubkey = harness.actor(NEW_OWNER);
+    harness.send(&[withdraw], &[NEW_OWNER]).await.unwrap();
+    assert_eq!(
+        harness.lamports(harness.vault_address(CREATOR)).await,
+        4 * LAMPORTS_PER_SOL
+    );
+
+    let mut close = harness.close_instruction(CREATOR);
+    close.accounts[0].pubkey = harness.actor(NEW_OWNER);
+    close.accounts[3].pubkey = harness.index_address(NEW_OWNER);
+    harness.send(&[close], &[NEW_OWNER]).await.unwrap();
+    assert!(harness.vault_state(CREATOR).await.is_none());
+    assert!(harness
+        .vault_index(NEW_OWNER)
+        .await
+        .unwrap()
+        .vaults
+        .is_empty());
+}
+
+#[tokio::test]
+async fn only_the_proposed_owner_can_accept() {
+    let mut harness = funded_vault().await;
+
+    // Nothing to accept before a proposal.
+    let accept = harness.accept_owner_instruction(NEW_OWNER, CREATOR, MAIN_VAULT, CREATOR);
+    let err = harness.send(&[accept], &[NEW_OWNER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotPendingOwner));
+
+    let propose = harness.propose_owner_instruction(CREATOR, harness.actor(NEW_OWNER));
+    harness.send(&[propose], &[CREATOR]).await.unwrap();
+
+    let accept = harness.accept_owner_instruction(OUTSIDER, CREATOR, MAIN_VAULT, CREATOR);
+    let err = harness.send(&[accept], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotPendingOwner));
+
+    // The owner can withdraw the offer before it is accepted.
+    let cancel = harness.propose_owner_instruction(CREATOR, Pubkey::default());
+    harness.send(&[cancel], &[CREATOR]).await.unwrap();
+
+    let accept = harness.accept_owner_instruction(NEW_OWNER, CREATOR, MAIN_VAULT, CREATOR);
+    let err = harness.send(&[accept], &[NEW_OWNER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotPendingOwner));
+
+    let to_self = harness.propose_owner_instruction(CREATOR, harness.actor(CREATOR));
+    let err = harness.send(&[to_self], &[CREATOR]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::InvalidNewOwner));
+
+    assert_eq!(
+        harness.vault_state(CREATOR).await.unwrap().owner,
+        harness.actor(CREATOR)
+    );
+}
+
+#[tokio::test]
+async fn deposits_name_the_current_owner() {
+    let mut harness = funded_vault().await;
+    hand_over(&mut harness).await;
+
+    let deposit = harness.deposit_for_instruction(OUTSIDER, CREATOR, LAMPORTS_PER_SOL);
+    let err = harness.send(&[deposit], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(ErrorCode::ConstraintHasOne as u32));
+
+    let mut deposit = harness.deposit_for_instruction(OUTSIDER, CREATOR, LAMPORTS_PER_SOL);
+    deposit.accounts[1].pubkey = harness.actor(NEW_OWNER);
+    harness.send(&[deposit], &[OUTSIDER]).await.unwrap();
+    assert_eq!(
+        harness.lamports(harness.vault_address(CREATOR)).await,
+        6 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn the_previous_owners_streams_and_sessions_stop_working() {
+    let mut harness = funded_vault().await;
+    let now = harness.clock().await.unix_timestamp;
+    let recipient = Pubkey::new_unique();
+    let session_key = harness.actor(OUTSIDER);
+
+    let stream = harness.create_stream_instruction(CREATOR, recipient, 1_000, 1, now - 10);
+    let session = harness.create_session_instruction(CREATOR, session_key, now + 1_000, 1_000);
+    harness.send(&[stream, session], &[CREATOR]).await.unwrap();
+    let withdraw = harness.session_withdraw_instruction(OUTSIDER, CREATOR, 100);
+    harness.send(&[withdraw], &[OUTSIDER]).await.unwrap();
+
+    hand_over(&mut harness).await;
+    assert_eq!(harness.vault_state(CREATOR).await.unwrap().generation, 2);
+
+    let crank = harness.crank_stream_instruction(CREATOR, recipient);
+    let err = harness.send(&[crank], &[]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StaleAccount));
+
+    let mut withdraw = harness.session_withdraw_instruction(OUTSIDER, CREATOR, 100);
+    withdraw.accounts[1].pubkey = harness.actor(NEW_OWNER);
+    let err = harness.send(&[withdraw], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StaleAccount));
+    assert_eq!(harness.lamports(recipient).await, 0);
+}
+
+#[tokio::test]
+async fn every_handover_draws_a_new_generation_from_the_creator() {
+    let mut harness = funded_vault().await;
+    hand_over(&mut harness).await;
+
+    // Between two other wallets, the creator's index has to come along.
+    let mut propose = harness.propose_owner_instruction(CREATOR, harness.actor(OUTSIDER));
+    propose.accounts[0].pubkey = harness.actor(NEW_OWNER);
+    harness.send(&[propose], &[NEW_OWNER]).await.unwrap();
+
+    let mut accept = harness.accept_owner_instruction(OUTSIDER, CREATOR, MAIN_VAULT, NEW_OWNER);
+    accept.accounts[4].pubkey = d1_anchor_vault::ID;
+    let err = harness.send(&[accept], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InvalidCreatorIndex)
+    );
+
+    let accept = harness.accept_owner_instruction(OUTSIDER, CREATOR, MAIN_VAULT, NEW_OWNER);
+    harness.send(&[accept], &[OUTSIDER]).await.unwrap();
+
+    // Back to the creator, whose own index is already part of the handover.
+    let mut propose = harness.propose_owner_instruction(CREATOR, harness.actor(CREATOR));
+    propose.accounts[0].pubkey = harness.actor(OUTSIDER);
+    harness.send(&[propose], &[OUTSIDER]).await.unwrap();
+    let accept = harness.accept_owner_instruction(CREATOR, CREATOR, MAIN_VAULT, OUTSIDER);
+    harness.send(&[accept], &[CREATOR]).await.unwrap();
+
+    let state = harness.vault_state(CREATOR).await.unwrap();
+    assert_eq!(state.owner, harness.actor(CREATOR));
+    assert_eq!(state.generation, 4);
+    assert_eq!(harness.vault_index(CREATOR).await.unwrap().opened, 4);
+}
@@ -0,0 +1,210 @@
+//! The program admin can pause new vaults, pools and multisigs and every deposit, but never stop
+//! users from exiting.
+
+use anchor_lang::error::ErrorCode;
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness, TOKEN_PROGRAM_ID};
+
+const USER: usize = 0;
+const NEW_ADMIN: usize = 1;
+const OUTSIDER: usize = 2;
+const SEED: u64 = 7;
+
+async fn paused(harness: &mut Harness, paused: bool) {
+    let pause = harness.set_paused_instruction(harness.admin(), paused);
+    harness.send(&[pause], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pause_blocks_new_funds_but_not_exits() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 3 * LAMPORTS_PER_SOL).await.unwrap();
+
+    paused(&mut harness, true).await;
+    assert!(harness.config().await.unwrap().paused);
+
+    let err = harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+
+    let err = harness.initialize(OUTSIDER).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+
+    // Users can always get their funds out.
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness.close(USER).await.unwrap();
+    assert!(harness.vault_state(USER).await.is_none());
+
+    paused(&mut harness, false).await;
+    harness.initialize(OUTSIDER).await.unwrap();
+    harness.deposit(OUTSIDER, LAMPORTS_PER_SOL).await.unwrap();
+}
+
+#[tokio::test]
+async fn only_the_admin_can_pause() {
+    let mut harness = Harness::start(program_test(), 3).await;
+
+    let pause = harness.set_paused_instruction(harness.actor(OUTSIDER), true);
+    let err = harness.send(&[pause], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotAdmin));
+    assert!(!harness.config().await.unwrap().paused);
+
+    // There is only ever one config, and with it one admin.
+    let initialize = harness.initialize_config_instruction(harness.admin());
+    let err = harness.send(&[initialize], &[]).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(0));
+}
+
+#[tokio::test]
+async fn the_upgrade_authority_sets_up_the_config_before_any_vault() {
+    let mut harness = Harness::start_unconfigured(program_test(), 3).await;
+
+    // Until the config exists, no vault can be opened (`AccountNotInitialized`).
+    let err = harness.initialize(USER).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(ErrorCode::AccountNotInitialized as u32)
+    );
+
+    // Getting there first is not enough to become admin.
+    let initialize = harness.initialize_config_instruction(harness.actor(OUTSIDER));
+    let err = harness.send(&[initialize], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::NotUpgradeAuthority)
+    );
+    assert!(harness.config().await.is_none());
+
+    let initialize = harness.initialize_config_instruction(harness.admin());
+    harness.send(&[initialize], &[]).await.unwrap();
+    assert_eq!(harness.config().await.unwrap().admin, harness.admin());
+
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+}
+
+#[tokio::test]
+async fn admin_rotation_takes_two_steps() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    let admin = harness.admin();
+    let new_admin = harness.actor(NEW_ADMIN);
+
+    let propose = harness.propose_admin_instruction(admin, new_admin);
+    harness.send(&[propose], &[]).await.unwrap();
+
+    // Until the rotation is accepted, the old admin stays in charge.
+    let pause = harness.set_paused_instruction(new_admin, true);
+    let err = harness.send(&[pause], &[NEW_ADMIN]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotAdmin));
+
+    let accept = harness.accept_admin_instruction(harness.actor(OUTSIDER));
+    let err = harness.send(&[accept], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotPendingAdmin));
+
+    let accept = harness.accept_admin_instruction(new_admin);
+    harness.send(&[accept], &[NEW_ADMIN]).await.unwrap();
+
+    let config = harness.config().await.unwrap();
+    assert_eq!(config.admin, new_admin);
+    assert_eq!(config.pending_admin, Pubkey::default());
+
+    let pause = harness.set_paused_instruction(admin, true);
+    let err = harness.send(&[pause], &[]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotAdmin));
+
+    let pause = harness.set_paused_instruction(new_admin, true);
+    harness.send(&[pause], &[NEW_ADMIN]).await.unwrap();
+    assert!(harness.config().await.unwrap().paused);
+}
+
+#[tokio::test]
+async fn pause_blocks_token_deposits_but_not_token_withdrawals() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    let mint = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    harness.mint_to(mint, USER, 1_000).await.unwrap();
+    harness.initialize(USER).await.unwrap();
+    let deposit = harness.deposit_tokens_instruction(USER, mint, 500);
+    harness.send(&[deposit], &[USER]).await.unwrap();
+
+    paused(&mut harness, true).await;
+
+    let deposit = harness.deposit_tokens_instruction(USER, mint, 500);
+    let err = harness.send(&[deposit], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+
+    let withdraw = harness.withdraw_tokens_instruction(USER, mint, 500);
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pause_blocks_new_pools() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    paused(&mut harness, true).await;
+
+    let initialize = harness.initialize_pool_instruction(USER, SEED);
+    let err = harness.send(&[initialize], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+    assert!(harness
+        .pool(harness.pool_address(USER, SEED))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn pause_blocks_pool_deposits_but_not_redemptions() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    let initialize = harness.initialize_pool_instruction(USER, SEED);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    let pool = harness.pool_address(USER, SEED);
+    let deposit = harness.pool_deposit_instruction(OUTSIDER, pool, LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[OUTSIDER]).await.unwrap();
+
+    paused(&mut harness, true).await;
+
+    let deposit = harness.pool_deposit_instruction(OUTSIDER, pool, LAMPORTS_PER_SOL);
+    let err = harness.send(&[deposit], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+
+    let shares = harness
+        .token_balance(harness.shares_address(OUTSIDER, &pool))
+        .await;
+    let withdraw = harness.pool_withdraw_instruction(OUTSIDER, pool, shares);
+    harness.send(&[withdraw], &[OUTSIDER]).await.unwrap();
+}
+
+#[tokio::test]
+async fn pause_blocks_new_multisigs() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    paused(&mut harness, true).await;
+
+    let owners = vec![harness.actor(USER), harness.actor(OUTSIDER)];
+    let initialize = harness.initialize_multisig_instruction(USER, SEED, owners, 1);
+    let err = harness.send(&[initialize], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+    assert!(harness
+        .multisig(harness.multisig_address(USER, SEED))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn pause_blocks_multisig_deposits() {
+    let mut harness = Harness::start(program_test(), 3).await;
+    let owners = vec![harness.actor(USER), harness.actor(OUTSIDER)];
+    let initialize = harness.initialize_multisig_instruction(USER, SEED, owners, 1);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    let multisig = harness.multisig_address(USER, SEED);
+
+    paused(&mut harness, true).await;
+
+    let deposit = harness.multisig_deposit_instruction(OUTSIDER, multisig, LAMPORTS_PER_SOL);
+    let err = harness.send(&[deposit], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ProgramPaused));
+    assert_eq!(
+        harness
+            .lamports(harness.multisig_vault_address(&multisig))
+            .await,
+        0
+    );
+}
@@ -0,0 +1,146 @@
+//! A pool mints shares proportional to each deposit and redeems them for the lamports they are worth.
+
+use d1_anchor_vault::{VaultError, VIRTUAL_SHARES};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, system_instruction};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const CREATOR: usize = 0;
+const ALICE: usize = 1;
+const BOB: usize = 2;
+const SEED: u64 = 7;
+
+struct Setup {
+    harness: Harness,
+    pool: Pubkey,
+    vault: Pubkey,
+}
+
+async fn pool() -> Setup {
+    let mut harness = Harness::start(program_test(), 3).await;
+
+    let initialize = harness.initialize_pool_instruction(CREATOR, SEED);
+    harness.send(&[initialize], &[CREATOR]).await.unwrap();
+
+    let pool = harness.pool_address(CREATOR, SEED);
+    let vault = harness.pool_vault_address(&pool);
+
+    Setup {
+        harness,
+        pool,
+        vault,
+    }
+}
+
+async fn deposit(harness: &mut Harness, depositor: usize, pool: Pubkey, amount: u64) {
+    let deposit = harness.pool_deposit_instruction(depositor, pool, amount);
+    harness.send(&[deposit], &[depositor]).await.unwrap();
+}
+
+/// Redeems `shares` and returns the lamports paid out for them.
+async fn withdraw(harness: &mut Harness, depositor: usize, pool: Pubkey, shares: u64) -> u64 {
+    let before = harness.lamports(harness.actor(depositor)).await;
+    let withdraw = harness.pool_withdraw_instruction(depositor, pool, shares);
+    harness.send(&[withdraw], &[depositor]).await.unwrap();
+    harness.lamports(harness.actor(depositor)).await - before
+}
+
+#[tokio::test]
+async fn shares_are_proportional_to_deposits() {
+    let Setup {
+        mut harness,
+        pool,
+        vault,
+    } = pool().await;
+    let reserve = harness.rent().await.minimum_balance(0);
+    assert_eq!(harness.lamports(vault).await, reserve);
+
+    deposit(&mut harness, ALICE, pool, LAMPORTS_PER_SOL).await;
+    deposit(&mut harness, BOB, pool, 3 * LAMPORTS_PER_SOL).await;
+
+    let alice_shares = harness
+        .token_balance(harness.shares_address(ALICE, &pool))
+        .await;
+    let bob_shares = harness
+        .token_balance(harness.shares_address(BOB, &pool))
+        .await;
+    assert_eq!(alice_shares, LAMPORTS_PER_SOL * VIRTUAL_SHARES);
+    assert_eq!(bob_shares, 3 * alice_shares);
+    assert_eq!(
+        harness.pool(pool).await.unwrap().total_assets,
+        4 * LAMPORTS_PER_SOL
+    );
+
+    // Nobody gets out more than they put in, and rounding costs at most a lamport.
+    let alice_out = withdraw(&mut harness, ALICE, pool, alice_shares).await;
+    let bob_out = withdraw(&mut harness, BOB, pool, bob_shares).await;
+    assert!(alice_out <= LAMPORTS_PER_SOL && alice_out + 1 >= LAMPORTS_PER_SOL);
+    assert!(bob_out <= 3 * LAMPORTS_PER_SOL && bob_out + 1 >= 3 * LAMPORTS_PER_SOL);
+
+    // Whatever rounding left behind stays in the pool, on top of the untouchable reserve.
+    let remainder = harness.pool(pool).await.unwrap().total_assets;
+    assert_eq!(harness.lamports(vault).await, reserve + remainder);
+}
+
+#[tokio::test]
+async fn rounding_favours_the_pool() {
+    let Setup {
+        mut harness, pool, ..
+    } = pool().await;
+    deposit(&mut harness, ALICE, pool, LAMPORTS_PER_SOL).await;
+
+    // Just under two lamports worth of shares redeems a single lamport.
+    assert_eq!(
+        withdraw(&mut harness, ALICE, pool, 2 * VIRTUAL_SHARES - 1).await,
+        1
+    );
+
+    // Less than a lamport worth of shares redeems nothing, so it is rejected.
+    let redeem = harness.pool_withdraw_instruction(ALICE, pool, VIRTUAL_SHARES - 1);
+    let err = harness.send(&[redeem], &[ALICE]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::SharesRoundToZero)
+    );
+
+    // An empty deposit never gets as far as rounding.
+    let deposit = harness.pool_deposit_instruction(BOB, pool, 0);
+    let err = harness.send(&[deposit], &[BOB]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+}
+
+#[tokio::test]
+async fn donations_cannot_inflate_the_share_price() {
+    let Setup {
+        mut harness,
+        pool,
+        vault,
+    } = pool().await;
+    let attacker = ALICE;
+    let victim = BOB;
+
+    // The classic attack: own the whole pool with a dust deposit, then send lamports straight
+    // to the vault so the next depositor's shares round down to almost nothing.
+    deposit(&mut harness, attacker, pool, 1).await;
+    let donation =
+        system_instruction::transfer(&harness.actor(attacker), &vault, 10 * LAMPORTS_PER_SOL);
+    harness.send(&[donation], &[attacker]).await.unwrap();
+
+    deposit(&mut harness, victim, pool, LAMPORTS_PER_SOL).await;
+    let victim_shares = harness
+        .token_balance(harness.shares_address(victim, &pool))
+        .await;
+    assert_eq!(victim_shares, LAMPORTS_PER_SOL * VIRTUAL_SHARES);
+
+    let victim_out = withdraw(&mut harness, victim, pool, victim_shares).await;
+    assert!(victim_out + 1 >= LAMPORTS_PER_SOL);
+
+    // The donation is not part of `total_assets`, so the attacker cannot get it back either.
+    let attacker_shares = harness
+        .token_balance(harness.shares_address(attacker, &pool))
+        .await;
+    assert_eq!(
+        withdraw(&mut harness, attacker, pool, attacker_shares).await,
+        1
+    );
+    assert!(harness.lamports(vault).await > 10 * LAMPORTS_PER_SOL);
+}
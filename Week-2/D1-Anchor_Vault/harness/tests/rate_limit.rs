@@ -0,0 +1,137 @@
+//! Withdrawals are capped per window, and loosening the cap only takes effect after a delay.
+
+use d1_anchor_vault::{VaultError, CAP_CHANGE_DELAY, WITHDRAW_WINDOW};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const NOW: i64 = 1_700_000_000;
+const CAP: u64 = 2 * LAMPORTS_PER_SOL;
+
+async fn capped_vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 10 * LAMPORTS_PER_SOL).await.unwrap();
+
+    let cap = harness.set_withdraw_cap_instruction(USER, CAP);
+    harness.send(&[cap], &[USER]).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn withdrawals_are_capped_per_window() {
+    let mut harness = capped_vault().await;
+
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness.withdraw(USER, LAMPORTS_PER_SOL / 2).await.unwrap();
+
+    let err = harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    // The rest of the allowance is still there.
+    harness.withdraw(USER, LAMPORTS_PER_SOL / 2).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + WITHDRAW_WINDOW);
+    harness.withdraw(USER, CAP).await.unwrap();
+}
+
+#[tokio::test]
+async fn close_counts_against_the_cap() {
+    let mut harness = capped_vault().await;
+
+    let err = harness.close(USER).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    for day in 0..4 {
+        harness.warp_to_timestamp(NOW + day * WITHDRAW_WINDOW);
+        harness.withdraw(USER, CAP).await.unwrap();
+    }
+
+    harness.warp_to_timestamp(NOW + 4 * WITHDRAW_WINDOW);
+    harness.close(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn raising_the_cap_is_delayed() {
+    let mut harness = capped_vault().await;
+
+    let raise = harness.set_withdraw_cap_instruction(USER, 10 * LAMPORTS_PER_SOL);
+    harness.send(&[raise], &[USER]).await.unwrap();
+
+    let state = harness.vault_state(USER).await.unwrap();
+    assert_eq!(state.withdraw_cap, CAP);
+    assert_eq!(state.pending_cap, 10 * LAMPORTS_PER_SOL);
+    assert_eq!(state.pending_cap_at, NOW + CAP_CHANGE_DELAY);
+
+    let err = harness
+        .withdraw(USER, 3 * LAMPORTS_PER_SOL)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    harness.warp_to_timestamp(NOW + CAP_CHANGE_DELAY);
+    harness.withdraw(USER, 3 * LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().withdraw_cap,
+        10 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn removing_the_cap_is_delayed() {
+    let mut harness = capped_vault().await;
+
+    let remove = harness.set_withdraw_cap_instruction(USER, 0);
+    harness.send(&[remove], &[USER]).await.unwrap();
+
+    let err = harness
+        .withdraw(USER, 3 * LAMPORTS_PER_SOL)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    harness.warp_to_timestamp(NOW + CAP_CHANGE_DELAY);
+    harness.close(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn lowering_the_cap_is_immediate_and_cancels_a_raise() {
+    let mut harness = capped_vault().await;
+
+    let raise = harness.set_withdraw_cap_instruction(USER, 10 * LAMPORTS_PER_SOL);
+    harness.send(&[raise], &[USER]).await.unwrap();
+
+    let lower = harness.set_withdraw_cap_instruction(USER, LAMPORTS_PER_SOL);
+    harness.send(&[lower], &[USER]).await.unwrap();
+
+    let state = harness.vault_state(USER).await.unwrap();
+    assert_eq!(state.withdraw_cap, LAMPORTS_PER_SOL);
+    assert_eq!(state.pending_cap_at, 0);
+
+    let err = harness.withdraw(USER, CAP).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    // The cancelled raise never lands.
+    harness.warp_to_timestamp(NOW + CAP_CHANGE_DELAY);
+    let err = harness.withdraw(USER, CAP).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+}
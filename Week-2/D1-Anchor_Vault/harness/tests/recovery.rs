@@ -0,0 +1,263 @@
+//! Guardians can move a vault to a new key after a waiting period, unless the owner cancels.
+
+use d1_anchor_vault::{VaultError, RECOVERY_DELAY};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const GUARDIANS: [usize; 3] = [1, 2, 3];
+const OUTSIDER: usize = 4;
+const NOW: i64 = 1_700_000_000;
+const BALANCE: u64 = 5 * LAMPORTS_PER_SOL;
+
+/// A funded vault guarded by three guardians, two of which must approve a recovery.
+async fn guarded_vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 5).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, BALANCE).await.unwrap();
+
+    let guardians = GUARDIANS.iter().map(|&g| harness.actor(g)).collect();
+    let set = harness.set_guardians_instruction(USER, guardians, 2);
+    harness.send(&[set], &[USER]).await.unwrap();
+    harness
+}
+
+async fn propose(harness: &mut Harness, guardian: usize, new_owner: Pubkey) {
+    let propose = harness.propose_recovery_instruction(guardian, USER, new_owner);
+    harness.send(&[propose], &[guardian]).await.unwrap();
+}
+
+async fn approve(harness: &mut Harness, guardian: usize) -> Result<(), Option<u32>> {
+    let approve = harness.approve_recovery_instruction(guardian, USER);
+    harness
+        .send(&[approve], &[guardian])
+        .await
+        .map_err(|err| custom_error(&err))
+}
+
+async fn execute(harness: &mut Harness, new_owner: Pubkey) -> Result<(), Option<u32>> {
+    let execute = harness.execute_recovery_instruction(USER, new_owner, GUARDIANS[0]);
+    harness
+        .send(&[execute], &[])
+        .await
+        .map_err(|err| custom_error(&err))
+}
+
+#[tokio::test]
+async fn guardians_recover_the_vault_after_the_delay() {
+    let mut harness = guarded_vault().await;
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::NotEnoughApprovals))
+    );
+
+    approve(&mut harness, GUARDIANS[1]).await.unwrap();
+    assert_eq!(
+        approve(&mut harness, GUARDIANS[1]).await,
+        Err(vault_error(VaultError::AlreadyApproved))
+    );
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::RecoveryPending))
+    );
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    let state_rent = harness.lamports(harness.state_address(USER)).await;
+    execute(&mut harness, new_owner).await.unwrap();
+
+    assert_eq!(harness.lamports(new_owner).await, BALANCE + state_rent);
+    assert!(harness.account(harness.vault_address(USER)).await.is_none());
+    assert!(harness.account(harness.state_address(USER)).await.is_none());
+    assert!(harness
+        .account(harness.recovery_address(USER))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn owner_cancels_a_pending_recovery() {
+    let mut harness = guarded_vault().await;
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    approve(&mut harness, GUARDIANS[2]).await.unwrap();
+
+    // Only the owner can cancel.
+    let mut cancel = harness.cancel_recovery_instruction(USER, GUARDIANS[0]);
+    cancel.accounts[0].pubkey = harness.actor(OUTSIDER);
+    assert!(harness.send(&[cancel], &[OUTSIDER]).await.is_err());
+
+    let cancel = harness.cancel_recovery_instruction(USER, GUARDIANS[0]);
+    harness.send(&[cancel], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    assert!(execute(&mut harness, new_owner).await.is_err());
+    assert_eq!(harness.lamports(harness.vault_address(USER)).await, BALANCE);
+    assert_eq!(harness.lamports(new_owner).await, 0);
+}
+
+#[tokio::test]
+async fn only_guardians_take_part() {
+    let mut harness = guarded_vault().await;
+
+    let outsider = harness.propose_recovery_instruction(OUTSIDER, USER, Pubkey::new_unique());
+    let err = harness.send(&[outsider], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotGuardian));
+
+    propose(&mut harness, GUARDIANS[0], Pubkey::new_unique()).await;
+    assert_eq!(
+        approve(&mut harness, OUTSIDER).await,
+        Err(vault_error(VaultError::NotGuardian))
+    );
+}
+
+#[tokio::test]
+async fn changing_guardians_voids_pending_recoveries() {
+    let mut harness = guarded_vault().await;
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    approve(&mut harness, GUARDIANS[1]).await.unwrap();
+
+    let guardians = vec![harness.actor(GUARDIANS[1]), harness.actor(GUARDIANS[2])];
+    let set = harness.set_guardians_instruction(USER, guardians, 2);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    // The approvals were for the old set, even where the guardians stayed the same.
+    assert_eq!(
+        approve(&mut harness, GUARDIANS[2]).await,
+        Err(vault_error(VaultError::StaleRecovery))
+    );
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::StaleRecovery))
+    );
+
+    // The new guardians start over without waiting for the owner to throw it out.
+    propose(&mut harness, GUARDIANS[1], new_owner).await;
+    approve(&mut harness, GUARDIANS[2]).await.unwrap();
+}
+
+#[tokio::test]
+async fn guardians_replace_a_stale_recovery_without_the_owner() {
+    let mut harness = guarded_vault().await;
+    let stale_owner = Pubkey::new_unique();
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], stale_owner).await;
+
+    // The owner changes the guardians and then loses their key.
+    let guardians = vec![harness.actor(GUARDIANS[1]), harness.actor(GUARDIANS[2])];
+    let set = harness.set_guardians_instruction(USER, guardians, 2);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    propose(&mut harness, GUARDIANS[1], new_owner).await;
+    approve(&mut harness, GUARDIANS[2]).await.unwrap();
+
+    let recovery = harness.recovery(USER).await.unwrap();
+    assert_eq!(recovery.new_owner, new_owner);
+    assert_eq!(recovery.proposer, harness.actor(GUARDIANS[1]));
+    assert_eq!(recovery.approvals.len(), 2);
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    let state_rent = harness.lamports(harness.state_address(USER)).await;
+    let execute = harness.execute_recovery_instruction(USER, new_owner, GUARDIANS[1]);
+    harness.send(&[execute], &[]).await.unwrap();
+    assert_eq!(harness.lamports(new_owner).await, BALANCE + state_rent);
+    assert_eq!(harness.lamports(stale_owner).await, 0);
+}
+
+#[tokio::test]
+async fn turning_recovery_off_voids_pending_recoveries() {
+    let mut harness = guarded_vault().await;
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    approve(&mut harness, GUARDIANS[1]).await.unwrap();
+
+    // Without guardians the threshold drops to zero, which must not let the request through.
+    let set = harness.set_guardians_instruction(USER, vec![], 0);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::StaleRecovery))
+    );
+    assert_eq!(harness.lamports(harness.vault_address(USER)).await, BALANCE);
+    assert_eq!(harness.lamports(new_owner).await, 0);
+}
+
+#[tokio::test]
+async fn closing_the_vault_voids_pending_recoveries() {
+    let mut harness = guarded_vault().await;
+    let new_owner = Pubkey::new_unique();
+
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    approve(&mut harness, GUARDIANS[1]).await.unwrap();
+
+    // The owner closes the vault and opens it again under the same id and guardians.
+    harness.close(USER).await.unwrap();
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, BALANCE).await.unwrap();
+    let guardians = GUARDIANS.iter().map(|&g| harness.actor(g)).collect();
+    let set = harness.set_guardians_instruction(USER, guardians, 2);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::StaleRecovery))
+    );
+    assert_eq!(
+        approve(&mut harness, GUARDIANS[2]).await,
+        Err(vault_error(VaultError::StaleRecovery))
+    );
+
+    // A new request replaces the stale one, approvals and waiting period included.
+    propose(&mut harness, GUARDIANS[0], new_owner).await;
+    approve(&mut harness, GUARDIANS[1]).await.unwrap();
+    assert_eq!(
+        execute(&mut harness, new_owner).await,
+        Err(vault_error(VaultError::RecoveryPending))
+    );
+    assert_eq!(harness.lamports(harness.vault_address(USER)).await, BALANCE);
+}
+
+#[tokio::test]
+async fn guardian_sets_are_validated() {
+    let mut harness = guarded_vault().await;
+    let guardian = harness.actor(GUARDIANS[0]);
+
+    let invalid = [
+        (vec![guardian], 0),
+        (vec![guardian], 2),
+        (vec![guardian, guardian], 1),
+        (vec![harness.actor(USER)], 1),
+        (vec![], 1),
+        ((0..6).map(|_| Pubkey::new_unique()).collect(), 1),
+    ];
+
+    for (guardians, threshold) in invalid {
+        let set = harness.set_guardians_instruction(USER, guardians, threshold);
+        let err = harness.send(&[set], &[USER]).await.unwrap_err();
+        assert_eq!(
+            custom_error(&err),
+            vault_error(VaultError::InvalidGuardians)
+        );
+    }
+
+    let set = harness.set_guardians_instruction(USER, vec![], 0);
+    harness.send(&[set], &[USER]).await.unwrap();
+    assert!(harness
+        .vault_state(USER)
+        .await
+        .unwrap()
+        .guardians
+        .is_empty());
+}
@@ -0,0 +1,98 @@
+//! `withdraw` and `close` never leave the vault PDA rent-paying.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+
+async fn funded_vault(amount: u64) -> Harness {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, amount).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn withdraw_all_empties_the_vault() {
+    let mut harness = funded_vault(LAMPORTS_PER_SOL).await;
+    let vault = harness.vault_address(USER);
+
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    assert!(harness.account(vault).await.is_none());
+}
+
+#[tokio::test]
+async fn partial_withdraw_keeps_the_vault_rent_exempt() {
+    let mut harness = funded_vault(LAMPORTS_PER_SOL).await;
+    let vault = harness.vault_address(USER);
+    let minimum = harness.rent().await.minimum_balance(0);
+
+    // Leaving exactly the rent-exempt minimum is fine.
+    harness
+        .withdraw(USER, LAMPORTS_PER_SOL - minimum)
+        .await
+        .unwrap();
+    assert_eq!(harness.lamports(vault).await, minimum);
+
+    // Leaving a single lamport less is not.
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    let err = harness
+        .withdraw(USER, LAMPORTS_PER_SOL + 1)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::RentExemptionViolation)
+    );
+    assert_eq!(harness.lamports(vault).await, LAMPORTS_PER_SOL + minimum);
+}
+
+#[tokio::test]
+async fn first_deposit_must_cover_rent() {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.initialize(USER).await.unwrap();
+    let minimum = harness.rent().await.minimum_balance(0);
+
+    let err = harness.deposit(USER, minimum - 1).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::RentExemptionViolation)
+    );
+
+    harness.deposit(USER, minimum).await.unwrap();
+}
+
+#[tokio::test]
+async fn close_after_withdraw_returns_the_state_rent() {
+    let mut harness = funded_vault(LAMPORTS_PER_SOL).await;
+    let state = harness.state_address(USER);
+    let vault = harness.vault_address(USER);
+
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    let before = harness.lamports(harness.actor(USER)).await;
+    let state_rent = harness.lamports(state).await;
+
+    harness.close(USER).await.unwrap();
+
+    assert!(harness.account(state).await.is_none());
+    assert!(harness.account(vault).await.is_none());
+    assert_eq!(
+        harness.lamports(harness.actor(USER)).await,
+        before + state_rent
+    );
+}
+
+#[tokio::test]
+async fn closed_vault_can_be_reopened() {
+    let mut harness = funded_vault(LAMPORTS_PER_SOL).await;
+    let vault = harness.vault_address(USER);
+
+    harness.close(USER).await.unwrap();
+    assert!(harness.account(vault).await.is_none());
+
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    assert_eq!(harness.lamports(vault).await, LAMPORTS_PER_SOL);
+}
@@ -0,0 +1,122 @@
+//! A session key can deposit and withdraw to the owner within its limit, until it expires or
+//! the owner revokes it.
+
+use anchor_lang::error::ErrorCode;
+use d1_anchor_vault::VaultError;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const SESSION: usize = 1;
+const OUTSIDER: usize = 2;
+const NOW: i64 = 1_700_000_000;
+const HOUR: i64 = 60 * 60;
+
+async fn vault_with_session(spend_limit: u64) -> Harness {
+    let mut harness = Harness::start(program_test(), 3).await;
+    harness.warp_to_timestamp(NOW);
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, 5 * LAMPORTS_PER_SOL).await.unwrap();
+
+    let create =
+        harness.create_session_instruction(USER, harness.actor(SESSION), NOW + HOUR, spend_limit);
+    harness.send(&[create], &[USER]).await.unwrap();
+    harness
+}
+
+async fn session_withdraw(harness: &mut Harness, amount: u64) -> Option<u32> {
+    let withdraw = harness.session_withdraw_instruction(SESSION, USER, amount);
+    match harness.send(&[withdraw], &[SESSION]).await {
+        Ok(()) => None,
+        Err(err) => custom_error(&err),
+    }
+}
+
+#[tokio::test]
+async fn session_withdraws_to_the_owner_up_to_its_limit() {
+    let mut harness = vault_with_session(2 * LAMPORTS_PER_SOL).await;
+    let user = harness.actor(USER);
+    let session_key = harness.actor(SESSION);
+    let vault = harness.vault_address(USER);
+
+    // Deposits are open to anyone, the session key included.
+    let deposit = harness.deposit_for_instruction(SESSION, USER, LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[SESSION]).await.unwrap();
+    assert_eq!(harness.lamports(vault).await, 6 * LAMPORTS_PER_SOL);
+
+    let before = harness.lamports(user).await;
+    assert_eq!(session_withdraw(&mut harness, LAMPORTS_PER_SOL).await, None);
+    assert_eq!(harness.lamports(user).await, before + LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.session(USER, &session_key).await.unwrap().spent,
+        LAMPORTS_PER_SOL
+    );
+
+    assert_eq!(
+        session_withdraw(&mut harness, LAMPORTS_PER_SOL + 1).await,
+        vault_error(VaultError::SessionLimitExceeded)
+    );
+    assert_eq!(session_withdraw(&mut harness, LAMPORTS_PER_SOL).await, None);
+    assert_eq!(harness.lamports(vault).await, 4 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().total_withdrawn,
+        2 * LAMPORTS_PER_SOL
+    );
+
+    // The payout cannot be pointed anywhere but the owner.
+    let mut withdraw = harness.session_withdraw_instruction(SESSION, USER, 1);
+    withdraw.accounts[1].pubkey = harness.actor(OUTSIDER);
+    let err = harness.send(&[withdraw], &[SESSION]).await.unwrap_err();
+    assert_eq!(custom_error(&err), Some(ErrorCode::ConstraintHasOne as u32));
+}
+
+#[tokio::test]
+async fn sessions_stop_at_expiry_or_revocation() {
+    let mut harness = vault_with_session(LAMPORTS_PER_SOL).await;
+    let session_key = harness.actor(SESSION);
+
+    harness.warp_to_timestamp(NOW + HOUR);
+    assert_eq!(
+        session_withdraw(&mut harness, 1).await,
+        vault_error(VaultError::SessionExpired)
+    );
+
+    // A fresh session for the same key, revoked by the owner before it is used.
+    let revoke = harness.revoke_session_instruction(USER, session_key);
+    harness.send(&[revoke], &[USER]).await.unwrap();
+    let create =
+        harness.create_session_instruction(USER, session_key, NOW + 2 * HOUR, LAMPORTS_PER_SOL);
+    harness.send(&[create], &[USER]).await.unwrap();
+    assert_eq!(session_withdraw(&mut harness, 1).await, None);
+
+    let revoke = harness.revoke_session_instruction(USER, session_key);
+    harness.send(&[revoke], &[USER]).await.unwrap();
+    assert!(harness.session(USER, &session_key).await.is_none());
+    // The session account is gone.
+    assert_eq!(
+        session_withdraw(&mut harness, 1).await,
+        Some(ErrorCode::AccountNotInitialized as u32)
+    );
+}
+
+#[tokio::test]
+async fn only_the_owner_manages_sessions() {
+    let mut harness = vault_with_session(LAMPORTS_PER_SOL).await;
+    let session_key = harness.actor(SESSION);
+
+    let mut revoke = harness.revoke_session_instruction(USER, session_key);
+    revoke.accounts[0].pubkey = harness.actor(OUTSIDER);
+    let err = harness.send(&[revoke], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    // A session key cannot hand itself a new session either.
+    let mut create =
+        harness.create_session_instruction(USER, harness.actor(OUTSIDER), NOW + HOUR, 1);
+    create.accounts[0].pubkey = session_key;
+    let err = harness.send(&[create], &[SESSION]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    let create = harness.create_session_instruction(USER, harness.actor(OUTSIDER), NOW, 1);
+    let err = harness.send(&[create], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::InvalidSession));
+}
@@ -0,0 +1,246 @@
+//! Idle vault lamports can be staked with a validator through the native Stake program.
+
+use d1_anchor_vault::{VaultError, RECOVERY_DELAY};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    stake::{self, instruction as stake_instruction, state::StakeStateV2},
+    system_instruction,
+};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const VALIDATOR: usize = 1;
+const GUARDIAN: usize = 2;
+const HEIR: usize = 3;
+const NOW: i64 = 1_700_000_000;
+const BALANCE: u64 = 10 * LAMPORTS_PER_SOL;
+const STAKE: u64 = 3 * LAMPORTS_PER_SOL;
+
+/// A funded vault next to a local validator's vote account.
+async fn vault_and_validator() -> (Harness, Pubkey) {
+    let mut harness = Harness::start(program_test(), 4).await;
+    harness.warp_to_timestamp(NOW);
+    let vote_account = harness.create_vote_account(VALIDATOR).await.unwrap();
+
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, BALANCE).await.unwrap();
+
+    (harness, vote_account)
+}
+
+async fn stake_state(harness: &mut Harness) -> StakeStateV2 {
+    let account = harness
+        .account(harness.stake_address(USER))
+        .await
+        .expect("stake account");
+    assert_eq!(account.owner, stake::program::ID);
+    account.deserialize_data().expect("stake state")
+}
+
+#[tokio::test]
+async fn stake_is_delegated_and_returns_to_the_vault() {
+    let (mut harness, vote_account) = vault_and_validator().await;
+    let vault = harness.vault_address(USER);
+
+    let create = harness.create_stake_instruction(USER, STAKE);
+    let delegate = harness.delegate_stake_instruction(USER, vote_account);
+    harness.send(&[create, delegate], &[USER]).await.unwrap();
+
+    assert_eq!(harness.lamports(vault).await, BALANCE - STAKE);
+    assert_eq!(harness.lamports(harness.stake_address(USER)).await, STAKE);
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, STAKE);
+
+    // Both authorities belong to the vault PDA, so no wallet can move the stake directly.
+    let state = stake_state(&mut harness).await;
+    let meta = state.meta().unwrap();
+    assert_eq!(meta.authorized.staker, vault);
+    assert_eq!(meta.authorized.withdrawer, vault);
+    assert_eq!(state.delegation().unwrap().voter_pubkey, vote_account);
+
+    // Delegated stake cannot be withdrawn until it is deactivated.
+    let withdraw = harness.withdraw_stake_instruction(USER);
+    assert!(harness.send(&[withdraw], &[USER]).await.is_err());
+
+    let deactivate = harness.deactivate_stake_instruction(USER);
+    let withdraw = harness.withdraw_stake_instruction(USER);
+    harness
+        .send(&[deactivate, withdraw], &[USER])
+        .await
+        .unwrap();
+
+    assert_eq!(harness.lamports(vault).await, BALANCE);
+    assert!(harness.account(harness.stake_address(USER)).await.is_none());
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, 0);
+}
+
+#[tokio::test]
+async fn stakes_too_small_to_delegate_are_rejected() {
+    let (mut harness, _) = vault_and_validator().await;
+    let rent = harness
+        .context
+        .banks_client
+        .get_rent()
+        .await
+        .unwrap()
+        .minimum_balance(StakeStateV2::size_of());
+    let minimum = rent + harness.minimum_delegation().await;
+
+    let create = harness.create_stake_instruction(USER, 0);
+    let err = harness.send(&[create], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    // Rent alone leaves nothing to delegate.
+    for amount in [rent, minimum - 1] {
+        let create = harness.create_stake_instruction(USER, amount);
+        let err = harness.send(&[create], &[USER]).await.unwrap_err();
+        assert_eq!(
+            custom_error(&err),
+            vault_error(VaultError::StakeBelowMinimum)
+        );
+    }
+    assert!(harness.account(harness.stake_address(USER)).await.is_none());
+
+    let create = harness.create_stake_instruction(USER, minimum);
+    harness.send(&[create], &[USER]).await.unwrap();
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, minimum);
+}
+
+#[tokio::test]
+async fn outstanding_stake_blocks_close_and_restaking() {
+    let (mut harness, vote_account) = vault_and_validator().await;
+
+    let create = harness.create_stake_instruction(USER, STAKE);
+    let delegate = harness.delegate_stake_instruction(USER, vote_account);
+    harness.send(&[create, delegate], &[USER]).await.unwrap();
+
+    let err = harness.close(USER).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StakeActive));
+
+    let create = harness.create_stake_instruction(USER, STAKE);
+    let err = harness.send(&[create], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::StakeActive));
+
+    let deactivate = harness.deactivate_stake_instruction(USER);
+    let withdraw = harness.withdraw_stake_instruction(USER);
+    harness
+        .send(&[deactivate, withdraw], &[USER])
+        .await
+        .unwrap();
+
+    harness.close(USER).await.unwrap();
+    assert!(harness.vault_state(USER).await.is_none());
+}
+
+#[tokio::test]
+async fn lamports_sent_to_the_stake_address_first_do_not_block_staking() {
+    let (mut harness, vote_account) = vault_and_validator().await;
+    let stake_account = harness.stake_address(USER);
+
+    // Anyone can fund the address before the vault gets to create its stake account there.
+    let grief =
+        system_instruction::transfer(&harness.actor(GUARDIAN), &stake_account, LAMPORTS_PER_SOL);
+    harness.send(&[grief], &[GUARDIAN]).await.unwrap();
+
+    staked(&mut harness, vote_account).await;
+    assert_eq!(
+        harness.lamports(stake_account).await,
+        STAKE + LAMPORTS_PER_SOL
+    );
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, STAKE);
+    assert_eq!(
+        stake_state(&mut harness)
+            .await
+            .delegation()
+            .unwrap()
+            .voter_pubkey,
+        vote_account
+    );
+}
+
+#[tokio::test]
+async fn staking_leaves_the_vault_rent_exempt() {
+    let (mut harness, _) = vault_and_validator().await;
+
+    let create = harness.create_stake_instruction(USER, BALANCE - 1);
+    let err = harness.send(&[create], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::RentExemptionViolation)
+    );
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, 0);
+}
+
+/// Stakes `STAKE` of the vault with the validator.
+async fn staked(harness: &mut Harness, vote_account: Pubkey) {
+    let create = harness.create_stake_instruction(USER, STAKE);
+    let delegate = harness.delegate_stake_instruction(USER, vote_account);
+    harness.send(&[create, delegate], &[USER]).await.unwrap();
+}
+
+/// Deactivates and withdraws the stake as `authority`, straight through the Stake program.
+async fn unwind_as(harness: &mut Harness, authority: usize) {
+    let stake_account = harness.stake_address(USER);
+    let authority_key = harness.actor(authority);
+    let deactivate = stake_instruction::deactivate_stake(&stake_account, &authority_key);
+    let withdraw =
+        stake_instruction::withdraw(&stake_account, &authority_key, &authority_key, STAKE, None);
+    harness
+        .send(&[deactivate, withdraw], &[authority])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn recovery_hands_the_stake_to_the_new_owner() {
+    let (mut harness, vote_account) = vault_and_validator().await;
+    let new_owner = harness.actor(HEIR);
+    staked(&mut harness, vote_account).await;
+
+    let set = harness.set_guardians_instruction(USER, vec![harness.actor(GUARDIAN)], 1);
+    let propose = harness.propose_recovery_instruction(GUARDIAN, USER, new_owner);
+    harness.send(&[set], &[USER]).await.unwrap();
+    harness.send(&[propose], &[GUARDIAN]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + RECOVERY_DELAY);
+    let execute = harness.execute_recovery_instruction(USER, new_owner, GUARDIAN);
+    harness.send(&[execute], &[]).await.unwrap();
+    assert!(harness.vault_state(USER).await.is_none());
+
+    // The vault state is gone, so the new owner holds the stake directly.
+    let meta = stake_state(&mut harness).await.meta().unwrap();
+    assert_eq!(meta.authorized.staker, new_owner);
+    assert_eq!(meta.authorized.withdrawer, new_owner);
+
+    let before = harness.lamports(new_owner).await;
+    unwind_as(&mut harness, HEIR).await;
+    assert_eq!(harness.lamports(new_owner).await, before + STAKE);
+    assert!(harness.account(harness.stake_address(USER)).await.is_none());
+}
+
+#[tokio::test]
+async fn the_beneficiary_inherits_the_stake() {
+    let (mut harness, vote_account) = vault_and_validator().await;
+    let heir = harness.actor(HEIR);
+    staked(&mut harness, vote_account).await;
+
+    let set = harness.set_beneficiary_instruction(USER, heir, 1);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + 1);
+    let claim = harness.claim_inheritance_instruction(HEIR, USER);
+    harness.send(&[claim], &[HEIR]).await.unwrap();
+    assert_eq!(harness.vault_state(USER).await.unwrap().staked, 0);
+
+    let meta = stake_state(&mut harness).await.meta().unwrap();
+    assert_eq!(meta.authorized.staker, heir);
+    assert_eq!(meta.authorized.withdrawer, heir);
+
+    // The owner can no longer touch it through the vault.
+    let deactivate = harness.deactivate_stake_instruction(USER);
+    assert!(harness.send(&[deactivate], &[USER]).await.is_err());
+
+    let before = harness.lamports(heir).await;
+    unwind_as(&mut harness, HEIR).await;
+    assert_eq!(harness.lamports(heir).await, before + STAKE);
+}
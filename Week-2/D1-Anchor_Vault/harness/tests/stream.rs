@@ -0,0 +1,168 @@
+//! A stream pays its recipient a fixed amount per elapsed period, whenever anyone cranks it.
+
+use anchor_lang::error::ErrorCode;
+use d1_anchor_vault::{VaultError, WITHDRAW_WINDOW};
+use solana_program_test::BanksClientError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const OUTSIDER: usize = 1;
+const NOW: i64 = 1_700_000_000;
+const MONTH: i64 = 30 * 24 * 60 * 60;
+
+async fn vault(unlock_at: Option<i64>) -> Harness {
+    let mut harness = Harness::start(program_test(), 2).await;
+    harness.warp_to_timestamp(NOW);
+
+    let initialize = harness.locked_initialize_instruction(USER, unlock_at);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    harness.deposit(USER, 10 * LAMPORTS_PER_SOL).await.unwrap();
+    harness
+}
+
+/// Streams one SOL a month to `recipient`, starting now.
+async fn stream_to(harness: &mut Harness, recipient: Pubkey) {
+    let create = harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, MONTH, NOW);
+    harness.send(&[create], &[USER]).await.unwrap();
+}
+
+/// Cranks without any signer besides the fee payer.
+async fn crank(harness: &mut Harness, recipient: Pubkey) -> Result<(), BanksClientError> {
+    let crank = harness.crank_stream_instruction(USER, recipient);
+    harness.send(&[crank], &[]).await
+}
+
+#[tokio::test]
+async fn crank_pays_every_elapsed_period() {
+    let mut harness = vault(None).await;
+    let recipient = Pubkey::new_unique();
+    stream_to(&mut harness, recipient).await;
+
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NothingDue));
+
+    harness.warp_to_timestamp(NOW + MONTH);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, LAMPORTS_PER_SOL);
+
+    // Periods nobody cranked are paid in one go, a partial period waits for the next crank.
+    harness.warp_to_timestamp(NOW + 3 * MONTH + MONTH / 2);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.stream(USER, &recipient).await.unwrap().periods_paid,
+        3
+    );
+
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NothingDue));
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        7 * LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn payouts_wait_for_the_time_lock() {
+    let mut harness = vault(Some(NOW + 2 * MONTH)).await;
+    let recipient = Pubkey::new_unique();
+    stream_to(&mut harness, recipient).await;
+
+    harness.warp_to_timestamp(NOW + MONTH);
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::VaultLocked));
+
+    // The periods stay owed and are paid once the vault unlocks.
+    harness.warp_to_timestamp(NOW + 2 * MONTH);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, 2 * LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn only_the_owner_creates_and_cancels_streams() {
+    let mut harness = vault(None).await;
+    let recipient = Pubkey::new_unique();
+
+    let invalid = harness.create_stream_instruction(USER, recipient, LAMPORTS_PER_SOL, 0, NOW);
+    let err = harness.send(&[invalid], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::InvalidStream));
+
+    stream_to(&mut harness, recipient).await;
+
+    let mut cancel = harness.cancel_stream_instruction(USER, recipient);
+    cancel.accounts[0].pubkey = harness.actor(OUTSIDER);
+    let err = harness.send(&[cancel], &[OUTSIDER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::NotVaultOwner));
+
+    let cancel = harness.cancel_stream_instruction(USER, recipient);
+    harness.send(&[cancel], &[USER]).await.unwrap();
+    assert!(harness.stream(USER, &recipient).await.is_none());
+
+    harness.warp_to_timestamp(NOW + MONTH);
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(ErrorCode::AccountNotInitialized as u32)
+    );
+    assert_eq!(harness.lamports(recipient).await, 0);
+}
+
+#[tokio::test]
+async fn a_backlog_over_the_cap_is_paid_as_the_cap_allows() {
+    let mut harness = vault(None).await;
+    let recipient = Pubkey::new_unique();
+    stream_to(&mut harness, recipient).await;
+
+    let cap =
+        harness.set_withdraw_cap_instruction(USER, 2 * LAMPORTS_PER_SOL + LAMPORTS_PER_SOL / 2);
+    harness.send(&[cap], &[USER]).await.unwrap();
+
+    // Five periods are owed, but only two whole ones fit under the cap.
+    harness.warp_to_timestamp(NOW + 5 * MONTH);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, 2 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.stream(USER, &recipient).await.unwrap().periods_paid,
+        2
+    );
+
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::WithdrawalLimitExceeded)
+    );
+
+    // The next window pays two more, and the one after that the rest.
+    harness.warp_to_timestamp(NOW + 5 * MONTH + WITHDRAW_WINDOW);
+    crank(&mut harness, recipient).await.unwrap();
+    harness.warp_to_timestamp(NOW + 5 * MONTH + 2 * WITHDRAW_WINDOW);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, 5 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.stream(USER, &recipient).await.unwrap().periods_paid,
+        5
+    );
+}
+
+#[tokio::test]
+async fn a_backlog_over_the_balance_is_paid_as_far_as_it_goes() {
+    let mut harness = vault(None).await;
+    let recipient = Pubkey::new_unique();
+    stream_to(&mut harness, recipient).await;
+
+    // Ten periods are owed, but the rent-exempt reserve leaves room for only nine.
+    harness.warp_to_timestamp(NOW + 10 * MONTH);
+    crank(&mut harness, recipient).await.unwrap();
+    assert_eq!(harness.lamports(recipient).await, 9 * LAMPORTS_PER_SOL);
+    assert_eq!(
+        harness.stream(USER, &recipient).await.unwrap().periods_paid,
+        9
+    );
+
+    let err = crank(&mut harness, recipient).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InsufficientFunds)
+    );
+}
@@ -0,0 +1,74 @@
+//! A time-locked vault keeps its funds until `unlock_at`, and the lock only moves later.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use vault_harness::{custom_error, program_test, vault_error, Harness};
+
+const USER: usize = 0;
+const NOW: i64 = 1_700_000_000;
+const DAY: i64 = 24 * 60 * 60;
+
+async fn locked_vault(unlock_at: i64) -> Harness {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.warp_to_timestamp(NOW);
+
+    let initialize = harness.locked_initialize_instruction(USER, Some(unlock_at));
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn funds_stay_locked_until_unlock_time() {
+    let mut harness = locked_vault(NOW + DAY).await;
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().unlock_at,
+        NOW + DAY
+    );
+
+    let err = harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::VaultLocked));
+
+    let err = harness.close(USER).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::VaultLocked));
+
+    // Deposits are still welcome while locked.
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    harness.warp_to_timestamp(NOW + DAY);
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness.close(USER).await.unwrap();
+}
+
+#[tokio::test]
+async fn unlocked_vault_is_open_immediately() {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    assert_eq!(harness.vault_state(USER).await.unwrap().unlock_at, 0);
+    harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap();
+}
+
+#[tokio::test]
+async fn lock_can_only_be_extended() {
+    let mut harness = locked_vault(NOW + DAY).await;
+
+    let extend = harness.extend_lock_instruction(USER, NOW + 2 * DAY);
+    harness.send(&[extend], &[USER]).await.unwrap();
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().unlock_at,
+        NOW + 2 * DAY
+    );
+
+    for unlock_at in [NOW + 2 * DAY, NOW + DAY, NOW] {
+        let shorten = harness.extend_lock_instruction(USER, unlock_at);
+        let err = harness.send(&[shorten], &[USER]).await.unwrap_err();
+        assert_eq!(custom_error(&err), vault_error(VaultError::LockNotExtended));
+    }
+
+    // The old unlock time no longer releases the funds.
+    harness.warp_to_timestamp(NOW + DAY);
+    let err = harness.withdraw(USER, LAMPORTS_PER_SOL).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::VaultLocked));
+}
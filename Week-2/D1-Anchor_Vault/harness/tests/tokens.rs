@@ -0,0 +1,215 @@
+//! SPL Token and Token-2022 deposits live in per-mint token vaults that `close` sweeps.
+
+use d1_anchor_vault::{VaultError, RECOVERY_DELAY};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{
+    custom_error, program_test, vault_error, Harness, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+const USER: usize = 0;
+const GUARDIAN: usize = 1;
+
+struct Setup {
+    harness: Harness,
+    /// One mint per token program.
+    mints: [usize; 2],
+}
+
+/// A vault holding SOL and 1_000 tokens of a Token and a Token-2022 mint.
+async fn vault_with_tokens() -> Setup {
+    let mut harness = Harness::start(program_test(), 2).await;
+    let token = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    let token_2022 = harness.create_mint(TOKEN_2022_PROGRAM_ID, 9).await.unwrap();
+
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+
+    for mint in [token, token_2022] {
+        harness.mint_to(mint, USER, 1_500).await.unwrap();
+        let deposit = harness.deposit_tokens_instruction(USER, mint, 1_000);
+        harness.send(&[deposit], &[USER]).await.unwrap();
+    }
+
+    Setup {
+        harness,
+        mints: [token, token_2022],
+    }
+}
+
+#[tokio::test]
+async fn tokens_of_both_programs_round_trip() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+
+    let state = harness.vault_state(USER).await.unwrap();
+    assert_eq!(
+        state.token_mints,
+        mints.map(|mint| harness.mints[mint].address)
+    );
+
+    for mint in mints {
+        let token_vault = harness.token_vault_address(USER, mint);
+        assert_eq!(harness.token_balance(token_vault).await, 1_000);
+
+        // Depositing the same mint again reuses its token vault.
+        let deposit = harness.deposit_tokens_instruction(USER, mint, 500);
+        harness.send(&[deposit], &[USER]).await.unwrap();
+
+        let withdraw = harness.withdraw_tokens_instruction(USER, mint, 1_200);
+        harness.send(&[withdraw], &[USER]).await.unwrap();
+
+        assert_eq!(harness.token_balance(token_vault).await, 300);
+        assert_eq!(harness.token_balance(harness.ata(USER, mint)).await, 1_200);
+    }
+
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().token_mints.len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn close_sweeps_every_token_vault() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+    let user = harness.actor(USER);
+
+    // Leaving a token vault out would strand its tokens behind the closed state PDA.
+    let mut close = harness.close_instruction(USER);
+    close
+        .accounts
+        .extend(harness.sweep_accounts(USER, &user, &mints[..1]));
+    let err = harness.send(&[close], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::TokenVaultsMissing)
+    );
+
+    let mut close = harness.close_instruction(USER);
+    close
+        .accounts
+        .extend(harness.sweep_accounts(USER, &user, &mints));
+    harness.send(&[close], &[USER]).await.unwrap();
+
+    for mint in mints {
+        assert_eq!(harness.token_balance(harness.ata(USER, mint)).await, 1_500);
+        assert!(harness
+            .account(harness.token_vault_address(USER, mint))
+            .await
+            .is_none());
+    }
+    assert!(harness.vault_state(USER).await.is_none());
+}
+
+#[tokio::test]
+async fn recovery_sweeps_tokens_to_the_new_owner() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+    let new_owner = Pubkey::new_unique();
+
+    let set = harness.set_guardians_instruction(USER, vec![harness.actor(GUARDIAN)], 1);
+    harness.send(&[set], &[USER]).await.unwrap();
+
+    let propose = harness.propose_recovery_instruction(GUARDIAN, USER, new_owner);
+    harness.send(&[propose], &[GUARDIAN]).await.unwrap();
+
+    let now = harness.clock().await.unix_timestamp;
+    harness.warp_to_timestamp(now + RECOVERY_DELAY);
+
+    for mint in mints {
+        harness.create_ata(&new_owner, mint).await.unwrap();
+    }
+
+    let mut execute = harness.execute_recovery_instruction(USER, new_owner, GUARDIAN);
+    execute
+        .accounts
+        .extend(harness.sweep_accounts(USER, &new_owner, &mints));
+    harness.send(&[execute], &[]).await.unwrap();
+
+    for mint in mints {
+        assert_eq!(
+            harness
+                .token_balance(harness.ata_of(&new_owner, mint))
+                .await,
+            1_000
+        );
+    }
+    assert!(harness.vault_state(USER).await.is_none());
+}
+
+#[tokio::test]
+async fn closing_an_empty_token_vault_frees_its_slot() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+    let token_vault = harness.token_vault_address(USER, mints[0]);
+
+    let close_vault = harness.close_token_vault_instruction(USER, mints[0]);
+    let err = harness.send(&[close_vault], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::TokenVaultNotEmpty)
+    );
+
+    let withdraw = harness.withdraw_tokens_instruction(USER, mints[0], 1_000);
+    harness.send(&[withdraw], &[USER]).await.unwrap();
+
+    let user = harness.actor(USER);
+    let before = harness.lamports(user).await;
+    let vault_rent = harness.lamports(token_vault).await;
+
+    let close_vault = harness.close_token_vault_instruction(USER, mints[0]);
+    harness.send(&[close_vault], &[USER]).await.unwrap();
+
+    assert!(harness.account(token_vault).await.is_none());
+    assert_eq!(harness.lamports(user).await, before + vault_rent);
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().token_mints,
+        [harness.mints[mints[1]].address]
+    );
+
+    // Only the remaining token vault needs sweeping now.
+    let mut close = harness.close_instruction(USER);
+    close
+        .accounts
+        .extend(harness.sweep_accounts(USER, &user, &mints[1..]));
+    harness.send(&[close], &[USER]).await.unwrap();
+    assert!(harness.vault_state(USER).await.is_none());
+}
+
+#[tokio::test]
+async fn frozen_token_vaults_do_not_block_close() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+    let user = harness.actor(USER);
+    let frozen = harness.token_vault_address(USER, mints[0]);
+    harness.freeze(mints[0], frozen).await.unwrap();
+
+    let mut close = harness.close_instruction(USER);
+    close
+        .accounts
+        .extend(harness.sweep_accounts(USER, &user, &mints));
+    harness.send(&[close], &[USER]).await.unwrap();
+
+    // The frozen token vault stays put, the other one is swept as usual.
+    assert_eq!(harness.token_balance(frozen).await, 1_000);
+    assert_eq!(
+        harness.token_balance(harness.ata(USER, mints[0])).await,
+        500
+    );
+    assert_eq!(
+        harness.token_balance(harness.ata(USER, mints[1])).await,
+        1_500
+    );
+    assert!(harness.vault_state(USER).await.is_none());
+}
+
+#[tokio::test]
+async fn a_frozen_token_vault_can_be_forgotten() {
+    let Setup { mut harness, mints } = vault_with_tokens().await;
+    let frozen = harness.token_vault_address(USER, mints[0]);
+    harness.freeze(mints[0], frozen).await.unwrap();
+
+    let close_vault = harness.close_token_vault_instruction(USER, mints[0]);
+    harness.send(&[close_vault], &[USER]).await.unwrap();
+
+    assert_eq!(harness.token_balance(frozen).await, 1_000);
+    assert_eq!(
+        harness.vault_state(USER).await.unwrap().token_mints,
+        [harness.mints[mints[1]].address]
+    );
+}
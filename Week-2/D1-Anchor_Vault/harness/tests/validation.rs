@@ -0,0 +1,114 @@
+//! Amounts the system program would reject fail early with a `VaultError` instead.
+
+use d1_anchor_vault::VaultError;
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, pubkey::Pubkey};
+use vault_harness::{
+    custom_error, program_test, vault_error, Harness, ACTOR_LAMPORTS, TOKEN_PROGRAM_ID,
+};
+
+const USER: usize = 0;
+const SEED: u64 = 1;
+
+async fn funded_vault() -> Harness {
+    let mut harness = Harness::start(program_test(), 1).await;
+    harness.initialize(USER).await.unwrap();
+    harness.deposit(USER, LAMPORTS_PER_SOL).await.unwrap();
+    harness
+}
+
+#[tokio::test]
+async fn zero_amounts_are_rejected() {
+    let mut harness = funded_vault().await;
+
+    let err = harness.deposit(USER, 0).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let err = harness.withdraw(USER, 0).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+}
+
+#[tokio::test]
+async fn zero_amounts_are_rejected_for_tokens_pools_and_multisigs() {
+    let mut harness = funded_vault().await;
+    let mint = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    harness.mint_to(mint, USER, 1_000).await.unwrap();
+
+    let deposit = harness.deposit_tokens_instruction(USER, mint, 0);
+    let err = harness.send(&[deposit], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let deposit = harness.deposit_tokens_instruction(USER, mint, 1_000);
+    harness.send(&[deposit], &[USER]).await.unwrap();
+    let withdraw = harness.withdraw_tokens_instruction(USER, mint, 0);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let initialize = harness.initialize_pool_instruction(USER, SEED);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    let pool = harness.pool_address(USER, SEED);
+    let deposit = harness.pool_deposit_instruction(USER, pool, 0);
+    let err = harness.send(&[deposit], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let deposit = harness.pool_deposit_instruction(USER, pool, LAMPORTS_PER_SOL);
+    harness.send(&[deposit], &[USER]).await.unwrap();
+    let withdraw = harness.pool_withdraw_instruction(USER, pool, 0);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let owners = vec![harness.actor(USER), Pubkey::new_unique()];
+    let initialize = harness.initialize_multisig_instruction(USER, SEED, owners, 1);
+    harness.send(&[initialize], &[USER]).await.unwrap();
+    let multisig = harness.multisig_address(USER, SEED);
+    let deposit = harness.multisig_deposit_instruction(USER, multisig, 0);
+    let err = harness.send(&[deposit], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+
+    let propose =
+        harness.propose_withdrawal_instruction(USER, multisig, 0, Pubkey::new_unique(), 0);
+    let err = harness.send(&[propose], &[USER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), vault_error(VaultError::ZeroAmount));
+}
+
+#[tokio::test]
+async fn withdrawing_more_than_the_vault_holds_fails() {
+    let mut harness = funded_vault().await;
+
+    let err = harness
+        .withdraw(USER, LAMPORTS_PER_SOL + 1)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InsufficientFunds)
+    );
+
+    // The same goes for third-party payouts.
+    let destination = Pubkey::new_unique();
+    let initialize = harness.initialize_allowlist_instruction(USER, 0);
+    let add = harness.add_destination_instruction(USER, destination);
+    harness.send(&[initialize, add], &[USER]).await.unwrap();
+
+    let withdraw = harness.withdraw_to_instruction(USER, destination, 2 * LAMPORTS_PER_SOL);
+    let err = harness.send(&[withdraw], &[USER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InsufficientFunds)
+    );
+
+    assert_eq!(
+        harness.lamports(harness.vault_address(USER)).await,
+        LAMPORTS_PER_SOL
+    );
+}
+
+#[tokio::test]
+async fn depositing_more_than_the_depositor_holds_fails() {
+    let mut harness = funded_vault().await;
+
+    let err = harness.deposit(USER, ACTOR_LAMPORTS).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        vault_error(VaultError::InsufficientFunds)
+    );
+}
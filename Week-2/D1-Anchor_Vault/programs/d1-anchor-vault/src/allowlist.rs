@@ -0,0 +1,289 @@
+use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
+
+use crate::{check_rent_exempt, check_withdrawable, VaultError, VaultState, VaultWithdrawn, MAX_DESTINATIONS};
+
+#[derive(Accounts)]
+// The `InitializeAllowlist` struct creates the list of third-party addresses the vault may pay out to.
+pub struct InitializeAllowlist<'info> {
+    #[account(mut)] // Pays for the `allowlist` account.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed, // Takes over an allowlist an earlier vault at this address left behind.
+        payer = user,
+        seeds = [b"allowlist", vault_state.key().as_ref()],
+        bump,
+        space = Allowlist::INIT_SPACE,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeAllowlist<'info> {
+    pub fn initialize_allowlist(&mut self, delay: i64, bumps: &InitializeAllowlistBumps) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+        self.vault_state
+            .check_replaceable(&self.allowlist.vault_state, self.allowlist.generation)?;
+
+        require!(delay >= 0, VaultError::InvalidDelay);
+
+        self.allowlist.set_inner(Allowlist {
+            vault_state: self.vault_state.key(),
+            delay,
+            pending_delay: 0,
+            pending_delay_at: 0,
+            destinations: Vec::new(),
+            generation: self.vault_state.generation,
+            bump: bumps.allowlist,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ManageAllowlist` struct only needs the owner and their allowlist, no lamports move.
+pub struct ManageAllowlist<'info> {
+    pub user: Signer<'info>, // Only the owner may change who their vault pays out to.
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"allowlist", vault_state.key().as_ref()],
+        bump = allowlist.bump,
+        has_one = vault_state,
+        constraint = allowlist.generation == vault_state.generation @ VaultError::StaleAccount,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+}
+
+impl<'info> ManageAllowlist<'info> {
+    pub fn add_destination(&mut self, destination: Pubkey) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let allowlist = &mut self.allowlist;
+
+        allowlist.apply_pending_delay(now);
+
+        require!(
+            allowlist.find(&destination).is_none(),
+            VaultError::DestinationAlreadyAllowed
+        );
+        require!(
+            allowlist.destinations.len() < MAX_DESTINATIONS,
+            VaultError::AllowlistFull
+        );
+
+        // A stolen key could add its own address, so the owner gets the delay to notice and remove it
+        let usable_at = now.saturating_add(allowlist.delay);
+        allowlist.destinations.push(Destination {
+            address: destination,
+            usable_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_destination(&mut self, destination: Pubkey) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // Removing can only make a stolen key less useful, so it applies right away
+        let index = self
+            .allowlist
+            .find(&destination)
+            .ok_or(VaultError::DestinationNotAllowed)?;
+
+        self.allowlist.destinations.remove(index);
+
+        Ok(())
+    }
+
+    pub fn set_allowlist_delay(&mut self, delay: i64) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        require!(delay >= 0, VaultError::InvalidDelay);
+
+        let now = Clock::get()?.unix_timestamp;
+        let allowlist = &mut self.allowlist;
+
+        allowlist.apply_pending_delay(now);
+
+        // A longer delay applies right away. A shorter one only after the current delay, or a
+        // stolen key could drop it to zero and add a destination that is usable immediately.
+        if delay >= allowlist.delay {
+            allowlist.delay = delay;
+            allowlist.pending_delay = 0;
+            allowlist.pending_delay_at = 0;
+        } else {
+            allowlist.pending_delay = delay;
+            allowlist.pending_delay_at = now.saturating_add(allowlist.delay);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `WithdrawTo` struct withdraws from the vault to an allowlisted address instead of the owner.
+pub struct WithdrawTo<'info> {
+    pub user: Signer<'info>, // The owner still has to sign every withdrawal.
+
+    #[account(mut)] // Receives the withdrawn lamports.
+    pub destination: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut, // Withdrawals are counted against the withdrawal cap stored here.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [b"allowlist", vault_state.key().as_ref()],
+        bump = allowlist.bump,
+        has_one = vault_state,
+        constraint = allowlist.generation == vault_state.generation @ VaultError::StaleAccount,
+    )]
+    pub allowlist: Account<'info, Allowlist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawTo<'info> {
+    pub fn withdraw_to(&mut self, amount: u64) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        check_withdrawable(&self.vault, amount)?;
+
+        let now = Clock::get()?.unix_timestamp;
+
+        let index = self
+            .allowlist
+            .find(&self.destination.key())
+            .ok_or(VaultError::DestinationNotAllowed)?;
+        require!(
+            now >= self.allowlist.destinations[index].usable_at,
+            VaultError::DestinationPending
+        );
+
+        // Paying a third party is still a withdrawal, with the same lock, cap and rent rules
+        self.vault_state.check_unlocked()?;
+        self.vault_state.record_withdrawal(amount, now)?;
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.destination.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        emit!(VaultWithdrawn {
+            vault_state: self.vault_state.key(),
+            destination: self.destination.key(),
+            amount,
+            total_withdrawn: self.vault_state.total_withdrawn,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Allowlist struct holds the third-party addresses a vault may withdraw to.
+pub struct Allowlist {
+    /// The vault state this allowlist belongs to.
+    pub vault_state: Pubkey,
+
+    /// Seconds a newly added destination waits before it can receive withdrawals.
+    pub delay: i64,
+
+    /// Delay that replaces `delay` once `pending_delay_at` has passed.
+    pub pending_delay: i64,
+
+    /// When `pending_delay` takes effect, `0` when no change is pending.
+    pub pending_delay_at: i64,
+
+    /// Allowed destinations, each usable from its own timestamp.
+    pub destinations: Vec<Destination>,
+
+    /// The vault's `generation` when the allowlist was created.
+    pub generation: u64,
+
+    /// Bump seed for the allowlist PDA.
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Destination {
+    /// Address withdrawals may be sent to.
+    pub address: Pubkey,
+
+    /// When the destination was added plus the delay at the time.
+    pub usable_at: i64,
+}
+
+impl Space for Allowlist {
+    // 8 bytes for Anchor discriminator, 32 bytes for `vault_state`, 3 * 8 bytes for the delay and
+    // its pending change, 4 + (32 + 8) * MAX_DESTINATIONS bytes for `destinations`, 8 bytes for
+    // `generation` and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 32 + 3 * 8 + 4 + (32 + 8) * MAX_DESTINATIONS + 8 + 1;
+}
+
+impl Allowlist {
+    /// Position of `address` in `destinations`, if it is on the list.
+    pub fn find(&self, address: &Pubkey) -> Option<usize> {
+        self.destinations
+            .iter()
+            .position(|destination| destination.address == *address)
+    }
+
+    /// Promotes `pending_delay` to `delay` once its own waiting period has passed.
+    pub fn apply_pending_delay(&mut self, now: i64) {
+        if self.pending_delay_at != 0 && now >= self.pending_delay_at {
+            self.delay = self.pending_delay;
+            self.pending_delay = 0;
+            self.pending_delay_at = 0;
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use anchor_lang::{prelude::*, solana_program::bpf_loader_upgradeable};
+
+use crate::VaultError;
+
+#[derive(Accounts)]
+// The `InitializeConfig` struct creates the program-wide config, making the signer its admin. There is
+// only one, so only the program's upgrade authority may create it, whenever it gets to it after deploying.
+pub struct InitializeConfig<'info> {
+    #[account(mut)] // Pays for the `config` account.
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ VaultError::NotUpgradeAuthority,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        bump,
+        space = Config::INIT_SPACE,
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeConfig<'info> {
+    pub fn initialize_config(&mut self, bumps: &InitializeConfigBumps) -> Result<()> {
+        self.config.set_inner(Config {
+            admin: self.admin.key(),
+            pending_admin: Pubkey::default(),
+            paused: false,
+            bump: bumps.config,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `Administer` struct only needs the admin and the config, no lamports move.
+pub struct Administer<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotAdmin,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> Administer<'info> {
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.config.paused = paused;
+
+        Ok(())
+    }
+
+    pub fn propose_admin(&mut self, new_admin: Pubkey) -> Result<()> {
+        require_keys_neq!(new_admin, self.admin.key(), VaultError::InvalidNewAdmin);
+
+        // A typo here cannot lock the program out, the old admin stays in charge until accepted
+        self.config.pending_admin = new_admin;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `AcceptAdmin` struct completes an admin rotation, signed by the proposed admin.
+pub struct AcceptAdmin<'info> {
+    pub new_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.pending_admin == new_admin.key() @ VaultError::NotPendingAdmin,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> AcceptAdmin<'info> {
+    pub fn accept_admin(&mut self) -> Result<()> {
+        self.config.admin = self.new_admin.key();
+        self.config.pending_admin = Pubkey::default();
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Config struct holds the program-wide settings, currently just the emergency pause.
+pub struct Config {
+    /// Wallet allowed to pause the program and to hand the role on.
+    pub admin: Pubkey,
+
+    /// Wallet that may accept the admin role, `Pubkey::default()` when no rotation is pending.
+    pub pending_admin: Pubkey,
+
+    /// While set, no vault is opened and no funds come in. Funds can always leave.
+    pub paused: bool,
+
+    /// Bump seed for the config PDA.
+    pub bump: u8,
+}
+
+impl Space for Config {
+    // 8 bytes for Anchor discriminator, 2 * 32 bytes for `admin` and `pending_admin`, 1 byte for
+    // `paused` and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 2 * 32 + 1 + 1;
+}
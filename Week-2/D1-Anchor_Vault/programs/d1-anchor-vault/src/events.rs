@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+// Events let off-chain accounting follow a vault's balance from the program logs alone.
+
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct VaultDeposited {
+    pub vault_state: Pubkey,
+    pub depositor: Pubkey,
+    pub amount: u64,
+
+    /// `VaultState::total_deposited` after this deposit.
+    pub total_deposited: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct VaultWithdrawn {
+    pub vault_state: Pubkey,
+
+    /// The owner for `withdraw`, an allowlisted address or a stream's recipient otherwise.
+    pub destination: Pubkey,
+    pub amount: u64,
+
+    /// `VaultState::total_withdrawn` after this withdrawal.
+    pub total_withdrawn: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+#[derive(Debug, PartialEq, Eq)]
+pub struct VaultClosed {
+    pub vault_state: Pubkey,
+
+    /// Wallet the remaining balance was swept to.
+    pub owner: Pubkey,
+
+    /// Lamports swept out on close, `0` if the vault was already empty.
+    pub amount: u64,
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    pub timestamp: i64,
+}
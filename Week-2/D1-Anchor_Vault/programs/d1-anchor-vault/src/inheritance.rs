@@ -0,0 +1,122 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::stake,
+    system_program::{Transfer, transfer},
+};
+
+use crate::{hand_over_stake, sweep_token_vaults, VaultError, VaultState, VaultWithdrawn};
+
+#[derive(Accounts)]
+// The `ClaimInheritance` struct lets the beneficiary take the vault's balance after the owner has
+// gone quiet for longer than the inactivity period they chose.
+pub struct ClaimInheritance<'info> {
+    #[account(mut)] // Receives the vault's balance.
+    pub beneficiary: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut, // The payout is added to `total_withdrawn` and the inactivity window starts over.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        has_one = beneficiary @ VaultError::NotBeneficiary,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// CHECK: the vault's stake account, handed to the beneficiary if the vault has one
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimInheritance<'info> {
+    pub fn claim_inheritance(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        // Any owner action pushes the deadline back, so a live owner can never be claimed from
+        let claimable_at = self
+            .vault_state
+            .last_heartbeat
+            .saturating_add(self.vault_state.inactivity_period);
+        require!(now >= claimable_at, VaultError::OwnerStillActive);
+
+        // Inheriting the vault does not get around its time lock
+        self.vault_state.check_unlocked()?;
+
+        // The stake is part of what the owner left, and the owner is the only one who can unwind
+        // it through this program, so the beneficiary takes it over as its staker and withdrawer
+        let staked = self.vault_state.staked;
+        hand_over_stake(
+            &mut self.vault_state,
+            &self.vault.to_account_info(),
+            &self.stake_account.to_account_info(),
+            &self.clock.to_account_info(),
+            &self.stake_program.to_account_info(),
+            &self.beneficiary.key(),
+        )?;
+
+        // Token vaults go to the beneficiary too, nobody else could empty them once the owner is gone
+        let had_tokens = !self.vault_state.token_mints.is_empty();
+        sweep_token_vaults(&mut self.vault_state, &self.beneficiary.to_account_info(), token_accounts)?;
+
+        let balance = self.vault.lamports();
+        require!(balance > 0 || staked > 0 || had_tokens, VaultError::InsufficientFunds);
+
+        // Like recovery, the claim takes everything at once and is not held to the withdrawal cap
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(balance);
+        self.vault_state.last_activity = now;
+
+        // The vault stays, so whatever reaches it later is only claimable after another full window
+        self.vault_state.last_heartbeat = now;
+
+        if balance == 0 {
+            return Ok(());
+        }
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.beneficiary.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, balance)?;
+
+        emit!(VaultWithdrawn {
+            vault_state: self.vault_state.key(),
+            destination: self.beneficiary.key(),
+            amount: balance,
+            total_withdrawn: self.vault_state.total_withdrawn,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+}
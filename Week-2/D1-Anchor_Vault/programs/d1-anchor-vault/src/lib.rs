@@ -2,6 +2,88 @@ use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
 
 declare_id!("71r1B1tee9RJsT1Tv8K5HqUJVig2Ve7Ykd1sbLgPo94H");
 
+pub mod recovery;
+pub use recovery::*;
+
+pub mod multisig;
+pub use multisig::*;
+
+pub mod tokens;
+pub use tokens::*;
+
+pub mod pool;
+pub use pool::*;
+
+pub mod staking;
+pub use staking::*;
+
+pub mod allowlist;
+pub use allowlist::*;
+
+pub mod vault_index;
+pub use vault_index::*;
+
+pub mod ownership;
+pub use ownership::*;
+
+pub mod stream;
+pub use stream::*;
+
+pub mod events;
+pub use events::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod inheritance;
+pub use inheritance::*;
+
+pub mod session;
+pub use session::*;
+
+pub mod migrate;
+pub use migrate::*;
+
+/// Layout version written by `initialize` and `migrate`.
+pub const VAULT_STATE_VERSION: u8 = 1;
+
+/// Length of a withdrawal-cap window in seconds.
+pub const WITHDRAW_WINDOW: i64 = 24 * 60 * 60;
+
+/// Seconds before a raised or removed withdrawal cap takes effect.
+pub const CAP_CHANGE_DELAY: i64 = 24 * 60 * 60;
+
+/// Most guardians a vault can name for recovery.
+pub const MAX_GUARDIANS: usize = 5;
+
+/// Seconds between proposing a recovery and being able to execute it.
+pub const RECOVERY_DELAY: i64 = 7 * 24 * 60 * 60;
+
+/// Most owners a multisig vault can have.
+pub const MAX_OWNERS: usize = 10;
+
+/// Most mints a vault can hold tokens of at the same time.
+pub const MAX_TOKEN_VAULTS: usize = 8;
+
+/// Shares of a pool that belong to nobody, see `Pool::shares_for`.
+pub const VIRTUAL_SHARES: u64 = 1_000;
+
+/// Lamports of a pool that belong to nobody, see `Pool::shares_for`.
+pub const VIRTUAL_ASSETS: u64 = 1;
+
+/// Decimals of a pool's share mint. A lamport is worth `VIRTUAL_SHARES` base units of shares
+/// when a pool opens, so shares carry three decimals more than SOL.
+pub const SHARE_DECIMALS: u8 = 12;
+
+/// Most third-party destinations a vault's allowlist can hold.
+pub const MAX_DESTINATIONS: usize = 10;
+
+/// Most vaults a user can have open at the same time.
+pub const MAX_VAULTS: usize = 16;
+
+/// Longest vault name in bytes.
+pub const MAX_NAME_LEN: usize = 32;
+
 #[program]
 pub mod anchor_vault {
     use super::*;
@@ -9,18 +91,20 @@ pub mod anchor_vault {
     /// Initializes the `vault_state` by setting its bump values.
     /// - Calls `initialize` on the `Initialize` context, storing the PDA bumps within `vault_state`.
     /// - This step is necessary for setting up the vault's initial state, allowing subsequent interactions to verify PDAs.
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        // Initializes `vault_state` by storing PDA bump values for future use
-        ctx.accounts.initialize(&ctx.bumps)?;
+    /// - `id` picks which of the user's vaults to create, and `name` labels it in the user's `VaultIndex`.
+    /// - `unlock_at` optionally time-locks the vault: `withdraw` and `close` fail before that unix timestamp.
+    pub fn initialize(ctx: Context<Initialize>, id: u64, name: String, unlock_at: Option<i64>) -> Result<()> {
+        // Initializes `vault_state` by storing PDA bump values and the unlock time for future use
+        ctx.accounts.initialize(id, name, unlock_at, &ctx.bumps)?;
 
         Ok(())
     }
 
     /// Deposits a specified `amount` of SOL into the `vault` account.
     /// - Calls `deposit` on the `Deposit` context, which handles the SOL transfer.
-    /// - This allows users to securely send SOL into the vault after initialization.
-    pub fn deposit(ctx: Context<Operation>, amount: u64) -> Result<()> {
-        // Executes the deposit function, transferring `amount` from `user` to `vault`
+    /// - The depositor names the vault's `owner` and does not have to be the owner, so anyone can fund a vault.
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        // Executes the deposit function, transferring `amount` from `depositor` to `vault`
         ctx.accounts.deposit(amount)?;
 
         Ok(())
@@ -32,8 +116,368 @@ pub mod anchor_vault {
         Ok(())
     }
 
-    pub fn close(ctx: Context<Close>) -> Result<()> {
-        ctx.accounts.close()?;
+    /// Closes the vault, sweeping its SOL and every token vault back to the user.
+    /// - The remaining accounts hold `[mint, token_vault, user_token_account, token_program]` for
+    ///   each mint in `vault_state.token_mints`, in order.
+    /// - Streams, sessions, the allowlist and any recovery left open stop working. A vault opened
+    ///   again under the same id is a new `generation`, and creating them again replaces them.
+    pub fn close<'info>(ctx: Context<'_, '_, 'info, 'info, Close<'info>>) -> Result<()> {
+        ctx.accounts.close(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    /// Deposits `amount` tokens of `mint` into the vault's token vault for that mint.
+    pub fn deposit_tokens(ctx: Context<TokenDeposit>, amount: u64) -> Result<()> {
+        ctx.accounts.deposit(amount)?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_tokens(ctx: Context<TokenWithdraw>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw(amount)?;
+
+        Ok(())
+    }
+
+    /// Closes the vault's empty token vault for `mint`, returning its rent to the owner, and drops
+    /// the mint from `token_mints`.
+    /// - A frozen token vault cannot be closed and is only dropped from `token_mints`.
+    pub fn close_token_vault(ctx: Context<CloseTokenVault>) -> Result<()> {
+        ctx.accounts.close_token_vault()?;
+
+        Ok(())
+    }
+
+    /// Moves the vault's unlock time to `unlock_at`.
+    /// - The lock can only ever be pushed later, so a compromised key cannot shorten it.
+    pub fn extend_lock(ctx: Context<Configure>, unlock_at: i64) -> Result<()> {
+        ctx.accounts.extend_lock(unlock_at)?;
+
+        Ok(())
+    }
+
+    /// Sets how many lamports may leave the vault per `WITHDRAW_WINDOW`; `0` removes the cap.
+    /// - Lowering the cap applies immediately, raising or removing it only after `CAP_CHANGE_DELAY`,
+    ///   so a stolen key cannot lift the limit and drain the vault in one go.
+    pub fn set_withdraw_cap(ctx: Context<Configure>, cap: u64) -> Result<()> {
+        ctx.accounts.set_withdraw_cap(cap)?;
+
+        Ok(())
+    }
+
+    /// Offers the vault to `new_owner`, who takes over by calling `accept_owner`.
+    /// - `Pubkey::default()` withdraws a pending offer.
+    pub fn propose_owner(ctx: Context<Configure>, new_owner: Pubkey) -> Result<()> {
+        ctx.accounts.propose_owner(new_owner)?;
+
+        Ok(())
+    }
+
+    /// Completes a handover proposed by the current owner, moving the vault to the signer's index.
+    /// - The vault becomes a new `generation`, so the previous owner's streams, sessions, allowlist
+    ///   and recoveries stop working. The creator's index hands it out, pass it as `creator_index`
+    ///   unless the creator is the previous or the new owner.
+    pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+        ctx.accounts.accept_owner(&ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Names up to `MAX_GUARDIANS` guardians, `threshold` of which can recover the vault to a new
+    /// key if the owner loses theirs. An empty list turns recovery off.
+    pub fn set_guardians(ctx: Context<Configure>, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        ctx.accounts.set_guardians(guardians, threshold)?;
+
+        Ok(())
+    }
+
+    /// Opens a recovery of the vault to `new_owner`, approved by the proposing guardian.
+    /// - A pending recovery made stale by a new guardian set or a reopened vault is replaced.
+    pub fn propose_recovery(ctx: Context<ProposeRecovery>, new_owner: Pubkey) -> Result<()> {
+        ctx.accounts.propose_recovery(new_owner, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    pub fn approve_recovery(ctx: Context<ApproveRecovery>) -> Result<()> {
+        ctx.accounts.approve_recovery()?;
+
+        Ok(())
+    }
+
+    /// Transfers the vault balance to the new owner once enough guardians approved and
+    /// `RECOVERY_DELAY` has passed since the proposal.
+    /// - Token vaults are swept to the new owner, with remaining accounts laid out as for `close`.
+    /// - A stake account the vault has is handed to the new owner, who becomes its staker and
+    ///   withdrawer.
+    pub fn execute_recovery<'info>(ctx: Context<'_, '_, 'info, 'info, ExecuteRecovery<'info>>) -> Result<()> {
+        ctx.accounts.execute_recovery(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    /// Lets the owner throw out a pending recovery; the `close` constraint does all the work.
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        ctx.accounts.cancel_recovery()?;
+
+        Ok(())
+    }
+
+    /// Creates an M-of-N vault owned by `owners`, any `threshold` of which can move its funds.
+    pub fn initialize_multisig(
+        ctx: Context<InitializeMultisig>,
+        seed: u64,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        ctx.accounts.initialize_multisig(seed, owners, threshold, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    pub fn multisig_deposit(ctx: Context<MultisigDeposit>, amount: u64) -> Result<()> {
+        ctx.accounts.deposit(amount)?;
+
+        Ok(())
+    }
+
+    /// Opens a proposal to send `amount` lamports from the multisig vault to `destination`.
+    pub fn propose_withdrawal(
+        ctx: Context<ProposeWithdrawal>,
+        destination: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.propose_withdrawal(destination, amount, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        ctx.accounts.approve_proposal()?;
+
+        Ok(())
+    }
+
+    /// Performs an approved withdrawal, signed by the vault PDA, and closes the proposal.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        ctx.accounts.execute_proposal()?;
+
+        Ok(())
+    }
+
+    /// Creates a vault shared by many depositors, together with the mint of its share tokens.
+    /// - The creator funds the vault's rent-exempt reserve, which no share can ever redeem.
+    pub fn initialize_pool(ctx: Context<InitializePool>, seed: u64) -> Result<()> {
+        ctx.accounts.initialize_pool(seed, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Deposits `amount` lamports into the pool and mints the depositor their share of it.
+    pub fn pool_deposit(ctx: Context<PoolDeposit>, amount: u64) -> Result<()> {
+        ctx.accounts.deposit(amount)?;
+
+        Ok(())
+    }
+
+    /// Burns `shares` and pays out the lamports they are worth.
+    /// - Both conversions round down, so any remainder stays with the pool.
+    pub fn pool_withdraw(ctx: Context<PoolWithdraw>, shares: u64) -> Result<()> {
+        ctx.accounts.withdraw(shares)?;
+
+        Ok(())
+    }
+
+    /// Moves `amount` lamports from the vault into its stake account, with the vault PDA as
+    /// the stake and withdraw authority.
+    /// - `amount` has to cover the stake account's rent plus the Stake program's minimum delegation.
+    pub fn create_stake(ctx: Context<CreateStake>, amount: u64) -> Result<()> {
+        ctx.accounts.create_stake(amount, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Delegates the vault's stake account to `vote_account`.
+    pub fn delegate_stake(ctx: Context<DelegateStake>) -> Result<()> {
+        ctx.accounts.delegate_stake()?;
+
+        Ok(())
+    }
+
+    pub fn deactivate_stake(ctx: Context<DeactivateStake>) -> Result<()> {
+        ctx.accounts.deactivate_stake()?;
+
+        Ok(())
+    }
+
+    /// Returns the whole stake account to the vault once it is fully deactivated.
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>) -> Result<()> {
+        ctx.accounts.withdraw_stake()?;
+
+        Ok(())
+    }
+
+    /// Creates the vault's allowlist of withdrawal destinations, empty and with the given `delay`.
+    pub fn initialize_allowlist(ctx: Context<InitializeAllowlist>, delay: i64) -> Result<()> {
+        ctx.accounts.initialize_allowlist(delay, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Allows withdrawals to `destination`, starting once the allowlist's delay has passed.
+    pub fn add_destination(ctx: Context<ManageAllowlist>, destination: Pubkey) -> Result<()> {
+        ctx.accounts.add_destination(destination)?;
+
+        Ok(())
+    }
+
+    pub fn remove_destination(ctx: Context<ManageAllowlist>, destination: Pubkey) -> Result<()> {
+        ctx.accounts.remove_destination(destination)?;
+
+        Ok(())
+    }
+
+    /// Sets how long new destinations wait before they are usable.
+    /// - A longer delay applies immediately, a shorter one only after the current delay.
+    pub fn set_allowlist_delay(ctx: Context<ManageAllowlist>, delay: i64) -> Result<()> {
+        ctx.accounts.set_allowlist_delay(delay)?;
+
+        Ok(())
+    }
+
+    /// Withdraws `amount` lamports to an allowlisted `destination` instead of the owner.
+    pub fn withdraw_to(ctx: Context<WithdrawTo>, amount: u64) -> Result<()> {
+        ctx.accounts.withdraw_to(amount)?;
+
+        Ok(())
+    }
+
+    /// Pays `recipient` `amount_per_period` lamports for every `period` seconds elapsed since `start`.
+    /// - Payouts only happen when someone calls `crank_stream`, which anyone can.
+    pub fn create_stream(
+        ctx: Context<CreateStream>,
+        amount_per_period: u64,
+        period: i64,
+        start: i64,
+    ) -> Result<()> {
+        ctx.accounts.create_stream(amount_per_period, period, start, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Pays out every period of the stream that has elapsed and was not paid yet.
+    /// - Payouts follow the same lock, cap and rent rules as `withdraw`.
+    /// - If the cap or balance cannot cover every elapsed period, as many whole periods as they
+    ///   allow are paid and the rest stays owed.
+    pub fn crank_stream(ctx: Context<CrankStream>) -> Result<()> {
+        ctx.accounts.crank_stream()?;
+
+        Ok(())
+    }
+
+    /// Creates the program-wide config with the signer as admin. Can only ever run once, signed by
+    /// the program's upgrade authority.
+    /// - `initialize` and `deposit` need the config, so this is the first instruction to send
+    ///   after deploying.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        ctx.accounts.initialize_config(&ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Pauses or resumes everything that opens a vault or brings funds in: `initialize`,
+    /// `deposit`, `deposit_tokens`, `initialize_pool`, `pool_deposit`, `initialize_multisig` and
+    /// `multisig_deposit`.
+    /// - Withdrawals and closing ignore the pause, so users can always take their funds out.
+    pub fn set_paused(ctx: Context<Administer>, paused: bool) -> Result<()> {
+        ctx.accounts.set_paused(paused)?;
+
+        Ok(())
+    }
+
+    /// Offers the admin role to `new_admin`, who takes over by calling `accept_admin`.
+    /// - `Pubkey::default()` withdraws a pending offer.
+    pub fn propose_admin(ctx: Context<Administer>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.propose_admin(new_admin)?;
+
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()?;
+
+        Ok(())
+    }
+
+    /// Stops the stream; the `close` constraint does all the work.
+    /// - Elapsed periods nobody cranked yet are not paid, so crank first to settle them.
+    pub fn cancel_stream(ctx: Context<CancelStream>) -> Result<()> {
+        ctx.accounts.cancel_stream()?;
+
+        Ok(())
+    }
+
+    /// Tells the vault its owner is still around, without changing anything else.
+    /// - Every other owner action does the same, this is for owners who have nothing else to do.
+    pub fn ping(ctx: Context<Configure>) -> Result<()> {
+        ctx.accounts.ping()?;
+
+        Ok(())
+    }
+
+    /// Names a `beneficiary` who can claim the vault's balance once the owner has not acted on
+    /// it for `inactivity_period` seconds. `Pubkey::default()` removes the beneficiary.
+    pub fn set_beneficiary(ctx: Context<Configure>, beneficiary: Pubkey, inactivity_period: i64) -> Result<()> {
+        ctx.accounts.set_beneficiary(beneficiary, inactivity_period)?;
+
+        Ok(())
+    }
+
+    /// Sends the vault's balance to its beneficiary once the owner's inactivity window has expired.
+    /// - The time lock still applies. A stake account the vault has is handed to the beneficiary,
+    ///   who becomes its staker and withdrawer.
+    /// - Token vaults are swept to the beneficiary, with remaining accounts laid out as for `close`.
+    /// - The claim restarts the inactivity window, so the vault cannot be claimed again at once.
+    pub fn claim_inheritance<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimInheritance<'info>>) -> Result<()> {
+        ctx.accounts.claim_inheritance(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    /// Lets `session_key` withdraw up to `spend_limit` lamports in total until `expires_at`.
+    /// - The lamports always go to the owner. Deposits need no session, anyone can deposit.
+    pub fn create_session(
+        ctx: Context<CreateSession>,
+        session_key: Pubkey,
+        expires_at: i64,
+        spend_limit: u64,
+    ) -> Result<()> {
+        ctx.accounts.create_session(session_key, expires_at, spend_limit, &ctx.bumps)?;
+
+        Ok(())
+    }
+
+    /// Ends a session before it expires; the `close` constraint does all the work.
+    pub fn revoke_session(ctx: Context<RevokeSession>) -> Result<()> {
+        ctx.accounts.revoke_session()?;
+
+        Ok(())
+    }
+
+    /// Withdraws `amount` to the owner on the session key's signature.
+    /// - The lock and the withdrawal cap apply on top of the session's own limit.
+    pub fn session_withdraw(ctx: Context<SessionWithdraw>, amount: u64) -> Result<()> {
+        ctx.accounts.session_withdraw(amount)?;
+
+        Ok(())
+    }
+
+    /// Moves a vault state written before versioning to the current layout, in place.
+    /// - The state keeps its `[b"state", user]` address and the vault its lamports; every new
+    ///   field starts out as `initialize` would set it. The vault is listed in the index as `name`.
+    pub fn migrate(ctx: Context<Migrate>, name: String) -> Result<()> {
+        ctx.accounts.migrate(name, &ctx.bumps)?;
 
         Ok(())
     }
@@ -41,6 +485,7 @@ pub mod anchor_vault {
 
 
 #[derive(Accounts)]
+#[instruction(id: u64)]
 pub struct Initialize<'info> {
     /// The user initializing the vault state account. Must sign the transaction.
     #[account(mut)] // mutable because it needs to pay for account creation which will change the num of lamports in account.
@@ -48,14 +493,14 @@ pub struct Initialize<'info> {
 
     /// The VaultState account to store the PDA bumps and relevant state data.
     /// - Initializes with the user's funds (`payer = user`).
-    /// - Uses a PDA derived from the seed "state" and the user's public key.
-    /// - Allocates space based on `VaultState::INIT_SPACE`.
+    /// - Uses a PDA derived from the seed "state", the user's public key and the vault id.
+    /// - Allocates space based on `VaultState::LEN`.
     #[account(
         init,                       // Indicates this account is created in this instruction
         payer = user,               // Specifies the user as the payer for account creation
-        seeds = [b"state", user.key().as_ref()], // Seeds for PDA derivation [byte representation]
+        seeds = [b"state", user.key().as_ref(), id.to_le_bytes().as_ref()], // Seeds for PDA derivation [byte representation]
         bump,                       // Automatically calculates and stores the PDA [canonical bump] in the account
-        space = VaultState::INIT_SPACE, // Allocates the required space for VaultState struct data
+        space = VaultState::LEN, // Allocates the required space for VaultState struct data
     )]
     pub vault_state: Account<'info, VaultState>,
 
@@ -69,95 +514,93 @@ pub struct Initialize<'info> {
     )]
     pub vault: SystemAccount<'info>,
 
+    /// The list of the user's vaults, created along with their first vault.
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"vaults", user.key().as_ref()],
+        bump,
+        space = VaultIndex::INIT_SPACE,
+    )]
+    pub index: Account<'info, VaultIndex>,
+
+    /// The program-wide config; no new vaults are opened while it is paused.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused,
+    )]
+    pub config: Account<'info, Config>,
+
     /// Reference to the System Program, required for account initialization and funding.
     pub system_program: Program<'info, System>,
 }
 
-// #[derive(Accounts)]
-// pub struct Deposit<'info> {
-//     /// The user signing the transaction to deposit SOL into the vault.
-//     /// as we are deducting lamports from user it must be mutable
-//     #[account(mut)]
-//     pub user: Signer<'info>,
-
-//     /// The vault account to receive deposited funds.
-//     /// - Mutable because funds will be transferred to this account.
-//     /// - Uses a PDA derived from `vault_state`'s public key with the seed "vault".
-//     /// - No need to recalculate the bump; instead, we use the stored bump (`vault_state.vault_bump`) for verification.
-//     #[account(
-//         mut,                                          // Allows the vault balance to increase with deposits
-//         seeds = [b"vault", vault_state.key().as_ref()], // PDA seeds derived from "vault" and `vault_state` key
-//         bump = vault_state.vault_bump,                 // Uses the pre-stored bump from `vault_state` to locate PDA
-//     )]
-//     pub vault: SystemAccount<'info>,
-
-//     /// The account storing state and PDA bump values for `vault` and `state`.
-//     /// - Uses a PDA derived from the user's key with the seed "state".
-//     /// - Uses the stored bump (`state_bump`) to find the exact PDA.
-//     /// - No need to be mut because we are not altering anything.
-//     #[account(
-//         seeds = [b"state", user.key().as_ref()],      // PDA seeds derived from "state" and user's public key
-//         bump = vault_state.state_bump,                // Uses the pre-stored bump for PDA verification
-//     )]
-//     pub vault_state: Account<'info, VaultState>,
-
-//     /// System Program reference, needed to transfer native SOL.
-//     pub system_program: Program<'info, System>,       // Essential for SOL transfers within CPI
-// }
-
-
-// impl<'info> Deposit<'info> {
-//     /// Transfers a specified `amount` of SOL from `user` to `vault`.
-//     pub fn deposit(&mut self, amount: u64) -> Result<()> {
-//         // Sets up the CPI context for the system program's `transfer` function
-//         let cpi_program = self.system_program.to_account_info();
-
-//         // Specifies the accounts involved in the transfer
-//         let cpi_accounts = Transfer {
-//             from: self.user.to_account_info(),       // Source: `user`'s account
-//             to: self.vault.to_account_info(),        // Destination: `vault` account
-//         };
-
-//         // Builds the CPI context for transferring SOL
-//         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-
-//         // Executes the SOL transfer
-//         transfer(cpi_ctx, amount)?;
-
-//         Ok(())
-//     }
-// }
- // since both deposit and withdraw has same context we can create one context as operation and implemet both deposit and withdraw inside this
 #[derive(Accounts)]
-pub struct Operation<'info> {
-    #[account(mut)] // Marks the user's account as mutable because they will receive lamports, modifying their balance
-    pub user: Signer<'info>, // user is signer because we need to varify the if the vault is derived from the user otherwise anyone will be able to empty my vault
+pub struct Deposit<'info> {
+    /// The account paying the deposit. Anyone can fund a vault: the owner, payroll, another program.
+    /// as we are deducting lamports from depositor it must be mutable
+    #[account(mut)]
+    pub depositor: Signer<'info>,
 
+    /// CHECK: the vault owner, named so depositors say whose vault they fund; checked by `has_one` on `vault_state`, it does not sign or receive anything
+    pub owner: UncheckedAccount<'info>,
+
+    /// The vault account to receive deposited funds.
+    /// - Mutable because funds will be transferred to this account.
+    /// - Uses a PDA derived from `vault_state`'s public key with the seed "vault".
+    /// - No need to recalculate the bump; instead, we use the stored bump (`vault_state.vault_bump`) for verification.
     #[account(
-        mut, // Allows the vault account balance to be modified as lamports will be withdrawn from it
-        seeds = [b"vault", vault_state.key().as_ref()], // Seeds used to derive this vault account address
-        bump = vault_state.vault_bump, // The "bump" seed for the derived address, ensuring uniqueness
+        mut,                                          // Allows the vault balance to increase with deposits
+        seeds = [b"vault", vault_state.key().as_ref()], // PDA seeds derived from "vault" and `vault_state` key
+        bump = vault_state.vault_bump,                 // Uses the pre-stored bump from `vault_state` to locate PDA
     )]
-    pub vault: SystemAccount<'info>, // System account representing the vault holding the lamports
+    pub vault: SystemAccount<'info>,
 
+    /// The account storing state and PDA bump values for `vault` and `state`.
+    /// - Uses a PDA derived from the vault's creator and id with the seed "state", and must belong to the named owner.
+    /// - Uses the stored bump (`state_bump`) to find the exact PDA.
+    /// - Mutable because the deposit is added to `total_deposited`.
     #[account(
-        seeds = [b"state", user.key().as_ref()], // Seeds to derive the `vault_state` account address using the user's key
-        bump = vault_state.state_bump, // Bump seed to uniquely identify this state account
+        mut,                                          // Deposits are counted in the totals stored here
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()], // PDA seeds derived from "state", the vault's creator and its id
+        bump = vault_state.state_bump,                // Uses the pre-stored bump for PDA verification
+        has_one = owner,                              // The named owner must be the vault's current owner
     )]
-    pub vault_state: Account<'info, VaultState>, // Stores state information for the vault, including bumps and other metadata
+    pub vault_state: Account<'info, VaultState>,
 
-    pub system_program: Program<'info, System> // Reference to the System Program for transferring lamports
+    /// The program-wide config; deposits are rejected while it is paused.
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused,
+    )]
+    pub config: Account<'info, Config>,
+
+    /// System Program reference, needed to transfer native SOL.
+    pub system_program: Program<'info, System>,       // Essential for SOL transfers within CPI
 }
 
-impl<'info> Operation<'info> {
 
+impl<'info> Deposit<'info> {
+    /// Transfers a specified `amount` of SOL from `depositor` to `vault`.
     pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        // Rejects deposits the system program would only fail on with an opaque CPI error
+        require!(amount > 0, VaultError::ZeroAmount);
+        require!(
+            self.depositor.lamports() >= amount,
+            VaultError::InsufficientFunds
+        );
+
+        // The first deposit creates the vault, so it has to cover the vault's rent on its own
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_add(amount))?;
+
         // Sets up the CPI context for the system program's `transfer` function
         let cpi_program = self.system_program.to_account_info();
 
         // Specifies the accounts involved in the transfer
         let cpi_accounts = Transfer {
-            from: self.user.to_account_info(),       // Source: `user`'s account
+            from: self.depositor.to_account_info(),  // Source: `depositor`'s account
             to: self.vault.to_account_info(),        // Destination: `vault` account
         };
 
@@ -167,11 +610,70 @@ impl<'info> Operation<'info> {
         // Executes the SOL transfer
         transfer(cpi_ctx, amount)?;
 
+        // Adds the deposit to the running totals and tells off-chain accounting about it
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state.record_deposit(amount, now);
+
+        // Only the owner's own deposits count as a sign of life, anyone can fund a vault
+        if self.depositor.key() == self.owner.key() {
+            self.vault_state.record_heartbeat()?;
+        }
+
+        emit!(VaultDeposited {
+            vault_state: self.vault_state.key(),
+            depositor: self.depositor.key(),
+            amount,
+            total_deposited: self.vault_state.total_deposited,
+            timestamp: now,
+        });
+
         Ok(())
     }
+}
+
+// Withdrawals move funds out of the vault, so unlike deposits they need the owner's signature
+#[derive(Accounts)]
+pub struct Operation<'info> {
+    #[account(mut)] // Marks the user's account as mutable because they will receive lamports, modifying their balance
+    pub user: Signer<'info>, // user is signer because we need to varify the if the vault is derived from the user otherwise anyone will be able to empty my vault
+
+    #[account(
+        mut, // Allows the vault account balance to be modified as lamports will be withdrawn from it
+        seeds = [b"vault", vault_state.key().as_ref()], // Seeds used to derive this vault account address
+        bump = vault_state.vault_bump, // The "bump" seed for the derived address, ensuring uniqueness
+    )]
+    pub vault: SystemAccount<'info>, // System account representing the vault holding the lamports
+
+    #[account(
+        mut, // Withdrawals are counted against the withdrawal cap stored here
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()], // Seeds to derive the `vault_state` account address from its creator and id, which never change
+        bump = vault_state.state_bump, // Bump seed to uniquely identify this state account
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner, // Only the current owner may withdraw
+    )]
+    pub vault_state: Account<'info, VaultState>, // Stores state information for the vault, including bumps and other metadata
+
+    pub system_program: Program<'info, System> // Reference to the System Program for transferring lamports
+}
 
+impl<'info> Operation<'info> {
     // Withdraw function to transfer specified amount of lamports from the vault to the user's account
     pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        // Rejects withdrawals the system program would only fail on with an opaque CPI error
+        check_withdrawable(&self.vault, amount)?;
+
+        // Withdrawing is an owner action, so it keeps the inheritance switch from firing
+        self.vault_state.record_heartbeat()?;
+
+        // Funds stay in the vault until its time lock has expired
+        self.vault_state.check_unlocked()?;
+
+        // Counts the withdrawal against the cap of the current window
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state.record_withdrawal(amount, now)?;
+
+        // A partial withdrawal must leave the vault rent exempt; withdrawing everything is always fine
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
         // Prepare the `to_account_info` for `system_program` to perform the CPI (Cross-Program Invocation) transfer
         let cpi_program = self.system_program.to_account_info();
 
@@ -199,6 +701,15 @@ impl<'info> Operation<'info> {
         // Execute the transfer, moving `amount` lamports from the vault to the user's account
         transfer(cpi_ctx, amount)?;
 
+        // Tells off-chain accounting about the withdrawal
+        emit!(VaultWithdrawn {
+            vault_state: self.vault_state.key(),
+            destination: self.user.key(),
+            amount,
+            total_withdrawn: self.vault_state.total_withdrawn,
+            timestamp: now,
+        });
+
         Ok(()) // Return success if transfer is completed without error
     }
 
@@ -220,11 +731,19 @@ pub struct Close<'info> {
     
     #[account(
         mut, // `vault_state` is mutable.
-        seeds = [b"state", user.key().as_ref()], // Derives vault_state's address from a seed prefix and user's public key.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()], // Derives vault_state's address from a seed prefix, its creator and the vault id.
         bump = vault_state.state_bump, // Matches the bump used in the PDA derivation.
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner, // Only the current owner may close the vault.
         close = user, // Closes `vault_state` account and returns remaining lamports to `user`.
     )]
     pub vault_state: Account<'info, VaultState>, // Custom account holding vault-related state.
+
+    #[account(
+        mut, // The closed vault is dropped from the user's list.
+        seeds = [b"vaults", user.key().as_ref()],
+        bump = index.bump,
+    )]
+    pub index: Account<'info, VaultIndex>,
     
     pub system_program: Program<'info, System>, // Reference to the system program, used for transferring lamports.
 }
@@ -232,7 +751,30 @@ pub struct Close<'info> {
 impl<'info> Close<'info> {
     // The `close` function transfers all lamports from the `vault` account to the `user` account
     // and then marks the `vault_state` account to be closed.
-    pub fn close(&mut self) -> Result<()> {
+    pub fn close(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        // Closing would release the funds as well, so it honours the time lock too
+        self.vault_state.check_unlocked()?;
+
+        // Stake is owned by the vault PDA, closing now would leave it behind untracked
+        self.vault_state.check_unstaked()?;
+
+        // Token vaults are owned by `vault_state`, so they have to go before it does
+        sweep_token_vaults(&mut self.vault_state, &self.user.to_account_info(), token_accounts)?;
+
+        let vault_state = self.vault_state.key();
+        self.index.remove(&vault_state);
+
+        // Nothing to sweep if everything was already withdrawn, the vault PDA no longer exists
+        let now = Clock::get()?.unix_timestamp;
+        let balance = self.vault.lamports();
+        if balance == 0 {
+            emit!(self.vault_state.closed_event(vault_state, self.user.key(), 0, now));
+            return Ok(());
+        }
+
+        // Closing is a withdrawal of everything, so it cannot be used to get around the cap
+        self.vault_state.record_withdrawal(balance, now)?;
+
         // Setup CPI (cross-program invocation) to perform a lamport transfer.
         let cpi_program = self.system_program.to_account_info(); // System program account.
         
@@ -258,8 +800,12 @@ impl<'info> Close<'info> {
             signer_seeds,
         );
         
-        // Perform the transfer from `vault` to `user` for the vault's entire lamport balance.
-        transfer(cpi_ctx, self.vault.lamports())?;
+        // Perform the transfer from `vault` to `user` for the vault's entire lamport balance,
+        // which leaves the vault PDA empty so `initialize` can be called again later.
+        transfer(cpi_ctx, balance)?;
+
+        // The final totals go out with the event, as `vault_state` is gone after this instruction
+        emit!(self.vault_state.closed_event(vault_state, self.user.key(), balance, now));
 
         Ok(())
     }
@@ -270,31 +816,499 @@ impl<'info> Initialize<'info> {
     /// Initializes the `vault_state` account by setting its stored bump values.
     /// - Uses the bump values from the `InitializeBumps` struct, which contains pre-calculated bumps for PDAs.
     /// - This function is called once during setup to store the bumps for later use in PDA derivations.
-    pub fn initialize(&mut self, bumps: &InitializeBumps) -> Result<()> {
+    pub fn initialize(&mut self, id: u64, name: String, unlock_at: Option<i64>, bumps: &InitializeBumps) -> Result<()> {
+        // Lists the new vault in the user's index, creating the index with their first vault
+        self.index.set_up(self.user.key(), bumps.index);
+        self.index.add(self.vault_state.key(), name)?;
+
+        // The index outlives the vault, so a vault reopened under the same id is a new generation
+        self.vault_state.generation = self.index.next_generation();
+
+        // The creator and id derive the `state` PDA for good, while the owner can change hands
+        self.vault_state.owner = self.user.key();
+        self.vault_state.creator = self.user.key();
+        self.vault_state.id = id;
+        self.vault_state.version = VAULT_STATE_VERSION;
+
         // Stores the bump for the `vault` PDA in `vault_state`
         self.vault_state.vault_bump = bumps.vault;
         
         // Stores the bump for the `state` PDA in `vault_state`
         self.vault_state.state_bump = bumps.vault_state;
 
+        // Without a lock the vault is unlocked from the start, as every timestamp is >= 0
+        self.vault_state.unlock_at = unlock_at.unwrap_or(0);
+
+        // Opening the vault is the owner's first sign of life
+        self.vault_state.record_heartbeat()?;
+
         Ok(())
     }
 }
 
 
 #[account]
+#[derive(InitSpace)]
 
 // The VaultState struct holds the bump values for the vault and state PDAs.
 // Bumps ensure the derived addresses are unique and help with re-deriving them.(basically to find out the exact PDA)
 pub struct VaultState {
+    /// Layout the account was written with, first so it sits at the same offset in every layout.
+    pub version: u8,
+
     /// Bump seed for the vault PDA. Used to recreate the PDA and verify its uniqueness.
     pub vault_bump: u8,
 
     /// Bump seed for the state PDA. Similar to `vault_bump`, used to recreate the state PDA.
     pub state_bump: u8,
+
+    /// Wallet that controls the vault. Starts as the creator and changes through `accept_owner`.
+    pub owner: Pubkey,
+
+    /// Wallet that created the vault, part of the state PDA seeds so the address never changes.
+    pub creator: Pubkey,
+
+    /// Which of the creator's vaults this is, part of the state PDA seeds.
+    pub id: u64,
+
+    /// Wallet that may accept ownership, `Pubkey::default()` when no handover is pending.
+    pub pending_owner: Pubkey,
+
+    /// Unix timestamp before which `withdraw` and `close` are rejected. Only ever moves later.
+    pub unlock_at: i64,
+
+    /// Most lamports that may leave the vault per `WITHDRAW_WINDOW`, `0` when uncapped.
+    pub withdraw_cap: u64,
+
+    /// Start of the current withdrawal window.
+    pub window_start: i64,
+
+    /// Lamports withdrawn since `window_start`.
+    pub withdrawn_in_window: u64,
+
+    /// Cap that replaces `withdraw_cap` once `pending_cap_at` has passed.
+    pub pending_cap: u64,
+
+    /// When `pending_cap` takes effect, `0` when no change is pending.
+    pub pending_cap_at: i64,
+
+    /// Keys allowed to propose and approve a recovery of the vault.
+    #[max_len(MAX_GUARDIANS)]
+    pub guardians: Vec<Pubkey>,
+
+    /// Approvals a recovery needs before it can execute.
+    pub guardian_threshold: u8,
+
+    /// Mints the vault has a token vault for, so `close` can sweep them all.
+    #[max_len(MAX_TOKEN_VAULTS)]
+    pub token_mints: Vec<Pubkey>,
+
+    /// Lamports moved from the vault into its stake account. Liquid lamports are whatever the
+    /// vault PDA itself holds.
+    pub staked: u64,
+
+    /// Lamports deposited into the vault over its lifetime.
+    pub total_deposited: u64,
+
+    /// Lamports paid out of the vault over its lifetime, to the owner or anyone else.
+    pub total_withdrawn: u64,
+
+    /// Last time lamports were deposited into or paid out of the vault.
+    pub last_activity: i64,
+
+    /// Wallet that can claim the vault's balance once the owner has been inactive for
+    /// `inactivity_period`, `Pubkey::default()` when there is none.
+    pub beneficiary: Pubkey,
+
+    /// Seconds without an owner action after which `beneficiary` can claim the vault.
+    pub inactivity_period: i64,
+
+    /// Last time the owner did anything with the vault.
+    pub last_heartbeat: i64,
+
+    /// Set for vaults migrated from before multiple vaults, whose state PDA is still
+    /// `[b"state", creator]` without the id.
+    pub legacy_address: bool,
+
+    /// Changes whenever the guardian set does, so recoveries proposed under an older set lapse.
+    pub guardian_nonce: u64,
+
+    /// Tells this vault apart from earlier ones closed at the same address. Streams, sessions,
+    /// allowlists and recoveries only work for the generation they were created under.
+    pub generation: u64,
+
+    /// Room for new fields, so they can be added without reallocating existing vaults.
+    pub reserved: [u8; 48],
+}
+
+
+impl VaultState {
+    pub const LEN: usize = 8 + VaultState::INIT_SPACE;
 }
 
+impl VaultState {
+    /// The id's part of the state PDA seeds, empty for vaults that kept their legacy address.
+    pub fn id_seed(&self) -> Vec<u8> {
+        if self.legacy_address {
+            Vec::new()
+        } else {
+            self.id.to_le_bytes().to_vec()
+        }
+    }
+
+    /// Fails with `VaultLocked` while the vault's time lock has not expired yet.
+    pub fn check_unlocked(&self) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp >= self.unlock_at,
+            VaultError::VaultLocked
+        );
+
+        Ok(())
+    }
+
+    /// Fails with `StakeActive` while lamports are still out in the vault's stake account.
+    pub fn check_unstaked(&self) -> Result<()> {
+        require!(self.staked == 0, VaultError::StakeActive);
+
+        Ok(())
+    }
+
+    pub fn check_guardian(&self, key: &Pubkey) -> Result<()> {
+        require!(self.guardians.contains(key), VaultError::NotGuardian);
+
+        Ok(())
+    }
+
+    /// Lets `init_if_needed` take over an account an earlier generation of this vault left behind.
+    /// - `vault_state` and `generation` are read from the account, and a fresh one has neither.
+    pub fn check_replaceable(&self, vault_state: &Pubkey, generation: u64) -> Result<()> {
+        require!(
+            *vault_state == Pubkey::default() || generation != self.generation,
+            VaultError::AccountInUse
+        );
+
+        Ok(())
+    }
+
+    /// Promotes `pending_cap` to `withdraw_cap` once its delay has passed.
+    pub fn apply_pending_cap(&mut self, now: i64) {
+        if self.pending_cap_at != 0 && now >= self.pending_cap_at {
+            self.withdraw_cap = self.pending_cap;
+            self.pending_cap = 0;
+            self.pending_cap_at = 0;
+        }
+    }
+
+    /// Records that the owner is still around, restarting the inactivity window.
+    pub fn record_heartbeat(&mut self) -> Result<()> {
+        self.last_heartbeat = Clock::get()?.unix_timestamp;
+
+        Ok(())
+    }
+
+    /// Adds `amount` to the lifetime deposit total.
+    pub fn record_deposit(&mut self, amount: u64, now: i64) {
+        self.total_deposited = self.total_deposited.saturating_add(amount);
+        self.last_activity = now;
+    }
+
+    /// Lamports `record_withdrawal` would still accept at `now`, `u64::MAX` when uncapped.
+    pub fn cap_remaining(&mut self, now: i64) -> u64 {
+        self.apply_pending_cap(now);
+
+        if self.withdraw_cap == 0 {
+            return u64::MAX;
+        }
+
+        if now >= self.window_start.saturating_add(WITHDRAW_WINDOW) {
+            return self.withdraw_cap;
+        }
+
+        self.withdraw_cap.saturating_sub(self.withdrawn_in_window)
+    }
+
+    /// Adds `amount` to the lifetime total and to the lamports withdrawn in the current window,
+    /// failing with `WithdrawalLimitExceeded` if that would go over `withdraw_cap`.
+    pub fn record_withdrawal(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
+        self.last_activity = now;
+
+        self.apply_pending_cap(now);
+
+        if self.withdraw_cap == 0 {
+            return Ok(());
+        }
+
+        // A new window starts once the previous one is over
+        if now >= self.window_start.saturating_add(WITHDRAW_WINDOW) {
+            self.window_start = now;
+            self.withdrawn_in_window = 0;
+        }
+
+        let withdrawn = self.withdrawn_in_window.saturating_add(amount);
+        require!(
+            withdrawn <= self.withdraw_cap,
+            VaultError::WithdrawalLimitExceeded
+        );
+        self.withdrawn_in_window = withdrawn;
+
+        Ok(())
+    }
+
+    /// The `VaultClosed` event for sweeping the last `amount` lamports of this vault to `owner`.
+    pub fn closed_event(&self, vault_state: Pubkey, owner: Pubkey, amount: u64, now: i64) -> VaultClosed {
+        VaultClosed {
+            vault_state,
+            owner,
+            amount,
+            total_deposited: self.total_deposited,
+            total_withdrawn: self.total_withdrawn,
+            timestamp: now,
+        }
+    }
+}
+
+
+#[derive(Accounts)]
+// The `Configure` struct only needs the owner and their vault state, no lamports move.
+pub struct Configure<'info> {
+    pub user: Signer<'info>, // Only the owner may change the settings of their vault.
+
+    #[account(
+        mut, // Settings are updated in place.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()], // Derives vault_state's address from its creator and the vault id.
+        bump = vault_state.state_bump, // Matches the bump used in the PDA derivation.
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner, // Only the current owner may change settings.
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+impl<'info> Configure<'info> {
+    pub fn ping(&mut self) -> Result<()> {
+        self.vault_state.record_heartbeat()
+    }
+
+    pub fn set_beneficiary(&mut self, beneficiary: Pubkey, inactivity_period: i64) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // The owner can always withdraw themselves, inheriting only makes sense for someone else
+        if beneficiary != Pubkey::default() {
+            require!(
+                beneficiary != self.user.key() && inactivity_period > 0,
+                VaultError::InvalidBeneficiary
+            );
+        }
+
+        self.vault_state.beneficiary = beneficiary;
+        self.vault_state.inactivity_period = inactivity_period;
+
+        Ok(())
+    }
+
+    pub fn extend_lock(&mut self, unlock_at: i64) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // Shortening the lock would defeat its purpose, so the new time must be strictly later
+        require!(
+            unlock_at > self.vault_state.unlock_at,
+            VaultError::LockNotExtended
+        );
+
+        self.vault_state.unlock_at = unlock_at;
+
+        Ok(())
+    }
+
+    pub fn set_withdraw_cap(&mut self, cap: u64) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let vault_state = &mut self.vault_state;
+
+        vault_state.apply_pending_cap(now);
+
+        // A lower cap can only make a stolen key less useful, so it applies right away and
+        // replaces any pending change. Anything looser has to wait out the delay.
+        let tightens = cap != 0 && (vault_state.withdraw_cap == 0 || cap <= vault_state.withdraw_cap);
+
+        if tightens {
+            vault_state.withdraw_cap = cap;
+            vault_state.pending_cap = 0;
+            vault_state.pending_cap_at = 0;
+        } else {
+            vault_state.pending_cap = cap;
+            vault_state.pending_cap_at = now.saturating_add(CAP_CHANGE_DELAY);
+        }
+
+        Ok(())
+    }
+
+    pub fn propose_owner(&mut self, new_owner: Pubkey) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // Handing the vault to its current owner would be a no-op that still blocks the slot
+        require_keys_neq!(new_owner, self.user.key(), VaultError::InvalidNewOwner);
+
+        // The handover only completes once `new_owner` signs for it, so a typo cannot lose the vault
+        self.vault_state.pending_owner = new_owner;
+
+        Ok(())
+    }
+
+    pub fn set_guardians(&mut self, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // The owner cannot be their own guardian, recovery is for when their key is gone
+        let user = self.user.key();
+        let unique = guardians
+            .iter()
+            .enumerate()
+            .all(|(i, guardian)| *guardian != user && !guardians[..i].contains(guardian));
+
+        let valid = if guardians.is_empty() {
+            threshold == 0
+        } else {
+            threshold >= 1 && threshold as usize <= guardians.len()
+        };
+
+        require!(
+            unique && valid && guardians.len() <= MAX_GUARDIANS,
+            VaultError::InvalidGuardians
+        );
+
+        self.vault_state.guardians = guardians;
+        self.vault_state.guardian_threshold = threshold;
+
+        // Approvals were given for the old set, a pending recovery has to start over
+        self.vault_state.guardian_nonce = self.vault_state.guardian_nonce.wrapping_add(1);
+
+        Ok(())
+    }
+}
+
+/// Fails with `ZeroAmount` for an empty withdrawal and with `InsufficientFunds` if the vault
+/// holds less than `amount`.
+pub(crate) fn check_withdrawable(vault: &SystemAccount, amount: u64) -> Result<()> {
+    require!(amount > 0, VaultError::ZeroAmount);
+    require!(vault.lamports() >= amount, VaultError::InsufficientFunds);
+
+    Ok(())
+}
+
+/// Checks that the vault ends up either empty or rent exempt after a transfer.
+/// - A system account left with fewer lamports than its rent-exempt minimum would be rent-paying,
+///   which the runtime rejects with an opaque error, so we fail early with `RentExemptionViolation`.
+pub(crate) fn check_rent_exempt(vault: &SystemAccount, balance: u64) -> Result<()> {
+    let minimum = Rent::get()?.minimum_balance(vault.data_len());
+
+    require!(
+        balance == 0 || balance >= minimum,
+        VaultError::RentExemptionViolation
+    );
+
+    Ok(())
+}
 
-impl Space for VaultState {
-    const INIT_SPACE: usize = 8 + 1 + 1; // 8 bytes for Anchor discriminator, 2 bytes for bumps (1 byte each)
+#[error_code]
+pub enum VaultError {
+    #[msg("Vault must stay rent exempt, withdraw the full balance or close the vault instead")]
+    RentExemptionViolation,
+    #[msg("Vault is time-locked, funds cannot leave it before its unlock time")]
+    VaultLocked,
+    #[msg("New unlock time must be later than the current one")]
+    LockNotExtended,
+    #[msg("Withdrawal exceeds the vault's withdrawal cap for the current window")]
+    WithdrawalLimitExceeded,
+    #[msg("Guardians must be distinct, exclude the owner and number at most MAX_GUARDIANS, with a threshold between 1 and their count")]
+    InvalidGuardians,
+    #[msg("Signer is not a guardian of this vault")]
+    NotGuardian,
+    #[msg("Signer already approved this request")]
+    AlreadyApproved,
+    #[msg("Request does not have enough approvals yet")]
+    NotEnoughApprovals,
+    #[msg("Recovery is still in its waiting period")]
+    RecoveryPending,
+    #[msg("Owners must be distinct and number at most MAX_OWNERS, with a threshold between 1 and their count")]
+    InvalidOwners,
+    #[msg("Signer is not an owner of this multisig")]
+    NotOwner,
+    #[msg("Vault already holds tokens of MAX_TOKEN_VAULTS mints")]
+    TooManyTokenVaults,
+    #[msg("Every token vault must be passed, in order, to be swept")]
+    TokenVaultsMissing,
+    #[msg("Amount is too small to be worth a single share or lamport")]
+    SharesRoundToZero,
+    #[msg("Share conversion overflowed")]
+    ShareMathOverflow,
+    #[msg("Vault has lamports in its stake account, withdraw the stake first")]
+    StakeActive,
+    #[msg("Staked balance overflowed")]
+    StakeMathOverflow,
+    #[msg("Delay cannot be negative")]
+    InvalidDelay,
+    #[msg("Destination is not on the vault's allowlist")]
+    DestinationNotAllowed,
+    #[msg("Destination is already on the vault's allowlist")]
+    DestinationAlreadyAllowed,
+    #[msg("Allowlist already holds MAX_DESTINATIONS destinations")]
+    AllowlistFull,
+    #[msg("Destination was added too recently to receive withdrawals yet")]
+    DestinationPending,
+    #[msg("Vault name must be between 1 and MAX_NAME_LEN bytes")]
+    InvalidVaultName,
+    #[msg("User already has MAX_VAULTS vaults open")]
+    TooManyVaults,
+    #[msg("Signer is not the owner of this vault")]
+    NotVaultOwner,
+    #[msg("New owner must differ from the current owner")]
+    InvalidNewOwner,
+    #[msg("Signer is not the pending owner of this vault")]
+    NotPendingOwner,
+    #[msg("Stream amount and period must be greater than zero")]
+    InvalidStream,
+    #[msg("No stream period has elapsed since the last payout")]
+    NothingDue,
+    #[msg("Stream payout overflowed")]
+    StreamMathOverflow,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Not enough lamports to cover the amount")]
+    InsufficientFunds,
+    #[msg("Program is paused, only withdrawals and closing are allowed")]
+    ProgramPaused,
+    #[msg("Signer is not the program admin")]
+    NotAdmin,
+    #[msg("New admin must differ from the current admin")]
+    InvalidNewAdmin,
+    #[msg("Signer is not the pending program admin")]
+    NotPendingAdmin,
+    #[msg("Beneficiary must differ from the owner and come with a positive inactivity period")]
+    InvalidBeneficiary,
+    #[msg("Signer is not the vault's beneficiary")]
+    NotBeneficiary,
+    #[msg("Owner was active too recently for the beneficiary to claim the vault")]
+    OwnerStillActive,
+    #[msg("Session must expire in the future and allow a non-zero amount")]
+    InvalidSession,
+    #[msg("Session has expired")]
+    SessionExpired,
+    #[msg("Withdrawal exceeds the session's spend limit")]
+    SessionLimitExceeded,
+    #[msg("Vault state already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Signer is not the program's upgrade authority")]
+    NotUpgradeAuthority,
+    #[msg("Recovery was proposed under an earlier guardian set or vault")]
+    StaleRecovery,
+    #[msg("Account belongs to an earlier vault that was closed at this address")]
+    StaleAccount,
+    #[msg("Account is already in use by this vault")]
+    AccountInUse,
+    #[msg("Stake must cover the stake account's rent and the minimum delegation")]
+    StakeBelowMinimum,
+    #[msg("Creator's index must be passed when neither the previous nor the new owner is the creator")]
+    InvalidCreatorIndex,
+    #[msg("Token vault still holds tokens, withdraw them before closing it")]
+    TokenVaultNotEmpty,
 }
@@ -0,0 +1,139 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+
+use crate::{VaultError, VaultIndex, VaultState, VAULT_STATE_VERSION};
+
+#[derive(Accounts)]
+// The `Migrate` struct upgrades a vault state written before versioning. Only the user whose key
+// derived the legacy address can run it, as the legacy layout does not record an owner.
+pub struct Migrate<'info> {
+    #[account(mut)] // Pays for the rent of the larger layout and, if needed, the index.
+    pub user: Signer<'info>,
+
+    /// CHECK: a legacy state cannot be deserialized as `VaultState`, so `migrate` checks its
+    /// discriminator, layout and address by hand.
+    #[account(mut, owner = crate::ID)]
+    pub vault_state: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"vaults", user.key().as_ref()],
+        bump,
+        space = VaultIndex::INIT_SPACE,
+    )]
+    pub index: Account<'info, VaultIndex>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Migrate<'info> {
+    pub fn migrate(&mut self, name: String, bumps: &MigrateBumps) -> Result<()> {
+        let legacy = self.load_legacy()?;
+        let user = self.user.key();
+
+        // Legacy vaults lived at one address per user, before the id was part of the seeds
+        let vault_state = Pubkey::create_program_address(
+            &[b"state", user.as_ref(), &[legacy.state_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(vault_state, self.vault_state.key(), ErrorCode::ConstraintSeeds);
+
+        self.index.set_up(user, bumps.index);
+        self.index.add(vault_state, name)?;
+        let generation = self.index.next_generation();
+
+        let rent = Rent::get()?.minimum_balance(VaultState::LEN);
+        let top_up = rent.saturating_sub(self.vault_state.lamports());
+
+        if top_up > 0 {
+            let cpi_accounts = Transfer {
+                from: self.user.to_account_info(),
+                to: self.vault_state.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+            transfer(cpi_ctx, top_up)?;
+        }
+
+        self.vault_state.realloc(VaultState::LEN, true)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let state = VaultState {
+            version: VAULT_STATE_VERSION,
+            vault_bump: bumps.vault,
+            state_bump: legacy.state_bump,
+            owner: user,
+            creator: user,
+            id: 0,
+            pending_owner: Pubkey::default(),
+            unlock_at: 0,
+            withdraw_cap: 0,
+            window_start: 0,
+            withdrawn_in_window: 0,
+            pending_cap: 0,
+            pending_cap_at: 0,
+            guardians: Vec::new(),
+            guardian_threshold: 0,
+            token_mints: Vec::new(),
+            staked: 0,
+            // Whatever the vault holds came in before totals were tracked, so it counts as deposited
+            total_deposited: self.vault.lamports(),
+            total_withdrawn: 0,
+            last_activity: 0,
+            beneficiary: Pubkey::default(),
+            inactivity_period: 0,
+            // Migrating is the owner's first sign of life in the new layout
+            last_heartbeat: now,
+            legacy_address: true,
+            guardian_nonce: 0,
+            generation,
+            reserved: [0; 48],
+        };
+
+        let mut data = self.vault_state.try_borrow_mut_data()?;
+        state.try_serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+
+    fn load_legacy(&self) -> Result<LegacyVaultState> {
+        let data = self.vault_state.try_borrow_data()?;
+
+        require!(
+            data.len() >= 8 && data[..8] == VaultState::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        require_neq!(data.len(), VaultState::LEN, VaultError::AlreadyMigrated);
+        require_eq!(
+            data.len(),
+            LegacyVaultState::LEN,
+            ErrorCode::AccountDidNotDeserialize
+        );
+
+        Ok(LegacyVaultState::deserialize(&mut &data[8..])?)
+    }
+}
+
+/// `VaultState` as it was stored before versioning: just the two PDA bumps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LegacyVaultState {
+    pub vault_bump: u8,
+    pub state_bump: u8,
+}
+
+impl LegacyVaultState {
+    // 8 bytes for Anchor discriminator and 2 bytes for bumps (1 byte each)
+    pub const LEN: usize = 8 + 1 + 1;
+}
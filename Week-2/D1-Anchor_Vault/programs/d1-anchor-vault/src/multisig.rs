@@ -0,0 +1,342 @@
+use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
+
+use crate::{check_rent_exempt, Config, VaultError, MAX_OWNERS};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+// The `InitializeMultisig` struct creates an M-of-N vault. Unlike a personal vault, it is derived
+// from its creator and a `seed`, and the creator has no special rights once it exists.
+pub struct InitializeMultisig<'info> {
+    #[account(mut)] // Pays for the `multisig` account.
+    pub creator: Signer<'info>,
+
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"multisig", creator.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = Multisig::INIT_SPACE,
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        seeds = [b"vault", multisig.key().as_ref()], // Same layout as a personal vault, keyed by the multisig.
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused, // No new funds come in while paused.
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeMultisig<'info> {
+    pub fn initialize_multisig(
+        &mut self,
+        seed: u64,
+        owners: Vec<Pubkey>,
+        threshold: u8,
+        bumps: &InitializeMultisigBumps,
+    ) -> Result<()> {
+        let unique = owners
+            .iter()
+            .enumerate()
+            .all(|(i, owner)| !owners[..i].contains(owner));
+
+        require!(
+            unique
+                && owners.len() <= MAX_OWNERS
+                && threshold >= 1
+                && threshold as usize <= owners.len(),
+            VaultError::InvalidOwners
+        );
+
+        self.multisig.set_inner(Multisig {
+            creator: self.creator.key(),
+            seed,
+            owners,
+            threshold,
+            proposal_count: 0,
+            vault_bump: bumps.vault,
+            bump: bumps.multisig,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `MultisigDeposit` struct lets anyone fund a multisig vault, no approvals needed.
+pub struct MultisigDeposit<'info> {
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", multisig.key().as_ref()],
+        bump = multisig.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused, // No new funds come in while paused.
+    )]
+    pub config: Account<'info, Config>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MultisigDeposit<'info> {
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_add(amount))?;
+
+        let cpi_accounts = Transfer {
+            from: self.depositor.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+        transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ProposeWithdrawal` struct opens a withdrawal from the multisig vault for the owners to approve.
+pub struct ProposeWithdrawal<'info> {
+    #[account(mut)] // Pays for the proposal and gets its rent back once it executes.
+    pub proposer: Signer<'info>,
+
+    #[account(
+        mut, // `proposal_count` moves on to the next proposal index.
+        seeds = [b"multisig", multisig.creator.as_ref(), multisig.seed.to_le_bytes().as_ref()],
+        bump = multisig.bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        init,
+        payer = proposer,
+        // Every proposal gets its own index, so an executed proposal can never be replayed.
+        seeds = [b"proposal", multisig.key().as_ref(), multisig.proposal_count.to_le_bytes().as_ref()],
+        bump,
+        space = Proposal::INIT_SPACE,
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeWithdrawal<'info> {
+    pub fn propose_withdrawal(
+        &mut self,
+        destination: Pubkey,
+        amount: u64,
+        bumps: &ProposeWithdrawalBumps,
+    ) -> Result<()> {
+        self.multisig.check_owner(&self.proposer.key())?;
+
+        // An empty withdrawal would only use up a proposal index
+        require!(amount > 0, VaultError::ZeroAmount);
+
+        // The proposal counts as the proposer's approval
+        self.proposal.set_inner(Proposal {
+            multisig: self.multisig.key(),
+            proposer: self.proposer.key(),
+            index: self.multisig.proposal_count,
+            destination,
+            amount,
+            approvals: vec![self.proposer.key()],
+            bump: bumps.proposal,
+        });
+
+        self.multisig.proposal_count += 1;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ApproveProposal` struct adds one owner's approval to a pending withdrawal.
+pub struct ApproveProposal<'info> {
+    pub owner: Signer<'info>,
+
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        mut, // `approvals` grows by one.
+        seeds = [b"proposal", multisig.key().as_ref(), proposal.index.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        has_one = multisig,
+    )]
+    pub proposal: Account<'info, Proposal>,
+}
+
+impl<'info> ApproveProposal<'info> {
+    pub fn approve_proposal(&mut self) -> Result<()> {
+        let owner = self.owner.key();
+        self.multisig.check_owner(&owner)?;
+
+        require!(
+            !self.proposal.approvals.contains(&owner),
+            VaultError::AlreadyApproved
+        );
+
+        self.proposal.approvals.push(owner);
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ExecuteProposal` struct pays out an approved withdrawal. Anyone may send it once the
+// proposal has reached the threshold.
+pub struct ExecuteProposal<'info> {
+    #[account(mut)] // Receives the withdrawn lamports.
+    pub destination: SystemAccount<'info>,
+
+    #[account(mut)] // Gets the rent of the executed proposal back.
+    pub proposer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", multisig.key().as_ref()],
+        bump = multisig.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", multisig.key().as_ref(), proposal.index.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        has_one = multisig,
+        has_one = destination,
+        has_one = proposer,
+        close = proposer, // Closing the proposal is what marks it executed.
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteProposal<'info> {
+    pub fn execute_proposal(&mut self) -> Result<()> {
+        require!(
+            self.proposal.approvals.len() >= self.multisig.threshold as usize,
+            VaultError::NotEnoughApprovals
+        );
+
+        let amount = self.proposal.amount;
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.destination.to_account_info(),
+        };
+
+        // The vault PDA signs for itself; no single owner holds a key that could
+        let seeds = &[
+            b"vault",
+            self.multisig.to_account_info().key.as_ref(),
+            &[self.multisig.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Multisig struct is the state of an M-of-N vault.
+pub struct Multisig {
+    /// Wallet that created the multisig, part of its PDA seeds.
+    pub creator: Pubkey,
+
+    /// Lets one creator set up several multisigs.
+    pub seed: u64,
+
+    /// Keys that can propose and approve withdrawals.
+    pub owners: Vec<Pubkey>,
+
+    /// Approvals a withdrawal needs before it can execute.
+    pub threshold: u8,
+
+    /// Index the next proposal is created at.
+    pub proposal_count: u64,
+
+    /// Bump seed for the vault PDA.
+    pub vault_bump: u8,
+
+    /// Bump seed for the multisig PDA.
+    pub bump: u8,
+}
+
+impl Space for Multisig {
+    // 8 bytes for Anchor discriminator, 32 bytes for `creator`, 8 bytes for `seed`,
+    // 4 + 32 * MAX_OWNERS bytes for `owners`, 1 byte for `threshold`, 8 bytes for `proposal_count`
+    // and 2 bytes for bumps
+    const INIT_SPACE: usize = 8 + 32 + 8 + 4 + 32 * MAX_OWNERS + 1 + 8 + 1 + 1;
+}
+
+impl Multisig {
+    pub fn check_owner(&self, key: &Pubkey) -> Result<()> {
+        require!(self.owners.contains(key), VaultError::NotOwner);
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Proposal struct is a pending withdrawal from a multisig vault.
+pub struct Proposal {
+    /// The multisig whose vault pays out.
+    pub multisig: Pubkey,
+
+    /// Owner that opened the proposal and paid for this account.
+    pub proposer: Pubkey,
+
+    /// Position among the multisig's proposals, part of the PDA seeds.
+    pub index: u64,
+
+    /// Wallet the lamports go to.
+    pub destination: Pubkey,
+
+    /// Lamports to withdraw.
+    pub amount: u64,
+
+    /// Owners that approved the withdrawal, the proposer included.
+    pub approvals: Vec<Pubkey>,
+
+    /// Bump seed for the proposal PDA.
+    pub bump: u8,
+}
+
+impl Space for Proposal {
+    // 8 bytes for Anchor discriminator, 3 * 32 bytes for the keys, 8 bytes each for `index` and
+    // `amount`, 4 + 32 * MAX_OWNERS bytes for `approvals` and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 3 * 32 + 8 + 8 + 4 + 32 * MAX_OWNERS + 1;
+}
@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+
+use crate::{VaultError, VaultIndex, VaultState};
+
+#[derive(Accounts)]
+// The `AcceptOwner` struct completes a handover: the proposed owner signs, and the vault moves from
+// the previous owner's index to theirs. Its addresses stay the same, nothing has to be re-deposited.
+pub struct AcceptOwner<'info> {
+    #[account(mut)] // Pays for `index` if this is the first vault they own.
+    pub new_owner: Signer<'info>,
+
+    #[account(
+        mut, // `owner` changes and the pending offer is cleared.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.pending_owner == new_owner.key() @ VaultError::NotPendingOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // The vault leaves the previous owner's list.
+        seeds = [b"vaults", vault_state.owner.as_ref()],
+        bump = previous_index.bump,
+    )]
+    pub previous_index: Account<'info, VaultIndex>,
+
+    #[account(
+        init_if_needed,
+        payer = new_owner,
+        seeds = [b"vaults", new_owner.key().as_ref()],
+        bump,
+        space = VaultIndex::INIT_SPACE,
+    )]
+    pub index: Account<'info, VaultIndex>,
+
+    // Hands out the vault's next generation, like it did when the vault was opened. Left out when
+    // the creator is the previous or the new owner, whose index is already here.
+    #[account(
+        mut,
+        seeds = [b"vaults", vault_state.creator.as_ref()],
+        bump = creator_index.bump,
+    )]
+    pub creator_index: Option<Account<'info, VaultIndex>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptOwner<'info> {
+    pub fn accept_owner(&mut self, bumps: &AcceptOwnerBumps) -> Result<()> {
+        let vault_state = self.vault_state.key();
+        let new_owner = self.new_owner.key();
+
+        // The vault keeps its name in the new owner's list
+        let name = self
+            .previous_index
+            .remove(&vault_state)
+            .map(|entry| entry.name)
+            .unwrap_or_else(|| self.vault_state.id.to_string());
+
+        self.index.set_up(new_owner, bumps.index);
+        self.index.add(vault_state, name)?;
+
+        // Streams, sessions, the allowlist and any recovery were set up by the previous owner, so
+        // they stop working and the new owner creates their own
+        self.vault_state.generation = self.next_generation(new_owner)?;
+
+        self.vault_state.owner = new_owner;
+        self.vault_state.pending_owner = Pubkey::default();
+
+        // The inactivity window starts over with the new owner
+        self.vault_state.record_heartbeat()?;
+
+        Ok(())
+    }
+
+    /// Draws the next generation from the creator's index, the only counter a vault reopened at
+    /// this address will ever draw from too.
+    fn next_generation(&mut self, new_owner: Pubkey) -> Result<u64> {
+        let creator = self.vault_state.creator;
+        let in_context = self.vault_state.owner == creator || new_owner == creator;
+        require!(
+            self.creator_index.is_some() != in_context,
+            VaultError::InvalidCreatorIndex
+        );
+
+        let generation = match &mut self.creator_index {
+            Some(creator_index) => creator_index.next_generation(),
+            None if self.vault_state.owner == creator => self.previous_index.next_generation(),
+            None => self.index.next_generation(),
+        };
+
+        Ok(generation)
+    }
+}
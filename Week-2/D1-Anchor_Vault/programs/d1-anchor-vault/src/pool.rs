@@ -0,0 +1,323 @@
+use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{burn, mint_to, Burn, Mint, MintTo, TokenAccount, TokenInterface},
+};
+
+use crate::{Config, VaultError, SHARE_DECIMALS, VIRTUAL_ASSETS, VIRTUAL_SHARES};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+// The `InitializePool` struct creates a vault shared by many depositors. Each depositor's claim on
+// it is tracked by share tokens minted from a mint the pool controls.
+pub struct InitializePool<'info> {
+    #[account(mut)] // Pays for the pool, its share mint and the vault's rent-exempt reserve.
+    pub creator: Signer<'info>,
+
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"pool", creator.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = Pool::INIT_SPACE,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut, // Receives the rent-exempt reserve.
+        seeds = [b"vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"shares", pool.key().as_ref()],
+        bump,
+        mint::decimals = SHARE_DECIMALS,
+        mint::authority = pool, // Only the program, signing as the pool, can mint shares.
+        mint::token_program = token_program,
+    )]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused, // No new funds come in while paused.
+    )]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializePool<'info> {
+    pub fn initialize_pool(&mut self, seed: u64, bumps: &InitializePoolBumps) -> Result<()> {
+        self.pool.set_inner(Pool {
+            creator: self.creator.key(),
+            seed,
+            share_mint: self.share_mint.key(),
+            total_assets: 0,
+            vault_bump: bumps.vault,
+            share_mint_bump: bumps.share_mint,
+            bump: bumps.pool,
+        });
+
+        // The reserve keeps the vault rent exempt forever. It is not part of `total_assets`, so
+        // no depositor can ever redeem it, and the last one out never trips over the rent minimum.
+        let reserve = Rent::get()?.minimum_balance(self.vault.data_len());
+
+        let cpi_accounts = Transfer {
+            from: self.creator.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+        transfer(cpi_ctx, reserve)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `PoolDeposit` struct adds lamports to a pool in exchange for newly minted shares.
+pub struct PoolDeposit<'info> {
+    #[account(mut)] // Pays the lamports, and for `depositor_shares` on first deposit.
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut, // `total_assets` grows by the deposit.
+        seeds = [b"pool", pool.creator.as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump,
+        has_one = share_mint,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", pool.key().as_ref()],
+        bump = pool.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, mint::token_program = token_program)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        associated_token::mint = share_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = token_program,
+    )]
+    pub depositor_shares: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused, // No new funds come in while paused.
+    )]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PoolDeposit<'info> {
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+
+        // Computed against the pool before the deposit lands, like every share price
+        let shares = self.pool.shares_for(amount, self.share_mint.supply)?;
+        require!(shares > 0, VaultError::SharesRoundToZero);
+
+        let cpi_accounts = Transfer {
+            from: self.depositor.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+        transfer(cpi_ctx, amount)?;
+
+        let cpi_accounts = MintTo {
+            mint: self.share_mint.to_account_info(),
+            to: self.depositor_shares.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+
+        let creator = self.pool.creator;
+        let seed = self.pool.seed.to_le_bytes();
+        let seeds = &[b"pool", creator.as_ref(), seed.as_ref(), &[self.pool.bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        mint_to(cpi_ctx, shares)?;
+
+        self.pool.total_assets = self
+            .pool
+            .total_assets
+            .checked_add(amount)
+            .ok_or(VaultError::ShareMathOverflow)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `PoolWithdraw` struct burns a depositor's shares and pays out the lamports they are worth.
+pub struct PoolWithdraw<'info> {
+    #[account(mut)] // Receives the redeemed lamports.
+    pub depositor: Signer<'info>,
+
+    #[account(
+        mut, // `total_assets` shrinks by the payout.
+        seeds = [b"pool", pool.creator.as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump = pool.bump,
+        has_one = share_mint,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", pool.key().as_ref()],
+        bump = pool.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, mint::token_program = token_program)]
+    pub share_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = share_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = token_program,
+    )]
+    pub depositor_shares: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PoolWithdraw<'info> {
+    pub fn withdraw(&mut self, shares: u64) -> Result<()> {
+        require!(shares > 0, VaultError::ZeroAmount);
+
+        let amount = self.pool.assets_for(shares, self.share_mint.supply)?;
+        require!(amount > 0, VaultError::SharesRoundToZero);
+
+        // The depositor signs for their own shares; burning more than they hold fails here
+        let cpi_accounts = Burn {
+            mint: self.share_mint.to_account_info(),
+            from: self.depositor_shares.to_account_info(),
+            authority: self.depositor.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        burn(cpi_ctx, shares)?;
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.depositor.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.pool.to_account_info().key.as_ref(),
+            &[self.pool.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        self.pool.total_assets = self
+            .pool
+            .total_assets
+            .checked_sub(amount)
+            .ok_or(VaultError::ShareMathOverflow)?;
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Pool struct is the state of a vault shared by many depositors.
+pub struct Pool {
+    /// Wallet that created the pool, part of its PDA seeds.
+    pub creator: Pubkey,
+
+    /// Lets one creator set up several pools.
+    pub seed: u64,
+
+    /// Mint of the pool's share tokens.
+    pub share_mint: Pubkey,
+
+    /// Lamports the shares are redeemable for. Only deposits and withdrawals move it, so lamports
+    /// sent straight to the vault do not change the share price.
+    pub total_assets: u64,
+
+    /// Bump seed for the vault PDA.
+    pub vault_bump: u8,
+
+    /// Bump seed for the share mint PDA.
+    pub share_mint_bump: u8,
+
+    /// Bump seed for the pool PDA.
+    pub bump: u8,
+}
+
+impl Space for Pool {
+    // 8 bytes for Anchor discriminator, 32 bytes for `creator`, 8 bytes for `seed`, 32 bytes for
+    // `share_mint`, 8 bytes for `total_assets` and 3 bytes for bumps
+    const INIT_SPACE: usize = 8 + 32 + 8 + 32 + 8 + 1 + 1 + 1;
+}
+
+impl Pool {
+    /// Shares minted for depositing `amount` lamports while `supply` shares exist, rounded down.
+    /// - `VIRTUAL_SHARES` and `VIRTUAL_ASSETS` act as a position nobody owns. The first depositor
+    ///   cannot set an extreme share price with a tiny deposit, because that position dilutes
+    ///   every price manipulation.
+    pub fn shares_for(&self, amount: u64, supply: u64) -> Result<u64> {
+        mul_div(
+            amount,
+            supply as u128 + VIRTUAL_SHARES as u128,
+            self.total_assets as u128 + VIRTUAL_ASSETS as u128,
+        )
+    }
+
+    /// Lamports paid out for redeeming `shares` while `supply` shares exist, rounded down.
+    pub fn assets_for(&self, shares: u64, supply: u64) -> Result<u64> {
+        mul_div(
+            shares,
+            self.total_assets as u128 + VIRTUAL_ASSETS as u128,
+            supply as u128 + VIRTUAL_SHARES as u128,
+        )
+    }
+}
+
+/// `value * numerator / denominator` in u128, rounded down so every remainder stays in the pool.
+fn mul_div(value: u64, numerator: u128, denominator: u128) -> Result<u64> {
+    let result = (value as u128)
+        .checked_mul(numerator)
+        .ok_or(VaultError::ShareMathOverflow)?
+        / denominator;
+
+    u64::try_from(result).map_err(|_| error!(VaultError::ShareMathOverflow))
+}
@@ -0,0 +1,311 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::stake,
+    system_program::{Transfer, transfer},
+};
+
+use crate::{hand_over_stake, sweep_token_vaults, VaultError, VaultIndex, VaultState, MAX_GUARDIANS, RECOVERY_DELAY};
+
+#[derive(Accounts)]
+// The `ProposeRecovery` struct opens a recovery request for a vault whose owner lost their key.
+pub struct ProposeRecovery<'info> {
+    #[account(mut)] // Pays for the `recovery` account and gets the rent back once it is closed.
+    pub guardian: Signer<'info>,
+
+    // No seeds here: the owner's key is exactly what may be lost, so the vault state is identified
+    // by its address and trusted because it is owned by this program.
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed,                                        // One pending recovery per vault at a time
+        payer = guardian,
+        seeds = [b"recovery", vault_state.key().as_ref()],     // Bound to the vault it recovers
+        bump,
+        space = Recovery::INIT_SPACE,
+    )]
+    pub recovery: Account<'info, Recovery>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ProposeRecovery<'info> {
+    pub fn propose_recovery(&mut self, new_owner: Pubkey, bumps: &ProposeRecoveryBumps) -> Result<()> {
+        self.vault_state.check_guardian(&self.guardian.key())?;
+
+        // A request left behind by an earlier vault at this address, or approved by a guardian set
+        // the vault no longer has, is replaced, not resumed. The owner may have lost their key, so
+        // guardians must not have to wait for a `cancel_recovery` that never comes
+        if self.recovery.guardian_nonce == self.vault_state.guardian_nonce {
+            self.vault_state
+                .check_replaceable(&self.recovery.vault_state, self.recovery.generation)?;
+        }
+
+        // The proposal counts as the proposer's approval, and the waiting period starts now
+        self.recovery.set_inner(Recovery {
+            vault_state: self.vault_state.key(),
+            proposer: self.guardian.key(),
+            new_owner,
+            approvals: vec![self.guardian.key()],
+            proposed_at: Clock::get()?.unix_timestamp,
+            guardian_nonce: self.vault_state.guardian_nonce,
+            generation: self.vault_state.generation,
+            bump: bumps.recovery,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ApproveRecovery` struct adds one guardian's approval to a pending recovery.
+pub struct ApproveRecovery<'info> {
+    pub guardian: Signer<'info>,
+
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,                                                   // `approvals` grows by one
+        seeds = [b"recovery", vault_state.key().as_ref()],
+        bump = recovery.bump,
+        has_one = vault_state,
+    )]
+    pub recovery: Account<'info, Recovery>,
+}
+
+impl<'info> ApproveRecovery<'info> {
+    pub fn approve_recovery(&mut self) -> Result<()> {
+        let guardian = self.guardian.key();
+        self.vault_state.check_guardian(&guardian)?;
+        self.recovery.check_current(&self.vault_state)?;
+
+        require!(
+            !self.recovery.approvals.contains(&guardian),
+            VaultError::AlreadyApproved
+        );
+
+        self.recovery.approvals.push(guardian);
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `ExecuteRecovery` struct hands the vault to the new owner. Anyone may send it once the
+// recovery is approved and its waiting period is over.
+pub struct ExecuteRecovery<'info> {
+    #[account(mut)] // Receives the vault balance and the vault state's rent.
+    pub new_owner: SystemAccount<'info>,
+
+    #[account(mut)] // Gets the rent of the `recovery` account back.
+    pub proposer: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        close = new_owner, // The old owner's vault state is retired along with the vault.
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // The retired vault leaves the old owner's index.
+        seeds = [b"vaults", vault_state.owner.as_ref()],
+        bump = index.bump,
+    )]
+    pub index: Account<'info, VaultIndex>,
+
+    #[account(
+        mut,
+        seeds = [b"recovery", vault_state.key().as_ref()],
+        bump = recovery.bump,
+        has_one = vault_state,
+        has_one = new_owner,
+        has_one = proposer,
+        close = proposer,
+    )]
+    pub recovery: Account<'info, Recovery>,
+
+    /// CHECK: the vault's stake account, handed to the new owner if the vault has one
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteRecovery<'info> {
+    pub fn execute_recovery(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        // Every approval was given by a guardian of the current set, as long as it never changed
+        self.recovery.check_current(&self.vault_state)?;
+
+        // A vault without guardians cannot be recovered, however many approvals a request has
+        let threshold = self.vault_state.guardian_threshold as usize;
+        require!(
+            threshold > 0 && self.recovery.approvals.len() >= threshold,
+            VaultError::NotEnoughApprovals
+        );
+
+        // The owner gets the whole waiting period to notice and cancel
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= self.recovery.proposed_at.saturating_add(RECOVERY_DELAY),
+            VaultError::RecoveryPending
+        );
+
+        // Recovery replaces the owner's key, not the vault's time lock
+        self.vault_state.check_unlocked()?;
+
+        // The vault state is closed below and with it the program's hold on the stake account, so
+        // the new owner takes the stake over as its staker and withdrawer
+        hand_over_stake(
+            &mut self.vault_state,
+            &self.vault.to_account_info(),
+            &self.stake_account.to_account_info(),
+            &self.clock.to_account_info(),
+            &self.stake_program.to_account_info(),
+            &self.new_owner.key(),
+        )?;
+
+        // Token vaults are owned by `vault_state`, so they have to go before it does
+        sweep_token_vaults(&mut self.vault_state, &self.new_owner.to_account_info(), token_accounts)?;
+
+        let vault_state = self.vault_state.key();
+        self.index.remove(&vault_state);
+
+        let balance = self.vault.lamports();
+        if balance == 0 {
+            emit!(self.vault_state.closed_event(vault_state, self.new_owner.key(), 0, now));
+            return Ok(());
+        }
+
+        // Recovery is not held to the withdrawal cap, but the sweep still counts towards the total
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(balance);
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.new_owner.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        // Moves the vault's entire balance to the new owner, emptying the vault PDA
+        transfer(cpi_ctx, balance)?;
+
+        emit!(self.vault_state.closed_event(vault_state, self.new_owner.key(), balance, now));
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `CancelRecovery` struct lets the owner, who still has their key after all, stop a recovery.
+pub struct CancelRecovery<'info> {
+    pub user: Signer<'info>,
+
+    #[account(mut)] // Gets the rent of the `recovery` account back.
+    pub proposer: SystemAccount<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner, // Only the owner still holds their key.
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [b"recovery", vault_state.key().as_ref()],
+        bump = recovery.bump,
+        has_one = vault_state,
+        has_one = proposer,
+        close = proposer,
+    )]
+    pub recovery: Account<'info, Recovery>,
+}
+
+impl<'info> CancelRecovery<'info> {
+    pub fn cancel_recovery(&mut self) -> Result<()> {
+        // The `close` constraint throws out the recovery, this only records that the owner is around
+        self.vault_state.record_heartbeat()
+    }
+}
+
+#[account]
+
+// The Recovery struct is a pending request from the guardians to move a vault to `new_owner`.
+pub struct Recovery {
+    /// The vault state being recovered.
+    pub vault_state: Pubkey,
+
+    /// Guardian that opened the request and paid for this account.
+    pub proposer: Pubkey,
+
+    /// Wallet that receives the vault balance when the recovery executes.
+    pub new_owner: Pubkey,
+
+    /// Guardians that approved the request, the proposer included.
+    pub approvals: Vec<Pubkey>,
+
+    /// When the request was opened; the waiting period counts from here.
+    pub proposed_at: i64,
+
+    /// The vault's `guardian_nonce` when the request was opened.
+    pub guardian_nonce: u64,
+
+    /// The vault's `generation` when the request was opened.
+    pub generation: u64,
+
+    /// Bump seed for the recovery PDA.
+    pub bump: u8,
+}
+
+impl Space for Recovery {
+    // 8 bytes for Anchor discriminator, 3 * 32 bytes for the keys, 4 + 32 * MAX_GUARDIANS bytes
+    // for `approvals`, 8 bytes for `proposed_at`, 2 * 8 bytes for `guardian_nonce` and `generation`
+    // and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 3 * 32 + 4 + 32 * MAX_GUARDIANS + 8 + 2 * 8 + 1;
+}
+
+impl Recovery {
+    /// Fails with `StaleRecovery` once the vault's guardians changed after the request was opened,
+    /// or the vault it was opened for was closed and another opened at its address. The owner can
+    /// still throw such a request out with `cancel_recovery`, or guardians replace it with
+    /// `propose_recovery`.
+    pub fn check_current(&self, vault_state: &VaultState) -> Result<()> {
+        require!(
+            self.guardian_nonce == vault_state.guardian_nonce
+                && self.generation == vault_state.generation,
+            VaultError::StaleRecovery
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
+
+use crate::{check_rent_exempt, check_withdrawable, VaultError, VaultState, VaultWithdrawn};
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+// The `CreateSession` struct delegates limited withdrawals to a key the owner's app holds, so the
+// main wallet does not have to sign every time.
+pub struct CreateSession<'info> {
+    #[account(mut)] // Pays for the `session` account.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed, // Takes over a session an earlier vault at this address left behind.
+        payer = user,
+        seeds = [b"session", vault_state.key().as_ref(), session_key.as_ref()],
+        bump,
+        space = Session::INIT_SPACE,
+    )]
+    pub session: Account<'info, Session>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateSession<'info> {
+    pub fn create_session(
+        &mut self,
+        session_key: Pubkey,
+        expires_at: i64,
+        spend_limit: u64,
+        bumps: &CreateSessionBumps,
+    ) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+        self.vault_state
+            .check_replaceable(&self.session.vault_state, self.session.generation)?;
+
+        require!(
+            expires_at > Clock::get()?.unix_timestamp && spend_limit > 0,
+            VaultError::InvalidSession
+        );
+
+        self.session.set_inner(Session {
+            vault_state: self.vault_state.key(),
+            session_key,
+            expires_at,
+            spend_limit,
+            spent: 0,
+            generation: self.vault_state.generation,
+            bump: bumps.session,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `RevokeSession` struct ends a session early by closing it, refunding the rent to the owner.
+pub struct RevokeSession<'info> {
+    #[account(mut)] // Receives the session's rent.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"session", vault_state.key().as_ref(), session.session_key.as_ref()],
+        bump = session.bump,
+        has_one = vault_state,
+    )]
+    pub session: Account<'info, Session>,
+}
+
+impl<'info> RevokeSession<'info> {
+    pub fn revoke_session(&mut self) -> Result<()> {
+        // The `close` constraint removes the session, this only records that the owner is around
+        self.vault_state.record_heartbeat()
+    }
+}
+
+#[derive(Accounts)]
+// The `SessionWithdraw` struct withdraws on the session key's signature. The lamports can only go
+// to the vault's owner, so a leaked session key cannot send them anywhere else.
+pub struct SessionWithdraw<'info> {
+    pub session_key: Signer<'info>,
+
+    #[account(mut)] // Receives the withdrawn lamports.
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut, // Withdrawals are counted against the withdrawal cap stored here.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        has_one = owner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // `spent` grows by the withdrawal.
+        seeds = [b"session", vault_state.key().as_ref(), session_key.key().as_ref()],
+        bump = session.bump,
+        has_one = vault_state,
+        constraint = session.generation == vault_state.generation @ VaultError::StaleAccount,
+    )]
+    pub session: Account<'info, Session>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SessionWithdraw<'info> {
+    pub fn session_withdraw(&mut self, amount: u64) -> Result<()> {
+        check_withdrawable(&self.vault, amount)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(now < self.session.expires_at, VaultError::SessionExpired);
+
+        let spent = self.session.spent.saturating_add(amount);
+        require!(
+            spent <= self.session.spend_limit,
+            VaultError::SessionLimitExceeded
+        );
+        self.session.spent = spent;
+
+        // On top of the session's own limit, every vault rule applies as for the owner. The
+        // heartbeat is left alone, a session key is not proof that the owner is still around.
+        self.vault_state.check_unlocked()?;
+        self.vault_state.record_withdrawal(amount, now)?;
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.owner.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        emit!(VaultWithdrawn {
+            vault_state: self.vault_state.key(),
+            destination: self.owner.key(),
+            amount,
+            total_withdrawn: self.vault_state.total_withdrawn,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+}
+
+#[account]
+
+// The Session struct is a key the owner delegated limited withdrawals to, until it expires.
+pub struct Session {
+    /// The vault state the session may withdraw from.
+    pub vault_state: Pubkey,
+
+    /// Key that signs for the session, part of the PDA seeds.
+    pub session_key: Pubkey,
+
+    /// Unix timestamp from which the session can no longer withdraw.
+    pub expires_at: i64,
+
+    /// Most lamports the session may withdraw over its lifetime.
+    pub spend_limit: u64,
+
+    /// Lamports withdrawn through the session so far.
+    pub spent: u64,
+
+    /// The vault's `generation` when the session was created.
+    pub generation: u64,
+
+    /// Bump seed for the session PDA.
+    pub bump: u8,
+}
+
+impl Space for Session {
+    // 8 bytes for Anchor discriminator, 2 * 32 bytes for `vault_state` and `session_key`, 3 * 8
+    // bytes for the expiry and the limit, 8 bytes for `generation` and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 2 * 32 + 3 * 8 + 8 + 1;
+}
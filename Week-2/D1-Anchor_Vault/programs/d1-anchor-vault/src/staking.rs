@@ -0,0 +1,396 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        program::invoke_signed,
+        stake::{
+            self,
+            instruction as stake_instruction,
+            state::{Authorized, Lockup, StakeAuthorize, StakeStateV2},
+            tools::get_minimum_delegation,
+        },
+        sysvar,
+    },
+    system_program::{allocate, assign, transfer, Allocate, Assign, Transfer},
+};
+
+use crate::{check_rent_exempt, VaultError, VaultState};
+
+#[derive(Accounts)]
+// The `CreateStake` struct moves lamports out of the vault into a fresh stake account. The vault
+// PDA is both its staker and withdrawer, so only this program can ever move that stake.
+pub struct CreateStake<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // `staked` grows by the lamports moved out of the vault.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // Funds the stake account.
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: created here as a stake account, at the one address the vault can stake from
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateStake<'info> {
+    pub fn create_stake(&mut self, amount: u64, bumps: &CreateStakeBumps) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+        self.vault_state.record_heartbeat()?;
+
+        // The stake account needs its own rent plus the smallest stake the Stake program delegates;
+        // anything less would only fail later, deep inside the Stake program
+        let minimum = self
+            .rent
+            .minimum_balance(StakeStateV2::size_of())
+            .saturating_add(get_minimum_delegation()?);
+        require!(amount >= minimum, VaultError::StakeBelowMinimum);
+
+        // A vault has a single stake account, which has to be withdrawn before staking again
+        self.vault_state.check_unstaked()?;
+
+        // Staked lamports are still the owner's, but the vault itself has to stay rent exempt
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
+        let vault_state = self.vault_state.key();
+        let vault_seeds: &[&[u8]] = &[b"vault", vault_state.as_ref(), &[self.vault_state.vault_bump]];
+        let stake_seeds: &[&[u8]] = &[b"stake", vault_state.as_ref(), &[bumps.stake_account]];
+        let vault_signer = &[vault_seeds];
+        let stake_signer = &[stake_seeds];
+
+        // Anyone can send lamports to the stake address ahead of time, which would make
+        // `create_account` fail. Like Anchor's `init`, the account is funded, allocated and assigned
+        // in separate steps instead, and any lamports already there are staked along with `amount`.
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.stake_account.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            vault_signer,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        let cpi_accounts = Allocate {
+            account_to_allocate: self.stake_account.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            stake_signer,
+        );
+
+        allocate(cpi_ctx, StakeStateV2::size_of() as u64)?;
+
+        let cpi_accounts = Assign {
+            account_to_assign: self.stake_account.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            stake_signer,
+        );
+
+        assign(cpi_ctx, &stake::program::ID)?;
+
+        let authorized = Authorized::auto(&self.vault.key());
+        let instruction = stake_instruction::initialize(&self.stake_account.key(), &authorized, &Lockup::default());
+
+        invoke_signed(
+            &instruction,
+            &[
+                self.stake_account.to_account_info(),
+                self.rent.to_account_info(),
+                self.stake_program.to_account_info(),
+            ],
+            &[],
+        )?;
+
+        self.vault_state.staked = self
+            .vault_state
+            .staked
+            .checked_add(amount)
+            .ok_or(VaultError::StakeMathOverflow)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `DelegateStake` struct points the vault's stake account at a validator. It also re-delegates
+// a stake account that was deactivated earlier.
+pub struct DelegateStake<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>, // Signs as the stake authority.
+
+    /// CHECK: the vault's stake account, validated by the Stake program
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: the validator's vote account, validated by the Stake program
+    pub vote_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: the stake history sysvar
+    #[account(address = sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: the stake config account, checked against its well-known address below
+    pub stake_config: UncheckedAccount<'info>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+}
+
+impl<'info> DelegateStake<'info> {
+    pub fn delegate_stake(&mut self) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // Deprecated in favour of built-in defaults, but `DelegateStake` still takes the account
+        #[allow(deprecated)]
+        let config = stake::config::ID;
+        require_keys_eq!(self.stake_config.key(), config, ErrorCode::ConstraintAddress);
+
+        let instruction = stake_instruction::delegate_stake(
+            &self.stake_account.key(),
+            &self.vault.key(),
+            &self.vote_account.key(),
+        );
+
+        let vault_state = self.vault_state.key();
+        let seeds = &[b"vault", vault_state.as_ref(), &[self.vault_state.vault_bump]];
+
+        invoke_signed(
+            &instruction,
+            &[
+                self.stake_account.to_account_info(),
+                self.vote_account.to_account_info(),
+                self.clock.to_account_info(),
+                self.stake_history.to_account_info(),
+                self.stake_config.to_account_info(),
+                self.vault.to_account_info(),
+                self.stake_program.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `DeactivateStake` struct starts cooling down the vault's stake so it can be withdrawn.
+pub struct DeactivateStake<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>, // Signs as the stake authority.
+
+    /// CHECK: the vault's stake account, validated by the Stake program
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+}
+
+impl<'info> DeactivateStake<'info> {
+    pub fn deactivate_stake(&mut self) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        let instruction = stake_instruction::deactivate_stake(&self.stake_account.key(), &self.vault.key());
+
+        let vault_state = self.vault_state.key();
+        let seeds = &[b"vault", vault_state.as_ref(), &[self.vault_state.vault_bump]];
+
+        invoke_signed(
+            &instruction,
+            &[
+                self.stake_account.to_account_info(),
+                self.clock.to_account_info(),
+                self.vault.to_account_info(),
+                self.stake_program.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `WithdrawStake` struct empties the deactivated stake account back into the vault, rewards
+// included, which closes the stake account.
+pub struct WithdrawStake<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // `staked` drops back to zero.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // Receives the stake account's lamports.
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: the vault's stake account, validated by the Stake program
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref()],
+        bump,
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    /// CHECK: the stake history sysvar
+    #[account(address = sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: the native Stake program
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+}
+
+impl<'info> WithdrawStake<'info> {
+    pub fn withdraw_stake(&mut self) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        // The Stake program refuses this while any of the stake is still active or cooling down
+        let instruction = stake_instruction::withdraw(
+            &self.stake_account.key(),
+            &self.vault.key(),
+            &self.vault.key(),
+            self.stake_account.lamports(),
+            None,
+        );
+
+        let vault_state = self.vault_state.key();
+        let seeds = &[b"vault", vault_state.as_ref(), &[self.vault_state.vault_bump]];
+
+        invoke_signed(
+            &instruction,
+            &[
+                self.stake_account.to_account_info(),
+                self.vault.to_account_info(),
+                self.clock.to_account_info(),
+                self.stake_history.to_account_info(),
+                self.vault.to_account_info(),
+                self.stake_program.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+
+        // Everything that was staked, and whatever it earned, is liquid again
+        self.vault_state.staked = 0;
+
+        Ok(())
+    }
+}
+
+/// Makes `new_authority` the staker and withdrawer of the vault's stake account, for when the
+/// vault's lamports go to someone without the owner's key. They unwind the stake through the Stake
+/// program directly, so it no longer counts as `staked`.
+pub(crate) fn hand_over_stake<'info>(
+    vault_state: &mut Account<'info, VaultState>,
+    vault: &AccountInfo<'info>,
+    stake_account: &AccountInfo<'info>,
+    clock: &AccountInfo<'info>,
+    stake_program: &AccountInfo<'info>,
+    new_authority: &Pubkey,
+) -> Result<()> {
+    if vault_state.staked == 0 {
+        return Ok(());
+    }
+
+    let key = vault_state.key();
+    let seeds = &[b"vault", key.as_ref(), &[vault_state.vault_bump]];
+
+    for role in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        let instruction =
+            stake_instruction::authorize(&stake_account.key(), &vault.key(), new_authority, role, None);
+
+        invoke_signed(
+            &instruction,
+            &[
+                stake_account.clone(),
+                clock.clone(),
+                vault.clone(),
+                stake_program.clone(),
+            ],
+            &[&seeds[..]],
+        )?;
+    }
+
+    vault_state.staked = 0;
+
+    Ok(())
+}
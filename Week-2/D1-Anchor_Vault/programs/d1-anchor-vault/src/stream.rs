@@ -0,0 +1,236 @@
+use anchor_lang::{prelude::*, system_program::{Transfer, transfer}};
+
+use crate::{check_rent_exempt, VaultError, VaultState, VaultWithdrawn};
+
+#[derive(Accounts)]
+// The `CreateStream` struct sets up a recurring payout from the vault to one recipient.
+pub struct CreateStream<'info> {
+    #[account(mut)] // Pays for the `stream` account.
+    pub user: Signer<'info>,
+
+    pub recipient: SystemAccount<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed, // Takes over a stream an earlier vault at this address left behind.
+        payer = user,
+        seeds = [b"stream", vault_state.key().as_ref(), recipient.key().as_ref()],
+        bump,
+        space = Stream::INIT_SPACE,
+    )]
+    pub stream: Account<'info, Stream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateStream<'info> {
+    pub fn create_stream(
+        &mut self,
+        amount_per_period: u64,
+        period: i64,
+        start: i64,
+        bumps: &CreateStreamBumps,
+    ) -> Result<()> {
+        require!(amount_per_period > 0 && period > 0, VaultError::InvalidStream);
+        self.vault_state
+            .check_replaceable(&self.stream.vault_state, self.stream.generation)?;
+
+        self.vault_state.record_heartbeat()?;
+
+        self.stream.set_inner(Stream {
+            vault_state: self.vault_state.key(),
+            recipient: self.recipient.key(),
+            amount_per_period,
+            period,
+            start,
+            periods_paid: 0,
+            generation: self.vault_state.generation,
+            bump: bumps.stream,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `CrankStream` struct pays a stream's recipient for every period that has elapsed. Nobody has
+// to sign for the vault, so the recipient or any bot can call it.
+pub struct CrankStream<'info> {
+    #[account(mut)] // Receives the payout.
+    pub recipient: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.key().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut, // Payouts are counted against the withdrawal cap stored here.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut, // `periods_paid` catches up with the clock.
+        seeds = [b"stream", vault_state.key().as_ref(), recipient.key().as_ref()],
+        bump = stream.bump,
+        has_one = vault_state,
+        has_one = recipient,
+        constraint = stream.generation == vault_state.generation @ VaultError::StaleAccount,
+    )]
+    pub stream: Account<'info, Stream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CrankStream<'info> {
+    pub fn crank_stream(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        let due = self.stream.periods_due(now);
+        require!(due > 0, VaultError::NothingDue);
+
+        // The owner set the stream up, but a payout still leaves the vault like any withdrawal. If
+        // the lock is in the way, every period stays owed for a later crank.
+        self.vault_state.check_unlocked()?;
+
+        // Whatever the cap and balance allow is paid now, whole periods only, and the rest stays
+        // owed. The vault's rent-exempt reserve is never streamed out, `close` returns it.
+        let per_period = self.stream.amount_per_period;
+        let by_cap = self.vault_state.cap_remaining(now) / per_period;
+        require!(by_cap > 0, VaultError::WithdrawalLimitExceeded);
+
+        let minimum = Rent::get()?.minimum_balance(self.vault.data_len());
+        let by_balance = self.vault.lamports().saturating_sub(minimum) / per_period;
+        require!(by_balance > 0, VaultError::InsufficientFunds);
+
+        let periods = due.min(by_cap).min(by_balance);
+        let amount = per_period
+            .checked_mul(periods)
+            .ok_or(VaultError::StreamMathOverflow)?;
+
+        self.vault_state.record_withdrawal(amount, now)?;
+        check_rent_exempt(&self.vault, self.vault.lamports().saturating_sub(amount))?;
+
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.recipient.to_account_info(),
+        };
+
+        let seeds = &[
+            b"vault",
+            self.vault_state.to_account_info().key.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer(cpi_ctx, amount)?;
+
+        self.stream.periods_paid += periods;
+
+        emit!(VaultWithdrawn {
+            vault_state: self.vault_state.key(),
+            destination: self.recipient.key(),
+            amount,
+            total_withdrawn: self.vault_state.total_withdrawn,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+// The `CancelStream` struct stops a stream by closing its account, refunding the rent to the owner.
+pub struct CancelStream<'info> {
+    #[account(mut)] // Receives the stream's rent.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stream", vault_state.key().as_ref(), stream.recipient.as_ref()],
+        bump = stream.bump,
+        has_one = vault_state,
+    )]
+    pub stream: Account<'info, Stream>,
+}
+
+impl<'info> CancelStream<'info> {
+    pub fn cancel_stream(&mut self) -> Result<()> {
+        // The `close` constraint removes the stream, this only records that the owner is around
+        self.vault_state.record_heartbeat()
+    }
+}
+
+#[account]
+
+// The Stream struct describes a recurring payout from a vault to a single recipient.
+pub struct Stream {
+    /// The vault state this stream pays out of.
+    pub vault_state: Pubkey,
+
+    /// Wallet that receives the payouts, part of the PDA seeds.
+    pub recipient: Pubkey,
+
+    /// Lamports paid for each elapsed period.
+    pub amount_per_period: u64,
+
+    /// Length of a period in seconds.
+    pub period: i64,
+
+    /// Unix timestamp the first period starts at.
+    pub start: i64,
+
+    /// Periods already paid out.
+    pub periods_paid: u64,
+
+    /// The vault's `generation` when the stream was created.
+    pub generation: u64,
+
+    /// Bump seed for the stream PDA.
+    pub bump: u8,
+}
+
+impl Space for Stream {
+    // 8 bytes for Anchor discriminator, 2 * 32 bytes for `vault_state` and `recipient`, 4 * 8 bytes
+    // for the schedule and `periods_paid`, 8 bytes for `generation` and 1 byte for the bump
+    const INIT_SPACE: usize = 8 + 2 * 32 + 4 * 8 + 8 + 1;
+}
+
+impl Stream {
+    /// Periods that have fully elapsed at `now` but were not paid out yet.
+    pub fn periods_due(&self, now: i64) -> u64 {
+        if now < self.start {
+            return 0;
+        }
+
+        let elapsed = (now.saturating_sub(self.start) / self.period) as u64;
+
+        elapsed.saturating_sub(self.periods_paid)
+    }
+}
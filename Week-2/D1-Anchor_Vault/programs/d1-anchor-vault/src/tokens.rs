@@ -0,0 +1,309 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::{Config, VaultError, VaultState, MAX_TOKEN_VAULTS};
+
+#[derive(Accounts)]
+// The `TokenDeposit` struct moves tokens of one mint into the vault. Each mint gets its own
+// token vault: the `vault_state` PDA's associated token account, created on first deposit.
+pub struct TokenDeposit<'info> {
+    #[account(mut)] // Pays for the token vault the first time a mint is deposited.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The mint is recorded in `token_mints` so that `close` knows what to sweep.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = vault_state, // The state PDA signs for its token vaults.
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused, // No new funds come in while paused.
+    )]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Interface<'info, TokenInterface>, // Token or Token-2022, whichever owns `mint`.
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TokenDeposit<'info> {
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+        self.vault_state.record_heartbeat()?;
+
+        let mint = self.mint.key();
+        let token_mints = &mut self.vault_state.token_mints;
+
+        if !token_mints.contains(&mint) {
+            require!(
+                token_mints.len() < MAX_TOKEN_VAULTS,
+                VaultError::TooManyTokenVaults
+            );
+            token_mints.push(mint);
+        }
+
+        let cpi_accounts = TransferChecked {
+            from: self.user_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.token_vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+#[derive(Accounts)]
+// The `TokenWithdraw` struct moves tokens of one mint from its token vault back to the owner.
+pub struct TokenWithdraw<'info> {
+    #[account(mut)] // Pays for `user_ata` if it no longer exists.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The owner's heartbeat is refreshed.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TokenWithdraw<'info> {
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+        self.vault_state.record_heartbeat()?;
+
+        // Tokens are locked along with the SOL
+        self.vault_state.check_unlocked()?;
+
+        let cpi_accounts = TransferChecked {
+            from: self.token_vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.user_ata.to_account_info(),
+            authority: self.vault_state.to_account_info(),
+        };
+
+        let creator = self.vault_state.creator;
+        let id = self.vault_state.id_seed();
+        let seeds = &[b"state", creator.as_ref(), id.as_ref(), &[self.vault_state.state_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+#[derive(Accounts)]
+// The `CloseTokenVault` struct closes an empty token vault and forgets its mint, freeing its slot in
+// `token_mints` for another mint.
+pub struct CloseTokenVault<'info> {
+    #[account(mut)] // Gets the token vault's rent back.
+    pub user: Signer<'info>,
+
+    #[account(
+        mut, // The mint leaves `token_mints`.
+        seeds = [b"state", vault_state.creator.as_ref(), vault_state.id_seed().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.owner == user.key() @ VaultError::NotVaultOwner,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_state,
+        associated_token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CloseTokenVault<'info> {
+    pub fn close_token_vault(&mut self) -> Result<()> {
+        self.vault_state.record_heartbeat()?;
+
+        let mint = self.mint.key();
+        self.vault_state.token_mints.retain(|token_mint| *token_mint != mint);
+
+        // The token program refuses to close a frozen account, so a frozen token vault is only
+        // forgotten. Depositing the mint again picks it up once the mint authority thaws it.
+        if self.token_vault.is_frozen() {
+            return Ok(());
+        }
+
+        require!(self.token_vault.amount == 0, VaultError::TokenVaultNotEmpty);
+
+        let cpi_accounts = CloseAccount {
+            account: self.token_vault.to_account_info(),
+            destination: self.user.to_account_info(),
+            authority: self.vault_state.to_account_info(),
+        };
+
+        let creator = self.vault_state.creator;
+        let id = self.vault_state.id_seed();
+        let seeds = &[b"state", creator.as_ref(), id.as_ref(), &[self.vault_state.state_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        close_account(cpi_ctx)
+    }
+}
+
+/// Empties and closes every token vault of `vault_state` into `recipient`'s token accounts, so
+/// the state account can be closed without stranding tokens behind a PDA nobody can sign for.
+/// - `accounts` holds `[mint, token_vault, recipient_token_account, token_program]` for each mint
+///   in `vault_state.token_mints`, in the same order.
+/// - Frozen token vaults are left as they are, since the token program would refuse to move or
+///   close them and block `close` and recovery for good. Reopening the vault under the same id and
+///   depositing the mint again picks them up once thawed.
+pub fn sweep_token_vaults<'info>(
+    vault_state: &mut Account<'info, VaultState>,
+    recipient: &AccountInfo<'info>,
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    require_eq!(
+        accounts.len(),
+        vault_state.token_mints.len() * 4,
+        VaultError::TokenVaultsMissing
+    );
+
+    let creator = vault_state.creator;
+    let id = vault_state.id_seed();
+    let seeds = &[b"state", creator.as_ref(), id.as_ref(), &[vault_state.state_bump]];
+    let signer_seeds = &[&seeds[..]];
+
+    for (group, expected_mint) in accounts.chunks_exact(4).zip(&vault_state.token_mints) {
+        let [mint_info, token_vault_info, recipient_info, token_program_info] = group else {
+            unreachable!("chunks_exact yields groups of four");
+        };
+
+        require_keys_eq!(mint_info.key(), *expected_mint, VaultError::TokenVaultsMissing);
+
+        let token_program = Interface::<TokenInterface>::try_from(token_program_info)?;
+        let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+        require_keys_eq!(*mint_info.owner, token_program.key(), ErrorCode::ConstraintMintTokenProgram);
+
+        // The token vault is the state PDA's associated token account for this mint
+        let expected_vault = get_associated_token_address_with_program_id(
+            &vault_state.key(),
+            expected_mint,
+            &token_program.key(),
+        );
+        require_keys_eq!(token_vault_info.key(), expected_vault, ErrorCode::ConstraintAssociated);
+        let token_vault = InterfaceAccount::<TokenAccount>::try_from(token_vault_info)?;
+
+        let destination = InterfaceAccount::<TokenAccount>::try_from(recipient_info)?;
+        require_keys_eq!(destination.owner, recipient.key(), ErrorCode::ConstraintTokenOwner);
+        require_keys_eq!(destination.mint, *expected_mint, ErrorCode::ConstraintTokenMint);
+
+        if token_vault.is_frozen() {
+            continue;
+        }
+
+        if token_vault.amount > 0 {
+            let cpi_accounts = TransferChecked {
+                from: token_vault_info.clone(),
+                mint: mint_info.clone(),
+                to: recipient_info.clone(),
+                authority: vault_state.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                token_program_info.clone(),
+                cpi_accounts,
+                signer_seeds,
+            );
+
+            transfer_checked(cpi_ctx, token_vault.amount, mint.decimals)?;
+        }
+
+        // The token vault's rent goes to the recipient along with the tokens
+        let cpi_accounts = CloseAccount {
+            account: token_vault_info.clone(),
+            destination: recipient.clone(),
+            authority: vault_state.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program_info.clone(),
+            cpi_accounts,
+            signer_seeds,
+        );
+
+        close_account(cpi_ctx)?;
+    }
+
+    vault_state.token_mints.clear();
+
+    Ok(())
+}
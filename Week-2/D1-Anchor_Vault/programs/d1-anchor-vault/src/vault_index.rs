@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use crate::{VaultError, MAX_NAME_LEN, MAX_VAULTS};
+
+#[account]
+
+// The VaultIndex struct lists a user's vaults, so clients can find them all from the user's key alone.
+pub struct VaultIndex {
+    /// Wallet whose vaults are listed, part of the PDA seeds.
+    pub user: Pubkey,
+
+    /// One entry per open vault, in the order they were created.
+    pub vaults: Vec<VaultEntry>,
+
+    /// Bump seed for the index PDA.
+    pub bump: u8,
+
+    /// Vaults the user has ever opened, closed ones included. Only ever grows, see
+    /// `VaultState::generation`.
+    pub opened: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultEntry {
+    /// The vault's state account, from which every other vault account derives.
+    pub vault_state: Pubkey,
+
+    /// Label chosen by the user, e.g. "rent" or "savings".
+    pub name: String,
+}
+
+impl Space for VaultIndex {
+    // 8 bytes for Anchor discriminator, 32 bytes for `user`, 4 + (32 + 4 + MAX_NAME_LEN) * MAX_VAULTS
+    // bytes for `vaults`, 1 byte for the bump and 8 bytes for `opened`
+    const INIT_SPACE: usize = 8 + 32 + 4 + (32 + 4 + MAX_NAME_LEN) * MAX_VAULTS + 1 + 8;
+}
+
+impl VaultIndex {
+    /// Fills in a freshly created index; an index that already belongs to `user` is left alone.
+    pub fn set_up(&mut self, user: Pubkey, bump: u8) {
+        if self.user == Pubkey::default() {
+            self.user = user;
+            self.bump = bump;
+        }
+    }
+
+    /// Records a vault the user now owns.
+    pub fn add(&mut self, vault_state: Pubkey, name: String) -> Result<()> {
+        require!(
+            !name.is_empty() && name.len() <= MAX_NAME_LEN,
+            VaultError::InvalidVaultName
+        );
+        require!(self.vaults.len() < MAX_VAULTS, VaultError::TooManyVaults);
+
+        self.vaults.push(VaultEntry { vault_state, name });
+
+        Ok(())
+    }
+
+    /// Counts a newly opened vault, returning its generation. The first vault is generation `1`,
+    /// so `0` is never handed out.
+    pub fn next_generation(&mut self) -> u64 {
+        self.opened = self.opened.saturating_add(1);
+        self.opened
+    }
+
+    /// Forgets a vault the user no longer owns, returning its entry if it was listed.
+    pub fn remove(&mut self, vault_state: &Pubkey) -> Option<VaultEntry> {
+        let position = self
+            .vaults
+            .iter()
+            .position(|entry| entry.vault_state == *vault_state)?;
+
+        Some(self.vaults.remove(position))
+    }
+}
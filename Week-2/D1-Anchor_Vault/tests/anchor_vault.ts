@@ -40,8 +40,19 @@ describe("anchor_vault", () => {
   const user = Keypair.generate();
   console.log("user :", user.publicKey.toBase58());
 
+  const vaultId = new BN(0);
+
   const [vault_state] = anchor.web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("state"), user.publicKey.toBytes()],
+    [
+      Buffer.from("state"),
+      user.publicKey.toBytes(),
+      vaultId.toArrayLike(Buffer, "le", 8),
+    ],
+    program.programId
+  );
+
+  const [index] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("vaults"), user.publicKey.toBytes()],
     program.programId
   );
 
@@ -50,6 +61,17 @@ describe("anchor_vault", () => {
     program.programId
   );
 
+  const [config] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+
+  // The upgrade authority, here the deploying wallet, is the only one allowed to create the config
+  const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBytes()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
   it("Airdrop sol", async () => {
     const airdropAmt = 100;
     const txhash = await provider.connection
@@ -58,14 +80,34 @@ describe("anchor_vault", () => {
     console.log("tx sign :", txhash);
   });
 
+  it("Initialize Config", async () => {
+    try {
+      const tx = await program.methods
+        .initializeConfig()
+        .accounts({
+          admin: provider.wallet.publicKey,
+          programData: programData,
+          config: config,
+          system_program: anchor.web3.SystemProgram.programId,
+        })
+        .rpc()
+        .then(confirmTx);
+      console.log("config txhash :", tx);
+    } catch (e) {
+      console.log("error while init config :", e);
+    }
+  });
+
   it("Initialize Account", async () => {
     try {
       const tx = await program.methods
-        .initialize()
+        .initialize(vaultId, "main", null)
         .accounts({
           user: user.publicKey,
           vault_state: vault_state,
           vault: vault,
+          index: index,
+          config: config,
           system_program: anchor.web3.SystemProgram.programId,
         })
         .signers([user])
@@ -84,9 +126,11 @@ describe("anchor_vault", () => {
       const tx = await program.methods
         .deposit(new anchor.BN(depositAmt * LAMPORTS_PER_SOL))
         .accounts({
-          user: user.publicKey,
+          depositor: user.publicKey,
+          owner: user.publicKey,
           vault: vault,
           vault_state: vault_state,
+          config: config,
           system_program: anchor.web3.SystemProgram.programId,
         })
         .signers([user])
@@ -126,6 +170,7 @@ describe("anchor_vault", () => {
           user: user.publicKey,
           vault: vault,
           vaultState: vault_state,
+          index: index,
           systemProgram: anchor.web3.SystemProgram.programId,
         })
         .signers([user])
@@ -0,0 +1,597 @@
+//! In-process harness for the escrow program.
+//!
+//! Runs `escrow` natively inside a `solana-program-test` bank next to the SPL Token,
+//! Token-2022 and Associated Token programs, so tests can drive `make`, `take` and
+//! `refund` without a validator.
+
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::entrypoint::ProgramResult,
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address_with_program_id,
+        spl_associated_token_account::{
+            self, instruction::create_associated_token_account_idempotent,
+        },
+    },
+    token_2022::spl_token_2022::{
+        extension::StateWithExtensions,
+        instruction::{initialize_mint2, mint_to},
+        state::{Account as TokenAccount, Mint as MintState},
+    },
+};
+use escrow::Escrow;
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+
+pub use anchor_spl::{token::ID as TOKEN_PROGRAM_ID, token_2022::ID as TOKEN_2022_PROGRAM_ID};
+
+/// Lamports every actor starts with, enough to pay for escrow and token account rent.
+pub const ACTOR_LAMPORTS: u64 = 100 * LAMPORTS_PER_SOL;
+
+/// Anchor ties the account slice and the accounts to one lifetime, which the native
+/// processor signature does not, so hand it an owned copy of the slice instead.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    escrow::entry(program_id, accounts, data)
+}
+
+/// A `ProgramTest` with the escrow program loaded as a native processor.
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new("escrow", escrow::ID, processor!(process_instruction));
+    program_test.prefer_bpf(false);
+    program_test
+}
+
+/// A `ProgramTest` with the BPF build of the escrow program, so that compute units are
+/// metered the way they are on chain.
+///
+/// The shared object is looked up in `SBF_OUT_DIR`, defaulting to the workspace's
+/// `target/deploy` where `anchor build` puts it.
+pub fn bpf_program_test() -> ProgramTest {
+    if std::env::var_os("BPF_OUT_DIR").is_none() && std::env::var_os("SBF_OUT_DIR").is_none() {
+        std::env::set_var(
+            "SBF_OUT_DIR",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy"),
+        );
+    }
+
+    let mut program_test = ProgramTest::new("escrow", escrow::ID, None);
+    program_test.prefer_bpf(true);
+    program_test
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mint {
+    pub address: Pubkey,
+    pub token_program: Pubkey,
+    pub decimals: u8,
+}
+
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub actors: Vec<Keypair>,
+    pub mints: Vec<Mint>,
+    slot: u64,
+}
+
+impl Harness {
+    /// Starts a bank with `actors` funded system accounts.
+    pub async fn start(mut program_test: ProgramTest, actors: usize) -> Self {
+        let actors: Vec<Keypair> = (0..actors).map(|_| Keypair::new()).collect();
+
+        for actor in &actors {
+            program_test.add_account(
+                actor.pubkey(),
+                Account {
+                    lamports: ACTOR_LAMPORTS,
+                    owner: system_program::ID,
+                    ..Account::default()
+                },
+            );
+        }
+
+        let mut context = program_test.start_with_context().await;
+        let slot = context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .expect("clock sysvar")
+            .slot;
+
+        Self {
+            context,
+            actors,
+            mints: Vec::new(),
+            slot,
+        }
+    }
+
+    pub fn actor(&self, index: usize) -> Pubkey {
+        self.actors[index].pubkey()
+    }
+
+    /// Slot the last transaction landed in; the next one lands in the slot after.
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Lets `slots` slots pass before the next transaction.
+    pub fn skip_slots(&mut self, slots: u64) {
+        self.slot += slots;
+    }
+
+    /// Sends `instructions` paid for by the context payer and signed by the given actors.
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[usize],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction(instructions, signers);
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Like [`Harness::send`], for signers that are not actors.
+    pub async fn send_with_keypairs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction_with_keypairs(instructions, signers);
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    /// Sends `instructions` like [`Harness::send`] and returns the compute units the whole
+    /// transaction consumed.
+    pub async fn compute_units(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[usize],
+    ) -> Result<u64, BanksClientError> {
+        let transaction = self.transaction(instructions, signers);
+        let processed = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await?;
+        processed.result?;
+
+        Ok(processed
+            .metadata
+            .expect("processed transactions carry metadata")
+            .compute_units_consumed)
+    }
+
+    fn transaction(&mut self, instructions: &[Instruction], signers: &[usize]) -> Transaction {
+        let signers: Vec<Keypair> = signers
+            .iter()
+            .map(|&signer| self.actors[signer].insecure_clone())
+            .collect();
+        self.transaction_with_keypairs(instructions, &signers.iter().collect::<Vec<_>>())
+    }
+
+    /// Every transaction lands in a fresh slot so that retrying an identical instruction
+    /// is executed again instead of being rejected as already processed.
+    fn transaction_with_keypairs(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Transaction {
+        self.slot += 1;
+        self.context
+            .warp_to_slot(self.slot)
+            .expect("warp to next slot");
+
+        let mut keypairs = vec![&self.context.payer];
+        keypairs.extend_from_slice(signers);
+
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &keypairs,
+            self.context.last_blockhash,
+        )
+    }
+
+    /// Creates a mint owned by `token_program` with the context payer as mint authority,
+    /// returning its index in `mints`.
+    pub async fn create_mint(
+        &mut self,
+        token_program: Pubkey,
+        decimals: u8,
+    ) -> Result<usize, BanksClientError> {
+        let mint = Keypair::new();
+        let payer = self.context.payer.pubkey();
+        let rent = self.context.banks_client.get_rent().await?;
+
+        let instructions = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(MintState::LEN),
+                MintState::LEN as u64,
+                &token_program,
+            ),
+            initialize_mint2(&token_program, &mint.pubkey(), &payer, None, decimals)
+                .expect("initialize mint instruction"),
+        ];
+
+        self.send_with_keypairs(&instructions, &[&mint]).await?;
+
+        self.mints.push(Mint {
+            address: mint.pubkey(),
+            token_program,
+            decimals,
+        });
+        Ok(self.mints.len() - 1)
+    }
+
+    /// Creates `owner`'s associated token account for `mint` if it does not exist yet.
+    pub async fn create_ata(&mut self, owner: usize, mint: usize) -> Result<(), BanksClientError> {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+
+        let instruction = create_associated_token_account_idempotent(
+            &self.context.payer.pubkey(),
+            &self.actor(owner),
+            &address,
+            &token_program,
+        );
+        self.send(&[instruction], &[]).await
+    }
+
+    /// Mints `amount` of `mint` into `owner`'s associated token account, creating it first.
+    pub async fn mint_to(
+        &mut self,
+        mint: usize,
+        owner: usize,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        self.mint_to_owner(mint, self.actor(owner), amount).await
+    }
+
+    /// Like [`Harness::mint_to`], for owners that are not actors, such as PDAs.
+    pub async fn mint_to_owner(
+        &mut self,
+        mint: usize,
+        owner: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+        let payer = self.context.payer.pubkey();
+
+        let instructions = [
+            create_associated_token_account_idempotent(&payer, &owner, &address, &token_program),
+            mint_to(
+                &token_program,
+                &address,
+                &self.ata_of(&owner, mint),
+                &payer,
+                &[],
+                amount,
+            )
+            .expect("mint to instruction"),
+        ];
+        self.send(&instructions, &[]).await
+    }
+
+    pub fn ata(&self, owner: usize, mint: usize) -> Pubkey {
+        self.ata_of(&self.actor(owner), mint)
+    }
+
+    pub fn ata_of(&self, owner: &Pubkey, mint: usize) -> Pubkey {
+        let Mint {
+            address,
+            token_program,
+            ..
+        } = self.mints[mint];
+        get_associated_token_address_with_program_id(owner, &address, &token_program)
+    }
+
+    pub fn escrow_address(&self, maker: usize, seed: u64) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                b"escrow",
+                self.actor(maker).as_ref(),
+                seed.to_le_bytes().as_ref(),
+            ],
+            &escrow::ID,
+        )
+        .0
+    }
+
+    pub fn vault_address(&self, maker: usize, seed: u64, mint_a: usize) -> Pubkey {
+        self.ata_of(&self.escrow_address(maker, seed), mint_a)
+    }
+
+    pub fn make_instruction(
+        &self,
+        maker: usize,
+        mint_a: usize,
+        mint_b: usize,
+        seed: u64,
+        deposit: u64,
+        receive: u64,
+    ) -> Instruction {
+        let token_program = self.mints[mint_a].token_program;
+
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::Make {
+                maker: self.actor(maker),
+                mint_a: self.mints[mint_a].address,
+                mint_b: self.mints[mint_b].address,
+                maker_ata_a: self.ata(maker, mint_a),
+                escrow: self.escrow_address(maker, seed),
+                vault: self.vault_address(maker, seed, mint_a),
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::Make {
+                seed,
+                deposit,
+                receive,
+            }
+            .data(),
+        }
+    }
+
+    pub fn take_instruction(
+        &self,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+        mint_b: usize,
+    ) -> Instruction {
+        self.guarded_take_instruction(taker, maker, seed, mint_a, mint_b, None)
+    }
+
+    /// Builds a take that passes `price_feed` along for the escrow's price guard.
+    pub fn guarded_take_instruction(
+        &self,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+        mint_b: usize,
+        price_feed: Option<Pubkey>,
+    ) -> Instruction {
+        let token_program = self.mints[mint_a].token_program;
+
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::Take {
+                taker: self.actor(taker),
+                maker: self.actor(maker),
+                mint_a: self.mints[mint_a].address,
+                mint_b: self.mints[mint_b].address,
+                taker_ata_a: self.ata(taker, mint_a),
+                taker_ata_b: self.ata(taker, mint_b),
+                maker_ata_b: self.ata(maker, mint_b),
+                escrow: self.escrow_address(maker, seed),
+                vault: self.vault_address(maker, seed, mint_a),
+                price_feed,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::Take {}.data(),
+        }
+    }
+
+    /// Builds `take_with_payer` with `payer` funding the token accounts `taker` lacks and
+    /// `price_feed` passed along for the escrow's price guard.
+    #[allow(clippy::too_many_arguments)]
+    pub fn take_with_payer_instruction(
+        &self,
+        payer: usize,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+        mint_b: usize,
+        price_feed: Option<Pubkey>,
+    ) -> Instruction {
+        let token_program = self.mints[mint_a].token_program;
+
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::TakeWithPayer {
+                payer: self.actor(payer),
+                taker: self.actor(taker),
+                maker: self.actor(maker),
+                mint_a: self.mints[mint_a].address,
+                mint_b: self.mints[mint_b].address,
+                taker_ata_a: self.ata(taker, mint_a),
+                taker_ata_b: self.ata(taker, mint_b),
+                maker_ata_b: self.ata(maker, mint_b),
+                escrow: self.escrow_address(maker, seed),
+                vault: self.vault_address(maker, seed, mint_a),
+                price_feed,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::TakeWithPayer {}.data(),
+        }
+    }
+
+    /// Builds a refund signed by `signer` for the escrow that `maker` opened with `seed`.
+    pub fn refund_instruction(
+        &self,
+        signer: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+    ) -> Instruction {
+        let token_program = self.mints[mint_a].token_program;
+
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::Refund {
+                maker: self.actor(signer),
+                mint_a: self.mints[mint_a].address,
+                maker_ata_a: self.ata(signer, mint_a),
+                escrow: self.escrow_address(maker, seed),
+                vault: self.vault_address(maker, seed, mint_a),
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::Refund {}.data(),
+        }
+    }
+
+    /// Builds `set_price_guard` for the escrow that `maker` opened with `seed`; a
+    /// `price_feed` of `None` removes the guard.
+    pub fn set_price_guard_instruction(
+        &self,
+        maker: usize,
+        seed: u64,
+        price_feed: Option<Pubkey>,
+        max_deviation_bps: u16,
+    ) -> Instruction {
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::SetPriceGuard {
+                maker: self.actor(maker),
+                escrow: self.escrow_address(maker, seed),
+                price_feed,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::SetPriceGuard { max_deviation_bps }.data(),
+        }
+    }
+
+    pub fn migrate_escrow_instruction(&self, payer: usize, escrow: Pubkey) -> Instruction {
+        Instruction {
+            program_id: escrow::ID,
+            accounts: escrow::accounts::MigrateEscrow {
+                payer: self.actor(payer),
+                escrow,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: escrow::instruction::MigrateEscrow {}.data(),
+        }
+    }
+
+    pub async fn make(
+        &mut self,
+        maker: usize,
+        mint_a: usize,
+        mint_b: usize,
+        seed: u64,
+        deposit: u64,
+        receive: u64,
+    ) -> Result<(), BanksClientError> {
+        let instruction = self.make_instruction(maker, mint_a, mint_b, seed, deposit, receive);
+        self.send(&[instruction], &[maker]).await
+    }
+
+    pub async fn take(
+        &mut self,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+        mint_b: usize,
+    ) -> Result<(), BanksClientError> {
+        let instruction = self.take_instruction(taker, maker, seed, mint_a, mint_b);
+        self.send(&[instruction], &[taker]).await
+    }
+
+    pub async fn refund(
+        &mut self,
+        signer: usize,
+        maker: usize,
+        seed: u64,
+        mint_a: usize,
+    ) -> Result<(), BanksClientError> {
+        let instruction = self.refund_instruction(signer, maker, seed, mint_a);
+        self.send(&[instruction], &[signer]).await
+    }
+
+    pub async fn account(&mut self, address: Pubkey) -> Option<Account> {
+        self.context
+            .banks_client
+            .get_account(address)
+            .await
+            .expect("get account")
+    }
+
+    /// Token balance of `address`, or zero when the account does not exist.
+    pub async fn token_balance(&mut self, address: Pubkey) -> u64 {
+        match self.account(address).await {
+            Some(account) => {
+                StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                    .expect("token account")
+                    .base
+                    .amount
+            }
+            None => 0,
+        }
+    }
+
+    pub async fn supply(&mut self, mint: usize) -> u64 {
+        let account = self
+            .account(self.mints[mint].address)
+            .await
+            .expect("mint account");
+        StateWithExtensions::<MintState>::unpack(&account.data)
+            .expect("mint")
+            .base
+            .supply
+    }
+
+    /// Decodes the escrow `maker` opened with `seed`, in either account layout.
+    pub async fn escrow(&mut self, maker: usize, seed: u64) -> Option<Escrow> {
+        let account = self.account(self.escrow_address(maker, seed)).await?;
+        Some(Escrow::unpack(&account.data).expect("escrow account"))
+    }
+}
+
+/// The custom program error a failed transaction ended with, if any.
+pub fn custom_error(error: &BanksClientError) -> Option<u32> {
+    match error {
+        BanksClientError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => Some(*code),
+        _ => None,
+    }
+}
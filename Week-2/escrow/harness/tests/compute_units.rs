@@ -0,0 +1,193 @@
+//! Compute-unit budget of every escrow instruction, measured against the BPF build.
+//!
+//! Run after `anchor build` with `anchor run compute-units`. Every case is compared with
+//! the checked-in `compute_units.txt` and the test fails when one grows by more than
+//! `THRESHOLD_PERCENT`. Set `UPDATE_COMPUTE_UNITS=1` to rewrite the report instead.
+
+use std::{collections::BTreeMap, fmt::Write as _, fs};
+
+use anchor_lang::{AnchorSerialize, Discriminator};
+use escrow::{Escrow, LegacyEscrow, PriceFeed};
+use escrow_harness::{bpf_program_test, Harness, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use solana_sdk::{account::AccountSharedData, pubkey::Pubkey};
+
+const REPORT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/compute_units.txt");
+const THRESHOLD_PERCENT: u64 = 5;
+
+/// Program publishing the mock feed for the price guard cases.
+const ORACLE: Pubkey = Pubkey::new_from_array([7; 32]);
+
+const HEADER: &str = "\
+# Compute units consumed by each escrow transaction, measured against the BPF build.
+# Checked by `anchor run compute-units`; regenerate with `UPDATE_COMPUTE_UNITS=1 anchor run compute-units`.
+";
+
+/// Measures every escrow instruction for mints owned by `token_program`: `make`, `take`
+/// (with and without creating the taker's and maker's token accounts), `take_with_payer`,
+/// `refund`, `set_price_guard` with a guarded `take`, and `migrate_escrow`.
+async fn measure(token_program: Pubkey, label: &str, measured: &mut BTreeMap<String, u64>) {
+    let (maker, taker, payer) = (0, 1, 2);
+    let mut harness = Harness::start(bpf_program_test(), 3).await;
+
+    let mint_a = harness.create_mint(token_program, 6).await.unwrap();
+    let mint_b = harness.create_mint(token_program, 6).await.unwrap();
+    harness.mint_to(mint_a, maker, 1_000_000).await.unwrap();
+    harness.mint_to(mint_b, taker, 1_000_000).await.unwrap();
+
+    let make = harness.make_instruction(maker, mint_a, mint_b, 0, 1_000, 2_000);
+    let units = harness.compute_units(&[make], &[maker]).await.unwrap();
+    measured.insert(format!("{label}/make"), units);
+
+    // Neither the taker's mint A account nor the maker's mint B account exist yet, so
+    // `take` has to create both.
+    let take = harness.take_instruction(taker, maker, 0, mint_a, mint_b);
+    let units = harness.compute_units(&[take], &[taker]).await.unwrap();
+    measured.insert(format!("{label}/take+create_atas"), units);
+
+    let make = harness.make_instruction(maker, mint_a, mint_b, 1, 1_000, 2_000);
+    harness.send(&[make], &[maker]).await.unwrap();
+    let take = harness.take_instruction(taker, maker, 1, mint_a, mint_b);
+    let units = harness.compute_units(&[take], &[taker]).await.unwrap();
+    measured.insert(format!("{label}/take"), units);
+
+    let make = harness.make_instruction(maker, mint_a, mint_b, 2, 1_000, 2_000);
+    harness.send(&[make], &[maker]).await.unwrap();
+    let refund = harness.refund_instruction(maker, maker, 2, mint_a);
+    let units = harness.compute_units(&[refund], &[maker]).await.unwrap();
+    measured.insert(format!("{label}/refund"), units);
+
+    let make = harness.make_instruction(maker, mint_a, mint_b, 3, 1_000, 2_000);
+    harness.send(&[make], &[maker]).await.unwrap();
+    let take = harness.take_with_payer_instruction(payer, taker, maker, 3, mint_a, mint_b, None);
+    let units = harness
+        .compute_units(&[take], &[payer, taker])
+        .await
+        .unwrap();
+    measured.insert(format!("{label}/take_with_payer"), units);
+
+    // The offer is 2 B per A, which the feed quotes exactly.
+    let make = harness.make_instruction(maker, mint_a, mint_b, 4, 1_000, 2_000);
+    harness.send(&[make], &[maker]).await.unwrap();
+    let feed = Pubkey::new_unique();
+    write_feed(&mut harness, feed, 200).await;
+    let guard = harness.set_price_guard_instruction(maker, 4, Some(feed), 100);
+    let units = harness.compute_units(&[guard], &[maker]).await.unwrap();
+    measured.insert(format!("{label}/set_price_guard"), units);
+
+    let take = harness.guarded_take_instruction(taker, maker, 4, mint_a, mint_b, Some(feed));
+    let units = harness.compute_units(&[take], &[taker]).await.unwrap();
+    measured.insert(format!("{label}/take+price_guard"), units);
+
+    let make = harness.make_instruction(maker, mint_a, mint_b, 5, 1_000, 2_000);
+    harness.send(&[make], &[maker]).await.unwrap();
+    let escrow = harness.escrow_address(maker, 5);
+    write_legacy(&mut harness, maker, 5).await;
+    let migrate = harness.migrate_escrow_instruction(payer, escrow);
+    let units = harness.compute_units(&[migrate], &[payer]).await.unwrap();
+    measured.insert(format!("{label}/migrate_escrow"), units);
+}
+
+/// Stores a feed owned by `ORACLE` quoting `price / 100` B per A, fresh for the next
+/// transaction.
+async fn write_feed(harness: &mut Harness, address: Pubkey, price: u64) {
+    let mut data = price.to_le_bytes().to_vec();
+    data.push(2);
+    data.resize(16, 0);
+    data.extend_from_slice(&harness.slot().to_le_bytes());
+    assert_eq!(data.len(), PriceFeed::LEN);
+
+    let rent = harness.context.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &ORACLE);
+    account.set_data_from_slice(&data);
+    harness.context.set_account(&address, &account);
+}
+
+/// Rewrites the escrow `maker` opened with `seed` in the layout used before versioning.
+async fn write_legacy(harness: &mut Harness, maker: usize, seed: u64) {
+    let escrow = harness.escrow(maker, seed).await.unwrap();
+    let legacy = LegacyEscrow {
+        seed: escrow.seed,
+        maker: escrow.maker,
+        mint_a: escrow.mint_a,
+        mint_b: escrow.mint_b,
+        receive: escrow.receive,
+        bump: escrow.bump,
+    };
+
+    let mut data = Escrow::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data).unwrap();
+    assert_eq!(data.len(), LegacyEscrow::LEN);
+
+    let rent = harness.context.banks_client.get_rent().await.unwrap();
+    let mut account =
+        AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &escrow::ID);
+    account.set_data_from_slice(&data);
+    let address = harness.escrow_address(maker, seed);
+    harness.context.set_account(&address, &account);
+}
+
+fn read_report() -> BTreeMap<String, u64> {
+    let report = fs::read_to_string(REPORT).unwrap_or_default();
+
+    report
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (case, units) = line
+                .split_once(char::is_whitespace)
+                .unwrap_or_else(|| panic!("malformed report line: {line}"));
+            let units = units
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("malformed report line: {line}"));
+            (case.to_string(), units)
+        })
+        .collect()
+}
+
+fn write_report(measured: &BTreeMap<String, u64>) {
+    let mut report = HEADER.to_string();
+    for (case, units) in measured {
+        writeln!(report, "{case:<32} {units}").unwrap();
+    }
+    fs::write(REPORT, report).unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs target/deploy/escrow.so from `anchor build`"]
+async fn compute_units_within_budget() {
+    let mut measured = BTreeMap::new();
+    measure(TOKEN_PROGRAM_ID, "token", &mut measured).await;
+    measure(TOKEN_2022_PROGRAM_ID, "token-2022", &mut measured).await;
+
+    if std::env::var_os("UPDATE_COMPUTE_UNITS").is_some() {
+        write_report(&measured);
+        return;
+    }
+
+    let baseline = read_report();
+    assert!(
+        !baseline.is_empty(),
+        "compute_units.txt has no measurements yet: run `UPDATE_COMPUTE_UNITS=1 anchor run \
+         compute-units` after `anchor build` and commit the report"
+    );
+
+    let mut failures = Vec::new();
+
+    for (case, &units) in &measured {
+        match baseline.get(case) {
+            Some(&budget) if units > budget + budget * THRESHOLD_PERCENT / 100 => {
+                failures.push(format!("{case}: {units} CU, budget {budget} CU"))
+            }
+            Some(&budget) => println!("{case}: {units} CU, budget {budget} CU"),
+            None => failures.push(format!("{case}: {units} CU, not in the report")),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "compute units regressed by more than {THRESHOLD_PERCENT}%:\n{}",
+        failures.join("\n")
+    );
+}
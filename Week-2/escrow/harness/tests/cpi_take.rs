@@ -0,0 +1,137 @@
+//! Another Anchor program takes escrows for its PDAs through `take_with_payer`.
+
+use anchor_lang::{
+    prelude::{AccountInfo, Pubkey},
+    solana_program::entrypoint::ProgramResult,
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::spl_associated_token_account;
+use escrow_harness::{custom_error, program_test, Harness, TOKEN_PROGRAM_ID};
+use solana_program_test::processor;
+use solana_sdk::instruction::Instruction;
+
+const MAKER: usize = 0;
+const AUTHORITY: usize = 1;
+const PAYER: usize = 2;
+const INTRUDER: usize = 3;
+const SEED: u64 = 1;
+
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    escrow_taker::entry(program_id, accounts, data)
+}
+
+fn taker_pda(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"taker", authority.as_ref()], &escrow_taker::ID).0
+}
+
+fn take_instruction(
+    harness: &Harness,
+    authority: usize,
+    taker: Pubkey,
+    mint_a: usize,
+    mint_b: usize,
+) -> Instruction {
+    Instruction {
+        program_id: escrow_taker::ID,
+        accounts: escrow_taker::accounts::Take {
+            payer: harness.actor(PAYER),
+            authority: harness.actor(authority),
+            taker,
+            maker: harness.actor(MAKER),
+            mint_a: harness.mints[mint_a].address,
+            mint_b: harness.mints[mint_b].address,
+            taker_ata_a: harness.ata_of(&taker, mint_a),
+            taker_ata_b: harness.ata_of(&taker, mint_b),
+            maker_ata_b: harness.ata(MAKER, mint_b),
+            escrow: harness.escrow_address(MAKER, SEED),
+            vault: harness.vault_address(MAKER, SEED, mint_a),
+            price_feed: None,
+            escrow_program: escrow::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: escrow_taker::instruction::Take {}.data(),
+    }
+}
+
+async fn setup() -> (Harness, Pubkey, usize, usize) {
+    let mut program_test = program_test();
+    program_test.add_program(
+        "escrow_taker",
+        escrow_taker::ID,
+        processor!(process_instruction),
+    );
+
+    let mut harness = Harness::start(program_test, 4).await;
+    let mint_a = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    let mint_b = harness.create_mint(TOKEN_PROGRAM_ID, 9).await.unwrap();
+
+    let taker = taker_pda(&harness.actor(AUTHORITY));
+    harness.mint_to(mint_a, MAKER, 1_000).await.unwrap();
+    harness.mint_to_owner(mint_b, taker, 500).await.unwrap();
+    harness
+        .make(MAKER, mint_a, mint_b, SEED, 600, 300)
+        .await
+        .unwrap();
+
+    (harness, taker, mint_a, mint_b)
+}
+
+#[tokio::test]
+async fn pda_taker_takes_through_cpi() {
+    let (mut harness, taker, mint_a, mint_b) = setup().await;
+    let payer_before = harness
+        .account(harness.actor(PAYER))
+        .await
+        .unwrap()
+        .lamports;
+
+    let take = take_instruction(&harness, AUTHORITY, taker, mint_a, mint_b);
+    harness.send(&[take], &[PAYER, AUTHORITY]).await.unwrap();
+
+    assert_eq!(
+        harness.token_balance(harness.ata_of(&taker, mint_a)).await,
+        600
+    );
+    assert_eq!(
+        harness.token_balance(harness.ata_of(&taker, mint_b)).await,
+        200
+    );
+    assert_eq!(harness.token_balance(harness.ata(MAKER, mint_b)).await, 300);
+    assert!(harness.escrow(MAKER, SEED).await.is_none());
+
+    // The PDA never needed lamports: the payer covered both new token accounts and got
+    // the vault's rent back.
+    assert!(harness.account(taker).await.is_none());
+    let payer_after = harness
+        .account(harness.actor(PAYER))
+        .await
+        .unwrap()
+        .lamports;
+    assert!(payer_after < payer_before);
+}
+
+#[tokio::test]
+async fn only_the_authority_can_spend_its_pda() {
+    let (mut harness, taker, mint_a, mint_b) = setup().await;
+
+    let take = take_instruction(&harness, INTRUDER, taker, mint_a, mint_b);
+    let err = harness.send(&[take], &[PAYER, INTRUDER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(anchor_lang::error::ErrorCode::ConstraintSeeds as u32)
+    );
+
+    assert_eq!(
+        harness.token_balance(harness.ata_of(&taker, mint_b)).await,
+        500
+    );
+    assert!(harness.escrow(MAKER, SEED).await.is_some());
+}
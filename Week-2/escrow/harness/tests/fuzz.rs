@@ -0,0 +1,610 @@
+//! State-machine fuzzing of the escrow program.
+//!
+//! Random actors run random sequences of `make`, `take`, `take_with_payer`, `refund`,
+//! `set_price_guard` and `migrate_escrow` over Token and Token-2022 mints. Some escrows
+//! are rewritten in the legacy layout right after `make`, and price guards quote a price
+//! near the offer's. After every step the bank is compared against a model of who should
+//! hold what, and the global invariants are checked:
+//!
+//! - the supply of every mint is fully accounted for by actor accounts and vaults,
+//! - a vault is empty and closed whenever its `Escrow` is closed,
+//! - a maker never loses mint A without being paid `receive` of mint B.
+
+use std::collections::HashMap;
+
+use anchor_lang::{AnchorSerialize, Discriminator};
+use escrow::{Escrow, LegacyEscrow, PriceFeed, ESCROW_VERSION, LEGACY_ESCROW_VERSION};
+use escrow_harness::{program_test, Harness, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use proptest::{
+    prelude::*,
+    test_runner::{Config, RngSeed},
+};
+use solana_sdk::{account::AccountSharedData, pubkey::Pubkey};
+
+const ACTORS: usize = 3;
+const SEEDS: u64 = 2;
+const MINT_PROGRAMS: [Pubkey; 4] = [
+    TOKEN_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+    TOKEN_2022_PROGRAM_ID,
+];
+const MINTS: usize = MINT_PROGRAMS.len();
+const MAX_BALANCE: u64 = 1_000;
+
+/// Program publishing the feeds of price guards.
+const ORACLE: Pubkey = Pubkey::new_from_array([7; 32]);
+/// Decimals of the prices feeds quote.
+const FEED_DECIMALS: u8 = 4;
+/// How far, in basis points either way, a feed's price strays from the offer's.
+const MAX_SKEW_BPS: i64 = 500;
+
+#[derive(Clone, Debug)]
+enum Action {
+    Make {
+        maker: usize,
+        mint_a: usize,
+        mint_b: usize,
+        seed: u64,
+        deposit: u64,
+        receive: u64,
+        /// Rewrite the escrow in the layout used before versioning once it is made.
+        legacy: bool,
+    },
+    Take {
+        taker: usize,
+        maker: usize,
+        seed: u64,
+    },
+    TakeWithPayer {
+        payer: usize,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+    },
+    Refund {
+        signer: usize,
+        maker: usize,
+        seed: u64,
+    },
+    /// Guards the escrow with a feed quoting its price off by `skew_bps`, or removes the
+    /// guard when `skew_bps` is `None`.
+    SetPriceGuard {
+        signer: usize,
+        maker: usize,
+        seed: u64,
+        skew_bps: Option<i64>,
+        max_deviation_bps: u16,
+    },
+    MigrateEscrow {
+        payer: usize,
+        maker: usize,
+        seed: u64,
+    },
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    // Amounts reach past `MAX_BALANCE` so that underfunded makes and takes are covered too.
+    let amount = 0..MAX_BALANCE + MAX_BALANCE / 2;
+
+    // Mostly pair mint A with the other mint of the same token program, sometimes with
+    // itself, and sometimes with a mint of the other program, which `make` must reject.
+    let mints = (0..MINTS).prop_flat_map(|mint_a| {
+        (
+            Just(mint_a),
+            prop_oneof![
+                6 => Just(mint_a ^ 1),
+                1 => Just(mint_a),
+                2 => Just((mint_a + MINTS / 2) % MINTS),
+            ],
+        )
+    });
+
+    prop_oneof![
+        4 => (0..ACTORS, mints, 0..SEEDS, amount.clone(), amount, prop::bool::weighted(0.3))
+            .prop_map(
+                |(maker, (mint_a, mint_b), seed, deposit, receive, legacy)| Action::Make {
+                    maker,
+                    mint_a,
+                    mint_b,
+                    seed,
+                    deposit,
+                    receive,
+                    legacy,
+                }
+            ),
+        3 => (0..ACTORS, 0..ACTORS, 0..SEEDS)
+            .prop_map(|(taker, maker, seed)| Action::Take { taker, maker, seed }),
+        2 => (0..ACTORS, 0..ACTORS, 0..ACTORS, 0..SEEDS).prop_map(
+            |(payer, taker, maker, seed)| Action::TakeWithPayer {
+                payer,
+                taker,
+                maker,
+                seed,
+            }
+        ),
+        2 => (0..ACTORS, 0..ACTORS, 0..SEEDS).prop_map(|(signer, maker, seed)| {
+            Action::Refund {
+                signer,
+                maker,
+                seed,
+            }
+        }),
+        // Mostly signed by the maker, who is the only one allowed to set a guard.
+        2 => (
+            0..ACTORS,
+            prop::option::weighted(0.25, 0..ACTORS),
+            0..SEEDS,
+            prop::option::weighted(0.8, -MAX_SKEW_BPS..=MAX_SKEW_BPS),
+            0..=MAX_SKEW_BPS as u16,
+        )
+            .prop_map(|(maker, signer, seed, skew_bps, max_deviation_bps)| {
+                Action::SetPriceGuard {
+                    signer: signer.unwrap_or(maker),
+                    maker,
+                    seed,
+                    skew_bps,
+                    max_deviation_bps,
+                }
+            }),
+        1 => (0..ACTORS, 0..ACTORS, 0..SEEDS).prop_map(|(payer, maker, seed)| {
+            Action::MigrateEscrow { payer, maker, seed }
+        }),
+    ]
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Offer {
+    mint_a: usize,
+    mint_b: usize,
+    deposit: u64,
+    receive: u64,
+    /// Whether the escrow is still in the legacy layout.
+    legacy: bool,
+    guard: Option<Guard>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Guard {
+    address: Pubkey,
+    feed: PriceFeed,
+    max_deviation_bps: u16,
+}
+
+/// What the bank should look like if the escrow behaves.
+struct Model {
+    /// Expected amount held by every token account the test knows about.
+    balances: HashMap<Pubkey, u64>,
+    /// Escrows that are currently open, keyed by `(maker, seed)`.
+    open: HashMap<(usize, u64), Offer>,
+    /// The last offer made for every `(maker, seed)`, open or not.
+    made: HashMap<(usize, u64), Offer>,
+    supply: Vec<u64>,
+}
+
+impl Model {
+    fn balance(&self, account: &Pubkey) -> u64 {
+        self.balances.get(account).copied().unwrap_or_default()
+    }
+
+    fn debit(&mut self, account: Pubkey, amount: u64) {
+        *self.balances.entry(account).or_default() -= amount;
+    }
+
+    fn credit(&mut self, account: Pubkey, amount: u64) {
+        *self.balances.entry(account).or_default() += amount;
+    }
+}
+
+struct Fuzzer {
+    harness: Harness,
+    model: Model,
+}
+
+impl Fuzzer {
+    async fn start(decimals: &[u8], balances: &[Vec<u64>]) -> Self {
+        let mut harness = Harness::start(program_test(), ACTORS).await;
+
+        for (token_program, decimals) in MINT_PROGRAMS.iter().zip(decimals) {
+            harness
+                .create_mint(*token_program, *decimals)
+                .await
+                .unwrap();
+        }
+
+        let mut model = Model {
+            balances: HashMap::new(),
+            open: HashMap::new(),
+            made: HashMap::new(),
+            supply: vec![0; MINTS],
+        };
+
+        for (actor, balances) in balances.iter().enumerate() {
+            for (mint, &amount) in balances.iter().enumerate() {
+                harness.mint_to(mint, actor, amount).await.unwrap();
+                model.credit(harness.ata(actor, mint), amount);
+                model.supply[mint] += amount;
+            }
+        }
+
+        Self { harness, model }
+    }
+
+    /// Every token account of `mint` the test knows about.
+    fn accounts(&self, mint: usize) -> Vec<Pubkey> {
+        let actors = (0..ACTORS).map(|actor| self.harness.ata(actor, mint));
+        let vaults = self
+            .model
+            .made
+            .iter()
+            .filter(|(_, offer)| offer.mint_a == mint)
+            .map(|(&(maker, seed), _)| self.harness.vault_address(maker, seed, mint));
+
+        actors.chain(vaults).collect()
+    }
+
+    async fn step(&mut self, action: &Action) {
+        match *action {
+            Action::Make {
+                maker,
+                mint_a,
+                mint_b,
+                seed,
+                deposit,
+                receive,
+                legacy,
+            } => {
+                let maker_ata_a = self.harness.ata(maker, mint_a);
+                let expected = !self.model.open.contains_key(&(maker, seed))
+                    && MINT_PROGRAMS[mint_a] == MINT_PROGRAMS[mint_b]
+                    && self.model.balance(&maker_ata_a) >= deposit;
+
+                let result = self
+                    .harness
+                    .make(maker, mint_a, mint_b, seed, deposit, receive)
+                    .await;
+                assert_eq!(result.is_ok(), expected, "{action:?}: {result:?}");
+
+                if expected {
+                    if legacy {
+                        self.write_legacy(maker, seed).await;
+                    }
+
+                    let offer = Offer {
+                        mint_a,
+                        mint_b,
+                        deposit,
+                        receive,
+                        legacy,
+                        guard: None,
+                    };
+                    self.model.debit(maker_ata_a, deposit);
+                    self.model
+                        .credit(self.harness.vault_address(maker, seed, mint_a), deposit);
+                    self.model.open.insert((maker, seed), offer);
+                    self.model.made.insert((maker, seed), offer);
+                }
+            }
+            Action::Take { taker, maker, seed } => {
+                self.take(action, None, taker, maker, seed).await
+            }
+            Action::TakeWithPayer {
+                payer,
+                taker,
+                maker,
+                seed,
+            } => self.take(action, Some(payer), taker, maker, seed).await,
+            Action::Refund {
+                signer,
+                maker,
+                seed,
+            } => {
+                let open = self.model.open.get(&(maker, seed)).copied();
+                let mint_a = open
+                    .or_else(|| self.model.made.get(&(maker, seed)).copied())
+                    .map_or(0, |offer| offer.mint_a);
+                let expected = open.is_some_and(|offer| !offer.legacy) && signer == maker;
+
+                let result = self.harness.refund(signer, maker, seed, mint_a).await;
+                assert_eq!(result.is_ok(), expected, "{action:?}: {result:?}");
+
+                if let (true, Some(offer)) = (expected, open) {
+                    self.model.debit(
+                        self.harness.vault_address(maker, seed, mint_a),
+                        offer.deposit,
+                    );
+                    self.model
+                        .credit(self.harness.ata(maker, mint_a), offer.deposit);
+                    self.model.open.remove(&(maker, seed));
+                }
+            }
+            Action::SetPriceGuard {
+                signer,
+                maker,
+                seed,
+                skew_bps,
+                max_deviation_bps,
+            } => {
+                let open = self.model.open.get(&(maker, seed)).copied();
+                let guard = match (open, skew_bps) {
+                    (Some(offer), Some(skew_bps)) => {
+                        Some(self.write_feed(&offer, skew_bps, max_deviation_bps).await)
+                    }
+                    _ => None,
+                };
+                // A feed quoting a price of zero is rejected as malformed.
+                let expected = open.is_some_and(|offer| !offer.legacy)
+                    && signer == maker
+                    && guard.is_none_or(|guard| guard.feed.price > 0);
+
+                let mut instruction = self.harness.set_price_guard_instruction(
+                    maker,
+                    seed,
+                    guard.map(|guard| guard.address),
+                    max_deviation_bps,
+                );
+                instruction.accounts[0].pubkey = self.harness.actor(signer);
+                let result = self.harness.send(&[instruction], &[signer]).await;
+                assert_eq!(result.is_ok(), expected, "{action:?}: {result:?}");
+
+                if expected {
+                    let offer = self.model.open.get_mut(&(maker, seed)).unwrap();
+                    offer.guard = guard;
+                    *self.model.made.get_mut(&(maker, seed)).unwrap() = *offer;
+                }
+            }
+            Action::MigrateEscrow { payer, maker, seed } => {
+                let open = self.model.open.get(&(maker, seed)).copied();
+                let expected = open.is_some_and(|offer| offer.legacy);
+
+                let escrow = self.harness.escrow_address(maker, seed);
+                let instruction = self.harness.migrate_escrow_instruction(payer, escrow);
+                let result = self.harness.send(&[instruction], &[payer]).await;
+                assert_eq!(result.is_ok(), expected, "{action:?}: {result:?}");
+
+                if expected {
+                    let offer = self.model.open.get_mut(&(maker, seed)).unwrap();
+                    offer.legacy = false;
+                    *self.model.made.get_mut(&(maker, seed)).unwrap() = *offer;
+                }
+            }
+        }
+    }
+
+    /// `take` or, with a `payer`, `take_with_payer`, passing the escrow's feed along if
+    /// the model says it is guarded.
+    async fn take(
+        &mut self,
+        action: &Action,
+        payer: Option<usize>,
+        taker: usize,
+        maker: usize,
+        seed: u64,
+    ) {
+        let open = self.model.open.get(&(maker, seed)).copied();
+        let Offer {
+            mint_a,
+            mint_b,
+            deposit,
+            receive,
+            guard,
+            ..
+        } = open
+            .or_else(|| self.model.made.get(&(maker, seed)).copied())
+            .unwrap_or(Offer {
+                mint_a: 0,
+                mint_b: 1,
+                deposit: 0,
+                receive: 0,
+                legacy: false,
+                guard: None,
+            });
+
+        let taker_ata_b = self.harness.ata(taker, mint_b);
+        let maker_ata_b = self.harness.ata(maker, mint_b);
+        let price_ok = guard.is_none_or(|guard| {
+            let within = guard.feed.within(
+                receive,
+                deposit,
+                self.harness.mints[mint_a].decimals,
+                self.harness.mints[mint_b].decimals,
+                guard.max_deviation_bps,
+            );
+            matches!(within, Ok(true))
+        });
+        let expected = open.is_some_and(|offer| !offer.legacy)
+            && self.model.balance(&taker_ata_b) >= receive
+            && price_ok;
+
+        let price_feed = guard.map(|guard| guard.address);
+        let instruction = match payer {
+            Some(payer) => self
+                .harness
+                .take_with_payer_instruction(payer, taker, maker, seed, mint_a, mint_b, price_feed),
+            None => self
+                .harness
+                .guarded_take_instruction(taker, maker, seed, mint_a, mint_b, price_feed),
+        };
+        let signers = match payer {
+            Some(payer) if payer != taker => vec![payer, taker],
+            _ => vec![taker],
+        };
+
+        let maker_b_before = self.harness.token_balance(maker_ata_b).await;
+        let result = self.harness.send(&[instruction], &signers).await;
+        assert_eq!(result.is_ok(), expected, "{action:?}: {result:?}");
+
+        if expected {
+            if taker != maker {
+                let maker_b_after = self.harness.token_balance(maker_ata_b).await;
+                assert_eq!(
+                    maker_b_after - maker_b_before,
+                    receive,
+                    "maker was not paid for {action:?}"
+                );
+            }
+
+            self.model.debit(taker_ata_b, receive);
+            self.model.credit(maker_ata_b, receive);
+            self.model
+                .debit(self.harness.vault_address(maker, seed, mint_a), deposit);
+            self.model.credit(self.harness.ata(taker, mint_a), deposit);
+            self.model.open.remove(&(maker, seed));
+        }
+    }
+
+    /// Publishes a fresh feed as `ORACLE` quoting the offer's price off by `skew_bps`.
+    async fn write_feed(&mut self, offer: &Offer, skew_bps: i64, max_deviation_bps: u16) -> Guard {
+        // The offer's price in whole B per whole A, with `FEED_DECIMALS` decimals
+        let decimals_a = self.harness.mints[offer.mint_a].decimals as u32;
+        let decimals_b = self.harness.mints[offer.mint_b].decimals as u32;
+        let offered = offer.receive as u128 * 10u128.pow(decimals_a + FEED_DECIMALS as u32)
+            / (offer.deposit.max(1) as u128 * 10u128.pow(decimals_b));
+        let price = offered * (10_000 + skew_bps) as u128 / 10_000;
+
+        let feed = PriceFeed {
+            price: price.try_into().unwrap_or(u64::MAX),
+            decimals: FEED_DECIMALS,
+            padding: [0; 7],
+            published_slot: self.harness.slot(),
+        };
+
+        let mut data = feed.price.to_le_bytes().to_vec();
+        data.push(feed.decimals);
+        data.resize(16, 0);
+        data.extend_from_slice(&feed.published_slot.to_le_bytes());
+        assert_eq!(data.len(), PriceFeed::LEN);
+
+        let address = Pubkey::new_unique();
+        let rent = self.harness.context.banks_client.get_rent().await.unwrap();
+        let mut account =
+            AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &ORACLE);
+        account.set_data_from_slice(&data);
+        self.harness.context.set_account(&address, &account);
+
+        Guard {
+            address,
+            feed,
+            max_deviation_bps,
+        }
+    }
+
+    /// Rewrites the escrow `maker` just made with `seed` in the legacy layout, keeping
+    /// its lamports.
+    async fn write_legacy(&mut self, maker: usize, seed: u64) {
+        let address = self.harness.escrow_address(maker, seed);
+        let lamports = self.harness.account(address).await.unwrap().lamports;
+        let escrow = self.harness.escrow(maker, seed).await.unwrap();
+        let legacy = LegacyEscrow {
+            seed: escrow.seed,
+            maker: escrow.maker,
+            mint_a: escrow.mint_a,
+            mint_b: escrow.mint_b,
+            receive: escrow.receive,
+            bump: escrow.bump,
+        };
+
+        let mut data = Escrow::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), LegacyEscrow::LEN);
+
+        let mut account = AccountSharedData::new(lamports, data.len(), &escrow::ID);
+        account.set_data_from_slice(&data);
+        self.harness.context.set_account(&address, &account);
+    }
+
+    async fn check_invariants(&mut self, action: &Action) {
+        for mint in 0..MINTS {
+            let mut total = 0;
+            for account in self.accounts(mint) {
+                let actual = self.harness.token_balance(account).await;
+                assert_eq!(
+                    actual,
+                    self.model.balance(&account),
+                    "balance of {account} after {action:?}"
+                );
+                total += actual;
+            }
+
+            let supply = self.harness.supply(mint).await;
+            assert_eq!(
+                supply, self.model.supply[mint],
+                "supply of mint {mint} changed"
+            );
+            assert_eq!(
+                total, supply,
+                "mint {mint} has tokens outside known accounts"
+            );
+        }
+
+        let made: Vec<_> = self.model.made.iter().map(|(&k, &v)| (k, v)).collect();
+        for ((maker, seed), offer) in made {
+            let vault = self.harness.vault_address(maker, seed, offer.mint_a);
+            let escrow = self.harness.escrow(maker, seed).await;
+            let vault_account = self.harness.account(vault).await;
+
+            match escrow {
+                Some(escrow) => {
+                    assert!(self.model.open.contains_key(&(maker, seed)));
+                    assert_eq!(escrow.receive, offer.receive);
+                    let version = if offer.legacy {
+                        LEGACY_ESCROW_VERSION
+                    } else {
+                        ESCROW_VERSION
+                    };
+                    assert_eq!(escrow.version, version);
+                    assert_eq!(
+                        escrow.price_feed,
+                        offer.guard.map_or(Pubkey::default(), |guard| guard.address)
+                    );
+                    assert_eq!(
+                        self.harness.token_balance(vault).await,
+                        offer.deposit,
+                        "open escrow ({maker}, {seed}) lost its deposit"
+                    );
+                }
+                None => {
+                    assert!(!self.model.open.contains_key(&(maker, seed)));
+                    assert!(
+                        vault_account.is_none(),
+                        "vault of closed escrow ({maker}, {seed}) still exists"
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn scenario() -> impl Strategy<Value = (Vec<u8>, Vec<Vec<u64>>, Vec<Action>)> {
+    (
+        prop::collection::vec(0u8..=9, MINTS),
+        prop::collection::vec(prop::collection::vec(0..=MAX_BALANCE, MINTS), ACTORS),
+        prop::collection::vec(action(), 1..64),
+    )
+}
+
+proptest! {
+    #![proptest_config(Config {
+        cases: 32,
+        rng_seed: RngSeed::Fixed(0x6573_6372_6f77),
+        failure_persistence: None,
+        ..Config::default()
+    })]
+
+    #[test]
+    fn escrow_never_leaks_tokens((decimals, balances, actions) in scenario()) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let mut fuzzer = Fuzzer::start(&decimals, &balances).await;
+
+            for action in &actions {
+                fuzzer.step(action).await;
+                fuzzer.check_invariants(action).await;
+            }
+        });
+    }
+}
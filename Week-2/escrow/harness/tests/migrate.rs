@@ -0,0 +1,156 @@
+//! `migrate_escrow` upgrades escrows written before the account layout was versioned.
+
+use anchor_lang::{error::ERROR_CODE_OFFSET, AnchorSerialize, Discriminator};
+use escrow::{Escrow, EscrowError, LegacyEscrow, ESCROW_VERSION, LEGACY_ESCROW_VERSION};
+use escrow_harness::{custom_error, program_test, Harness, TOKEN_2022_PROGRAM_ID};
+use solana_sdk::{account::AccountSharedData, pubkey::Pubkey};
+
+const MAKER: usize = 0;
+const TAKER: usize = 1;
+const SEED: u64 = 7;
+
+struct Setup {
+    harness: Harness,
+    mint_a: usize,
+    mint_b: usize,
+}
+
+/// Opens an escrow and rewrites its account in the legacy Borsh layout, as if it had
+/// been made before versioning.
+async fn legacy_escrow() -> Setup {
+    let mut harness = Harness::start(program_test(), 2).await;
+    let mint_a = harness.create_mint(TOKEN_2022_PROGRAM_ID, 6).await.unwrap();
+    let mint_b = harness.create_mint(TOKEN_2022_PROGRAM_ID, 6).await.unwrap();
+    harness.mint_to(mint_a, MAKER, 1_000).await.unwrap();
+    harness.mint_to(mint_b, TAKER, 1_000).await.unwrap();
+    harness
+        .make(MAKER, mint_a, mint_b, SEED, 400, 250)
+        .await
+        .unwrap();
+
+    let escrow = harness.escrow(MAKER, SEED).await.unwrap();
+    let address = harness.escrow_address(MAKER, SEED);
+    write_legacy(&mut harness, address, &escrow).await;
+
+    Setup {
+        harness,
+        mint_a,
+        mint_b,
+    }
+}
+
+async fn write_legacy(harness: &mut Harness, address: Pubkey, escrow: &Escrow) {
+    let legacy = LegacyEscrow {
+        seed: escrow.seed,
+        maker: escrow.maker,
+        mint_a: escrow.mint_a,
+        mint_b: escrow.mint_b,
+        receive: escrow.receive,
+        bump: escrow.bump,
+    };
+
+    let mut data = Escrow::DISCRIMINATOR.to_vec();
+    legacy.serialize(&mut data).unwrap();
+    assert_eq!(data.len(), LegacyEscrow::LEN);
+
+    let rent = harness.context.banks_client.get_rent().await.unwrap();
+    let mut account =
+        AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &escrow::ID);
+    account.set_data_from_slice(&data);
+    harness.context.set_account(&address, &account);
+}
+
+fn error(code: EscrowError) -> Option<u32> {
+    Some(ERROR_CODE_OFFSET + code as u32)
+}
+
+#[tokio::test]
+async fn clients_decode_both_layouts() {
+    let Setup { mut harness, .. } = legacy_escrow().await;
+
+    let legacy = harness.escrow(MAKER, SEED).await.unwrap();
+    assert_eq!(legacy.version, LEGACY_ESCROW_VERSION);
+    assert_eq!(legacy.seed, SEED);
+    assert_eq!(legacy.receive, 250);
+    assert_eq!(legacy.maker, harness.actor(MAKER));
+
+    let migrate = harness.migrate_escrow_instruction(TAKER, harness.escrow_address(MAKER, SEED));
+    harness.send(&[migrate], &[TAKER]).await.unwrap();
+
+    let current = harness.escrow(MAKER, SEED).await.unwrap();
+    assert_eq!(current.version, ESCROW_VERSION);
+    assert_eq!(current.seed, legacy.seed);
+    assert_eq!(current.receive, legacy.receive);
+    assert_eq!(current.maker, legacy.maker);
+    assert_eq!(current.mint_a, legacy.mint_a);
+    assert_eq!(current.mint_b, legacy.mint_b);
+    assert_eq!(current.bump, legacy.bump);
+}
+
+#[tokio::test]
+async fn legacy_escrow_must_be_migrated_before_settling() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+    } = legacy_escrow().await;
+    let address = harness.escrow_address(MAKER, SEED);
+
+    let err = harness
+        .take(TAKER, MAKER, SEED, mint_a, mint_b)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::LegacyEscrow));
+
+    let err = harness
+        .refund(MAKER, MAKER, SEED, mint_a)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::LegacyEscrow));
+
+    let migrate = harness.migrate_escrow_instruction(MAKER, address);
+    harness.send(&[migrate], &[MAKER]).await.unwrap();
+
+    let account = harness.account(address).await.unwrap();
+    let rent = harness.context.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.data.len(), Escrow::LEN);
+    assert!(rent.is_exempt(account.lamports, account.data.len()));
+
+    harness
+        .take(TAKER, MAKER, SEED, mint_a, mint_b)
+        .await
+        .unwrap();
+    assert_eq!(harness.token_balance(harness.ata(TAKER, mint_a)).await, 400);
+    assert_eq!(harness.token_balance(harness.ata(MAKER, mint_b)).await, 250);
+    assert!(harness.account(address).await.is_none());
+}
+
+#[tokio::test]
+async fn migrating_twice_fails() {
+    let Setup { mut harness, .. } = legacy_escrow().await;
+    let address = harness.escrow_address(MAKER, SEED);
+
+    let migrate = harness.migrate_escrow_instruction(MAKER, address);
+    harness.send(&[migrate], &[MAKER]).await.unwrap();
+
+    let migrate = harness.migrate_escrow_instruction(MAKER, address);
+    let err = harness.send(&[migrate], &[MAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::AlreadyMigrated));
+}
+
+#[tokio::test]
+async fn migration_checks_the_escrow_address() {
+    let Setup { mut harness, .. } = legacy_escrow().await;
+
+    // Legacy data claiming to be the maker's escrow, but stored at another address.
+    let escrow = harness.escrow(MAKER, SEED).await.unwrap();
+    let impostor = Pubkey::new_unique();
+    write_legacy(&mut harness, impostor, &escrow).await;
+
+    let migrate = harness.migrate_escrow_instruction(TAKER, impostor);
+    let err = harness.send(&[migrate], &[TAKER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        Some(anchor_lang::error::ErrorCode::ConstraintSeeds as u32)
+    );
+}
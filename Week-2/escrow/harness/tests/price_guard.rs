@@ -0,0 +1,269 @@
+//! `take` refuses to settle a guarded escrow once its price has drifted from the feed.
+
+use anchor_lang::error::ERROR_CODE_OFFSET;
+use escrow::{EscrowError, PriceFeed, MAX_PRICE_AGE_SLOTS};
+use escrow_harness::{custom_error, program_test, Harness, TOKEN_PROGRAM_ID};
+use solana_sdk::{account::AccountSharedData, pubkey::Pubkey};
+
+const MAKER: usize = 0;
+const TAKER: usize = 1;
+const SEED: u64 = 3;
+
+/// Program publishing the mock feeds.
+const ORACLE: Pubkey = Pubkey::new_from_array([7; 32]);
+
+/// 2 whole A for 3 whole B: an offer price of 1.5 B per A.
+const DEPOSIT: u64 = 2_000_000;
+const RECEIVE: u64 = 3_000_000_000;
+
+struct Setup {
+    harness: Harness,
+    mint_a: usize,
+    mint_b: usize,
+    feed: Pubkey,
+}
+
+/// Opens an escrow guarded by a feed quoting `price / 100` B per A with the given
+/// tolerance.
+async fn guarded_escrow(price: u64, max_deviation_bps: u16) -> Setup {
+    let mut harness = Harness::start(program_test(), 2).await;
+    // Far enough along that a feed can be published longer ago than `MAX_PRICE_AGE_SLOTS`
+    harness.skip_slots(2 * MAX_PRICE_AGE_SLOTS);
+    let mint_a = harness.create_mint(TOKEN_PROGRAM_ID, 6).await.unwrap();
+    let mint_b = harness.create_mint(TOKEN_PROGRAM_ID, 9).await.unwrap();
+    harness.mint_to(mint_a, MAKER, DEPOSIT).await.unwrap();
+    harness.mint_to(mint_b, TAKER, RECEIVE).await.unwrap();
+    harness
+        .make(MAKER, mint_a, mint_b, SEED, DEPOSIT, RECEIVE)
+        .await
+        .unwrap();
+
+    let feed = Pubkey::new_unique();
+    write_feed(&mut harness, feed, price).await;
+
+    let guard = harness.set_price_guard_instruction(MAKER, SEED, Some(feed), max_deviation_bps);
+    harness.send(&[guard], &[MAKER]).await.unwrap();
+
+    Setup {
+        harness,
+        mint_a,
+        mint_b,
+        feed,
+    }
+}
+
+/// Stores a mock feed in the bank as `ORACLE` would publish it, fresh for the next
+/// transaction.
+async fn write_feed(harness: &mut Harness, address: Pubkey, price: u64) {
+    let slot = harness.slot();
+    write_feed_as(harness, address, price, slot, ORACLE).await;
+}
+
+/// Stores a mock feed in the bank, published at `published_slot` and owned by `owner`.
+async fn write_feed_as(
+    harness: &mut Harness,
+    address: Pubkey,
+    price: u64,
+    published_slot: u64,
+    owner: Pubkey,
+) {
+    let mut data = price.to_le_bytes().to_vec();
+    data.push(2);
+    data.resize(16, 0);
+    data.extend_from_slice(&published_slot.to_le_bytes());
+    assert_eq!(data.len(), PriceFeed::LEN);
+
+    let rent = harness.context.banks_client.get_rent().await.unwrap();
+    let mut account = AccountSharedData::new(rent.minimum_balance(data.len()), data.len(), &owner);
+    account.set_data_from_slice(&data);
+    harness.context.set_account(&address, &account);
+}
+
+fn error(code: EscrowError) -> Option<u32> {
+    Some(ERROR_CODE_OFFSET + code as u32)
+}
+
+#[tokio::test]
+async fn take_settles_within_the_deviation() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        feed,
+    } = guarded_escrow(152, 200).await;
+
+    let escrow = harness.escrow(MAKER, SEED).await.unwrap();
+    assert_eq!(escrow.price_feed, feed);
+    assert_eq!(escrow.max_deviation_bps, 200);
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    harness.send(&[take], &[TAKER]).await.unwrap();
+
+    assert_eq!(
+        harness.token_balance(harness.ata(TAKER, mint_a)).await,
+        DEPOSIT
+    );
+    assert_eq!(
+        harness.token_balance(harness.ata(MAKER, mint_b)).await,
+        RECEIVE
+    );
+}
+
+#[tokio::test]
+async fn take_refuses_once_the_market_moves() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        feed,
+    } = guarded_escrow(150, 500).await;
+
+    // The feed now quotes 1.60 B per A, 6.25% away from the offer.
+    write_feed(&mut harness, feed, 160).await;
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::PriceDeviation));
+
+    // Below the offer counts the same as above it.
+    write_feed(&mut harness, feed, 140).await;
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::PriceDeviation));
+
+    assert_eq!(
+        harness.token_balance(harness.ata(TAKER, mint_b)).await,
+        RECEIVE
+    );
+    assert!(harness.escrow(MAKER, SEED).await.is_some());
+}
+
+#[tokio::test]
+async fn take_needs_the_escrows_own_feed() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        ..
+    } = guarded_escrow(150, 100).await;
+
+    let err = harness
+        .take(TAKER, MAKER, SEED, mint_a, mint_b)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::PriceFeedMismatch));
+
+    let other = Pubkey::new_unique();
+    write_feed(&mut harness, other, 150).await;
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(other));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::PriceFeedMismatch));
+}
+
+#[tokio::test]
+async fn only_the_maker_sets_the_guard() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        feed,
+    } = guarded_escrow(300, 100).await;
+
+    // A taker cannot loosen the guard, even by claiming to be the maker.
+    let mut guard = harness.set_price_guard_instruction(MAKER, SEED, None, 0);
+    guard.accounts[0].pubkey = harness.actor(TAKER);
+    assert!(harness.send(&[guard], &[TAKER]).await.is_err());
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::PriceDeviation));
+
+    // The maker can drop it, after which no feed is needed.
+    let guard = harness.set_price_guard_instruction(MAKER, SEED, None, 0);
+    harness.send(&[guard], &[MAKER]).await.unwrap();
+
+    let escrow = harness.escrow(MAKER, SEED).await.unwrap();
+    assert!(!escrow.has_price_guard());
+
+    harness
+        .take(TAKER, MAKER, SEED, mint_a, mint_b)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn malformed_feeds_are_rejected_up_front() {
+    let Setup { mut harness, .. } = guarded_escrow(150, 100).await;
+
+    let feed = Pubkey::new_unique();
+    write_feed(&mut harness, feed, 0).await;
+
+    let guard = harness.set_price_guard_instruction(MAKER, SEED, Some(feed), 100);
+    let err = harness.send(&[guard], &[MAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::InvalidPriceFeed));
+}
+
+#[tokio::test]
+async fn take_refuses_a_stale_feed() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        feed,
+    } = guarded_escrow(150, 100).await;
+
+    // Published one slot longer ago than a take may rely on.
+    let slot = harness.slot();
+    write_feed_as(&mut harness, feed, 150, slot - MAX_PRICE_AGE_SLOTS, ORACLE).await;
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::StalePriceFeed));
+
+    // A feed claiming a slot that has not happened yet is no better.
+    let slot = harness.slot();
+    write_feed_as(&mut harness, feed, 150, slot + 10, ORACLE).await;
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(custom_error(&err), error(EscrowError::InvalidPriceFeed));
+
+    // Exactly `MAX_PRICE_AGE_SLOTS` old still counts.
+    let slot = harness.slot();
+    write_feed_as(
+        &mut harness,
+        feed,
+        150,
+        slot + 1 - MAX_PRICE_AGE_SLOTS,
+        ORACLE,
+    )
+    .await;
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    harness.send(&[take], &[TAKER]).await.unwrap();
+}
+
+#[tokio::test]
+async fn take_refuses_a_feed_that_changed_owner() {
+    let Setup {
+        mut harness,
+        mint_a,
+        mint_b,
+        feed,
+    } = guarded_escrow(150, 100).await;
+    assert_eq!(
+        harness.escrow(MAKER, SEED).await.unwrap().price_feed_owner,
+        ORACLE
+    );
+
+    // Same address and a fair price, but no longer written by the oracle the maker chose.
+    let slot = harness.slot();
+    write_feed_as(&mut harness, feed, 150, slot, Pubkey::new_unique()).await;
+
+    let take = harness.guarded_take_instruction(TAKER, MAKER, SEED, mint_a, mint_b, Some(feed));
+    let err = harness.send(&[take], &[TAKER]).await.unwrap_err();
+    assert_eq!(
+        custom_error(&err),
+        error(EscrowError::PriceFeedOwnerMismatch)
+    );
+    assert!(harness.escrow(MAKER, SEED).await.is_some());
+}
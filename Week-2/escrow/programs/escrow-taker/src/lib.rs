@@ -0,0 +1,96 @@
+//! Sample caller of the escrow program's CPI interface.
+//!
+//! Every `authority` gets a taker PDA that holds tokens but no lamports. `take` settles
+//! an escrow on the PDA's behalf through `escrow::cpi::take_with_payer`, with the
+//! transaction's `payer` covering any token accounts that need to be created.
+
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenInterface},
+};
+use escrow::{
+    cpi::{accounts::TakeWithPayer, take_with_payer},
+    program::Escrow as EscrowProgram,
+    Escrow,
+};
+
+declare_id!("6HtK3dPBfxWzsmj4QSae3xJgQV8HZHcHkwsF1TsPSDKb");
+
+#[program]
+pub mod escrow_taker {
+    use super::*;
+
+    pub fn take(ctx: Context<Take>) -> Result<()> {
+        ctx.accounts.take(&ctx.bumps)
+    }
+}
+
+#[derive(Accounts)]
+pub struct Take<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"taker", authority.key().as_ref()],
+        bump
+    )]
+    pub taker: SystemAccount<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// CHECK: created if needed and validated by the escrow program
+    #[account(mut)]
+    pub taker_ata_a: UncheckedAccount<'info>,
+    /// CHECK: validated by the escrow program
+    #[account(mut)]
+    pub taker_ata_b: UncheckedAccount<'info>,
+    /// CHECK: created if needed and validated by the escrow program
+    #[account(mut)]
+    pub maker_ata_b: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub escrow: AccountLoader<'info, Escrow>,
+    /// CHECK: validated by the escrow program
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: validated by the escrow program
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    pub escrow_program: Program<'info, EscrowProgram>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Take<'info> {
+    pub fn take(&mut self, bumps: &TakeBumps) -> Result<()> {
+        let authority = self.authority.key();
+        let signer_seeds: [&[&[u8]]; 1] = [&[b"taker", authority.as_ref(), &[bumps.taker]]];
+
+        let take_accounts = TakeWithPayer {
+            payer: self.payer.to_account_info(),
+            taker: self.taker.to_account_info(),
+            maker: self.maker.to_account_info(),
+            mint_a: self.mint_a.to_account_info(),
+            mint_b: self.mint_b.to_account_info(),
+            taker_ata_a: self.taker_ata_a.to_account_info(),
+            taker_ata_b: self.taker_ata_b.to_account_info(),
+            maker_ata_b: self.maker_ata_b.to_account_info(),
+            escrow: self.escrow.to_account_info(),
+            vault: self.vault.to_account_info(),
+            price_feed: self.price_feed.as_ref().map(|feed| feed.to_account_info()),
+            associated_token_program: self.associated_token_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.escrow_program.to_account_info(),
+            take_accounts,
+            &signer_seeds,
+        );
+
+        take_with_payer(cpi_ctx)
+    }
+}
@@ -5,7 +5,7 @@ use anchor_spl::{
     token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 
-use crate::Escrow;
+use crate::{Escrow, ESCROW_VERSION};
 
 #[derive(Accounts)]
 #[instruction(seed: u64)]
@@ -30,11 +30,11 @@ pub struct Make<'info> {
     #[account(
         init,
         payer = maker,
-        space = 8 + Escrow::INIT_SPACE,
+        space = Escrow::LEN,
         seeds = [b"escrow", maker.key().as_ref(),seed.to_le_bytes().as_ref()],
         bump
     )]
-    pub escrow: Account<'info, Escrow>,
+    pub escrow: AccountLoader<'info, Escrow>,
     #[account(
         init,
         payer = maker,
@@ -50,14 +50,20 @@ pub struct Make<'info> {
 
 impl<'info> Make<'info> {
     pub fn init_escrow(&mut self, seed: u64, receive: u64, bumps: &MakeBumps) -> Result<()> {
-        self.escrow.set_inner(Escrow {
+        *self.escrow.load_init()? = Escrow {
             seed,
+            receive,
             maker: self.maker.key(),
             mint_a: self.mint_a.key(),
             mint_b: self.mint_b.key(),
-            receive,
+            version: ESCROW_VERSION,
             bump: bumps.escrow,
-        });
+            max_deviation_bps: 0,
+            padding: [0; 4],
+            price_feed: Pubkey::default(),
+            price_feed_owner: Pubkey::default(),
+            reserved: [0; 64],
+        };
         Ok(())
     }
 
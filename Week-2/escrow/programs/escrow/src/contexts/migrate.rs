@@ -0,0 +1,81 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+
+use crate::{Escrow, EscrowError, LegacyEscrow, ESCROW_VERSION};
+
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    /// Pays for the rent of the larger layout. Migration keeps every field as it was, so
+    /// anyone may run it.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: a legacy escrow cannot be deserialized as `Escrow`, so `migrate` checks
+    /// its discriminator, layout and address by hand.
+    #[account(mut, owner = crate::ID)]
+    pub escrow: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateEscrow<'info> {
+    pub fn migrate(&mut self) -> Result<()> {
+        let legacy = self.load_legacy()?;
+
+        let escrow = Pubkey::create_program_address(
+            &[
+                b"escrow",
+                legacy.maker.as_ref(),
+                legacy.seed.to_le_bytes().as_ref(),
+                &[legacy.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(escrow, self.escrow.key(), ErrorCode::ConstraintSeeds);
+
+        let rent = Rent::get()?.minimum_balance(Escrow::LEN);
+        let top_up = rent.saturating_sub(self.escrow.lamports());
+
+        if top_up > 0 {
+            let transfer_accounts = Transfer {
+                from: self.payer.to_account_info(),
+                to: self.escrow.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+            transfer(cpi_ctx, top_up)?;
+        }
+
+        self.escrow.realloc(Escrow::LEN, true)?;
+
+        let escrow = Escrow {
+            version: ESCROW_VERSION,
+            ..Escrow::from(legacy)
+        };
+
+        let mut data = self.escrow.try_borrow_mut_data()?;
+        data[8..].copy_from_slice(bytemuck::bytes_of(&escrow));
+
+        Ok(())
+    }
+
+    fn load_legacy(&self) -> Result<LegacyEscrow> {
+        let data = self.escrow.try_borrow_data()?;
+
+        require!(
+            data.len() >= 8 && data[..8] == Escrow::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        require_neq!(data.len(), Escrow::LEN, EscrowError::AlreadyMigrated);
+        require_eq!(
+            data.len(),
+            LegacyEscrow::LEN,
+            ErrorCode::AccountDidNotDeserialize
+        );
+
+        Ok(LegacyEscrow::deserialize(&mut &data[8..])?)
+    }
+}
@@ -6,3 +6,15 @@ pub use refund::*;
 
 pub mod take;
 pub use take::*;
+
+pub mod take_with_payer;
+pub use take_with_payer::*;
+
+pub mod settlement;
+pub use settlement::*;
+
+pub mod price_guard;
+pub use price_guard::*;
+
+pub mod migrate;
+pub use migrate::*;
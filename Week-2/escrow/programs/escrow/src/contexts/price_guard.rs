@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::{Escrow, PriceFeed};
+
+/// Lets the maker guard an open offer with a price feed, or remove the guard by
+/// leaving `price_feed` out.
+#[derive(Accounts)]
+pub struct SetPriceGuard<'info> {
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        has_one = maker,
+        seeds = [b"escrow", maker.key().as_ref(), Escrow::load_current(&escrow)?.seed.to_le_bytes().as_ref()],
+        bump = escrow.load()?.bump
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    /// CHECK: any account holding a `PriceFeed`; `set_price_guard` parses it so a
+    /// malformed or stale feed is rejected before any taker runs into it, and pins the
+    /// program that owns it.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> SetPriceGuard<'info> {
+    pub fn set_price_guard(&mut self, max_deviation_bps: u16) -> Result<()> {
+        let mut escrow = self.escrow.load_mut()?;
+
+        match &self.price_feed {
+            Some(price_feed) => {
+                // Whoever owns the feed now is the program the maker trusts to publish it
+                PriceFeed::read(price_feed, price_feed.owner)?;
+                escrow.price_feed = price_feed.key();
+                escrow.price_feed_owner = *price_feed.owner;
+                escrow.max_deviation_bps = max_deviation_bps;
+            }
+            None => {
+                escrow.price_feed = Pubkey::default();
+                escrow.price_feed_owner = Pubkey::default();
+                escrow.max_deviation_bps = 0;
+            }
+        }
+
+        Ok(())
+    }
+}
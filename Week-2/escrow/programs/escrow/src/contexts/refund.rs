@@ -27,10 +27,10 @@ pub struct Refund<'info> {
         close = maker,
         has_one = mint_a,
         has_one = maker,
-        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
-        bump = escrow.bump
+        seeds = [b"escrow", maker.key().as_ref(), Escrow::load_current(&escrow)?.seed.to_le_bytes().as_ref()],
+        bump = escrow.load()?.bump
     )]
-    escrow: Account<'info, Escrow>,
+    escrow: AccountLoader<'info, Escrow>,
     #[account(
         mut,
         associated_token::mint = mint_a,
@@ -45,11 +45,16 @@ pub struct Refund<'info> {
 
 impl<'info> Refund<'info> {
     pub fn refund_and_close(&mut self) -> Result<()> {
+        let (seed, bump) = {
+            let escrow = self.escrow.load()?;
+            (escrow.seed, escrow.bump)
+        };
+
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
             self.maker.to_account_info().key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.bump],
+            &seed.to_le_bytes()[..],
+            &[bump],
         ]];
 
         let xfer_accounts = TransferChecked {
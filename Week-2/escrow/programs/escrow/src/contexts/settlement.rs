@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::token_interface::{
+    close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+    TransferChecked,
+};
+
+use crate::Escrow;
+
+/// The accounts `Take` and `TakeWithPayer` settle an escrow with, so both run the same
+/// price check, transfers and close. `rent_receiver` gets the vault's rent back.
+pub struct Settlement<'a, 'info> {
+    pub taker: &'a Signer<'info>,
+    pub maker: &'a SystemAccount<'info>,
+    pub mint_a: &'a InterfaceAccount<'info, Mint>,
+    pub mint_b: &'a InterfaceAccount<'info, Mint>,
+    pub taker_ata_a: &'a InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_b: &'a InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_b: &'a InterfaceAccount<'info, TokenAccount>,
+    pub escrow: &'a AccountLoader<'info, Escrow>,
+    pub vault: &'a InterfaceAccount<'info, TokenAccount>,
+    pub price_feed: Option<&'a UncheckedAccount<'info>>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    pub rent_receiver: AccountInfo<'info>,
+}
+
+impl<'a, 'info> Settlement<'a, 'info> {
+    pub fn check_price(&self) -> Result<()> {
+        self.escrow.load()?.check_price(
+            self.price_feed.map(AsRef::as_ref),
+            self.vault.amount,
+            self.mint_a.decimals,
+            self.mint_b.decimals,
+        )
+    }
+
+    pub fn deposit(&self) -> Result<()> {
+        let transfer_accounts = TransferChecked {
+            from: self.taker_ata_b.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to: self.maker_ata_b.to_account_info(),
+            authority: self.taker.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
+
+        let receive = self.escrow.load()?.receive;
+
+        transfer_checked(cpi_ctx, receive, self.mint_b.decimals)
+    }
+
+    pub fn withdraw_and_close_vault(&self) -> Result<()> {
+        let (seed, bump) = {
+            let escrow = self.escrow.load()?;
+            (escrow.seed, escrow.bump)
+        };
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            self.maker.to_account_info().key.as_ref(),
+            &seed.to_le_bytes()[..],
+            &[bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: self.taker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let transfer_cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            &signer_seeds,
+        );
+
+        transfer_checked(transfer_cpi_ctx, self.vault.amount, self.mint_a.decimals)?;
+
+        let close_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.rent_receiver.clone(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let close_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            &signer_seeds,
+        );
+
+        close_account(close_ctx)
+    }
+}
@@ -2,13 +2,10 @@ use anchor_lang::prelude::*;
 
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{
-        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
-    },
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-use crate::Escrow;
+use crate::{contexts::Settlement, Escrow};
 
 #[derive(Accounts)]
 pub struct Take<'info> {
@@ -47,10 +44,10 @@ pub struct Take<'info> {
         has_one = maker,
         has_one = mint_a,
         has_one = mint_b,
-        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
-        bump = escrow.bump
+        seeds = [b"escrow", maker.key().as_ref(), Escrow::load_current(&escrow)?.seed.to_le_bytes().as_ref()],
+        bump = escrow.load()?.bump
     )]
-    escrow: Account<'info, Escrow>,
+    escrow: AccountLoader<'info, Escrow>,
     #[account(
         mut,
         associated_token::mint = mint_a,
@@ -58,60 +55,29 @@ pub struct Take<'info> {
         associated_token::token_program = token_program,
     )]
     pub vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: compared against the escrow's price guard and parsed in `check_price`;
+    /// only needed when the escrow has one.
+    pub price_feed: Option<UncheckedAccount<'info>>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Take<'info> {
-    pub fn deposit(&mut self) -> Result<()> {
-        let transfer_accounts = TransferChecked {
-            from: self.taker_ata_b.to_account_info(),
-            mint: self.mint_b.to_account_info(),
-            to: self.maker_ata_b.to_account_info(),
-            authority: self.taker.to_account_info(),
-        };
-
-        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
-
-        transfer_checked(cpi_ctx, self.escrow.receive, self.mint_b.decimals)
-    }
-
-    pub fn withdraw_and_close_vault(&mut self) -> Result<()> {
-        let signer_seeds: [&[&[u8]]; 1] = [&[
-            b"escrow",
-            self.maker.to_account_info().key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.bump],
-        ]];
-
-        let transfer_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
-            mint: self.mint_a.to_account_info(),
-            to: self.taker_ata_a.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
-
-        let transfer_cpi_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            transfer_accounts,
-            &signer_seeds,
-        );
-
-        transfer_checked(transfer_cpi_ctx, self.vault.amount, self.mint_a.decimals)?;
-
-        let close_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
-            destination: self.taker.to_account_info(),
-            authority: self.escrow.to_account_info(),
-        };
-
-        let close_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            close_accounts,
-            &signer_seeds,
-        );
-
-        close_account(close_ctx)
+    pub fn settlement(&self) -> Settlement<'_, 'info> {
+        Settlement {
+            taker: &self.taker,
+            maker: &self.maker,
+            mint_a: &self.mint_a,
+            mint_b: &self.mint_b,
+            taker_ata_a: &self.taker_ata_a,
+            taker_ata_b: &self.taker_ata_b,
+            maker_ata_b: &self.maker_ata_b,
+            escrow: &self.escrow,
+            vault: &self.vault,
+            price_feed: self.price_feed.as_ref(),
+            token_program: &self.token_program,
+            rent_receiver: self.taker.to_account_info(),
+        }
     }
 }
@@ -0,0 +1,87 @@
+use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::{contexts::Settlement, Escrow};
+
+/// `Take` for callers whose taker cannot pay: typically a PDA of another program that
+/// signs through CPI and holds no lamports. `payer` funds any token account that has to
+/// be created and receives the vault's rent back.
+#[derive(Accounts)]
+pub struct TakeWithPayer<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = mint_a,
+        has_one = mint_b,
+        seeds = [b"escrow", maker.key().as_ref(), Escrow::load_current(&escrow)?.seed.to_le_bytes().as_ref()],
+        bump = escrow.load()?.bump
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: compared against the escrow's price guard and parsed in `check_price`;
+    /// only needed when the escrow has one.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeWithPayer<'info> {
+    pub fn settlement(&self) -> Settlement<'_, 'info> {
+        Settlement {
+            taker: &self.taker,
+            maker: &self.maker,
+            mint_a: &self.mint_a,
+            mint_b: &self.mint_b,
+            taker_ata_a: &self.taker_ata_a,
+            taker_ata_b: &self.taker_ata_b,
+            maker_ata_b: &self.maker_ata_b,
+            escrow: &self.escrow,
+            vault: &self.vault,
+            price_feed: self.price_feed.as_ref(),
+            token_program: &self.token_program,
+            rent_receiver: self.payer.to_account_info(),
+        }
+    }
+}
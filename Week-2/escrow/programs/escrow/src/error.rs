@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("Escrow uses the legacy layout, migrate it with `migrate_escrow` first")]
+    LegacyEscrow,
+    #[msg("Escrow already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Price feed does not match the escrow's price guard")]
+    PriceFeedMismatch,
+    #[msg("Price feed account is malformed or reports a zero price")]
+    InvalidPriceFeed,
+    #[msg("Offer price deviates from the price feed by more than the maker allows")]
+    PriceDeviation,
+    #[msg("Amounts are too large to compare against the price feed")]
+    PriceMathOverflow,
+    #[msg("Price feed is not owned by the program it was pinned to")]
+    PriceFeedOwnerMismatch,
+    #[msg("Price feed has not been published recently enough")]
+    StalePriceFeed,
+}
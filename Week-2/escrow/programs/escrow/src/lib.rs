@@ -6,6 +6,9 @@ use contexts::*;
 pub mod state;
 pub use state::*;
 
+pub mod error;
+pub use error::*;
+
 declare_id!("2pDEXE7h7w7FK7cKqpgSHyzJZoQpGGAo32KnqHbbvMZE");
 
 #[program]
@@ -22,7 +25,24 @@ pub mod escrow {
     }
 
     pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.deposit()?;
-        ctx.accounts.withdraw_and_close_vault()
+        let settlement = ctx.accounts.settlement();
+        settlement.check_price()?;
+        settlement.deposit()?;
+        settlement.withdraw_and_close_vault()
+    }
+
+    pub fn take_with_payer(ctx: Context<TakeWithPayer>) -> Result<()> {
+        let settlement = ctx.accounts.settlement();
+        settlement.check_price()?;
+        settlement.deposit()?;
+        settlement.withdraw_and_close_vault()
+    }
+
+    pub fn set_price_guard(ctx: Context<SetPriceGuard>, max_deviation_bps: u16) -> Result<()> {
+        ctx.accounts.set_price_guard(max_deviation_bps)
+    }
+
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        ctx.accounts.migrate()
     }
 }
@@ -1,8 +1,111 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, Discriminator};
 
-#[account]
+use crate::EscrowError;
+
+mod price_feed;
+pub use price_feed::*;
+
+/// Layout version written by `make` and `migrate_escrow`.
+pub const ESCROW_VERSION: u8 = 1;
+
+/// Version reported for escrows that still use the unversioned Borsh layout.
+pub const LEGACY_ESCROW_VERSION: u8 = 0;
+
+#[account(zero_copy)]
 #[derive(InitSpace)]
 pub struct Escrow {
+    pub seed: u64,
+    pub receive: u64,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub version: u8,
+    pub bump: u8,
+    /// Largest deviation from `price_feed`, in basis points, that `take` accepts.
+    pub max_deviation_bps: u16,
+    pub padding: [u8; 4],
+    /// Feed guarding the offer's price, or `Pubkey::default()` when the escrow has no
+    /// price guard.
+    pub price_feed: Pubkey,
+    /// Program that owned `price_feed` when the guard was set. Only that program can
+    /// write the feed, so a feed that changes hands no longer counts.
+    pub price_feed_owner: Pubkey,
+    /// Room for new fields, so they can be added without reallocating existing escrows.
+    pub reserved: [u8; 64],
+}
+
+impl Escrow {
+    pub const LEN: usize = 8 + Escrow::INIT_SPACE;
+
+    /// Loads an escrow in the current layout, failing with `LegacyEscrow` instead of
+    /// reading past the end of an account that still needs `migrate_escrow`.
+    pub fn load_current<'a>(
+        escrow: &'a AccountLoader<'_, Escrow>,
+    ) -> Result<std::cell::Ref<'a, Escrow>> {
+        require_gte!(
+            escrow.as_ref().data_len(),
+            Escrow::LEN,
+            EscrowError::LegacyEscrow
+        );
+        escrow.load()
+    }
+
+    /// Decodes escrow account data in either layout, for clients that may still come
+    /// across escrows created before versioning.
+    pub fn unpack(data: &[u8]) -> Result<Escrow> {
+        require!(
+            data.len() >= 8 && data[..8] == Escrow::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        match data.len() {
+            LegacyEscrow::LEN => Ok(LegacyEscrow::deserialize(&mut &data[8..])?.into()),
+            len if len >= Escrow::LEN => Ok(bytemuck::pod_read_unaligned(&data[8..Escrow::LEN])),
+            _ => err!(ErrorCode::AccountDidNotDeserialize),
+        }
+    }
+
+    pub fn has_price_guard(&self) -> bool {
+        self.price_feed != Pubkey::default()
+    }
+
+    /// Refuses to settle a guarded escrow when trading `amount` of `mint_a` for
+    /// `receive` of `mint_b` is further from the feed's price than the maker allowed.
+    pub fn check_price(
+        &self,
+        price_feed: Option<&AccountInfo>,
+        amount: u64,
+        decimals_a: u8,
+        decimals_b: u8,
+    ) -> Result<()> {
+        if !self.has_price_guard() {
+            return Ok(());
+        }
+
+        let price_feed = price_feed.ok_or(EscrowError::PriceFeedMismatch)?;
+        require_keys_eq!(
+            price_feed.key(),
+            self.price_feed,
+            EscrowError::PriceFeedMismatch
+        );
+
+        let within = PriceFeed::read(price_feed, &self.price_feed_owner)?.within(
+            self.receive,
+            amount,
+            decimals_a,
+            decimals_b,
+            self.max_deviation_bps,
+        )?;
+        require!(within, EscrowError::PriceDeviation);
+
+        Ok(())
+    }
+}
+
+/// `Escrow` as it was stored before versioning: a Borsh account without version byte
+/// or reserved space.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LegacyEscrow {
     pub seed: u64,
     pub maker: Pubkey,
     pub mint_a: Pubkey,
@@ -10,3 +113,26 @@ pub struct Escrow {
     pub receive: u64,
     pub bump: u8,
 }
+
+impl LegacyEscrow {
+    pub const LEN: usize = 8 + 8 + 32 + 32 + 32 + 8 + 1;
+}
+
+impl From<LegacyEscrow> for Escrow {
+    fn from(legacy: LegacyEscrow) -> Self {
+        Escrow {
+            seed: legacy.seed,
+            receive: legacy.receive,
+            maker: legacy.maker,
+            mint_a: legacy.mint_a,
+            mint_b: legacy.mint_b,
+            version: LEGACY_ESCROW_VERSION,
+            bump: legacy.bump,
+            max_deviation_bps: 0,
+            padding: [0; 4],
+            price_feed: Pubkey::default(),
+            price_feed_owner: Pubkey::default(),
+            reserved: [0; 64],
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+use bytemuck::{Pod, Zeroable};
+
+use crate::EscrowError;
+
+/// Slots a feed may go without a new price before `take` stops trusting it, about a
+/// minute at 400ms slots.
+pub const MAX_PRICE_AGE_SLOTS: u64 = 150;
+
+/// Layout an escrow's price feed is read with: the price of one whole `mint_a` token
+/// in whole `mint_b` tokens, as `price / 10^decimals`, published at `published_slot`.
+/// The feed is plain data at the start of the account, so any program the maker trusts
+/// can publish it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct PriceFeed {
+    pub price: u64,
+    pub decimals: u8,
+    pub padding: [u8; 7],
+    pub published_slot: u64,
+}
+
+impl PriceFeed {
+    pub const LEN: usize = std::mem::size_of::<PriceFeed>();
+
+    /// Parses the feed in `account`, which has to be owned by `owner` and published
+    /// within the last `MAX_PRICE_AGE_SLOTS` slots.
+    pub fn read(account: &AccountInfo, owner: &Pubkey) -> Result<PriceFeed> {
+        require_keys_eq!(*account.owner, *owner, EscrowError::PriceFeedOwnerMismatch);
+
+        let data = account.try_borrow_data()?;
+        require_gte!(data.len(), PriceFeed::LEN, EscrowError::InvalidPriceFeed);
+
+        let feed: PriceFeed = bytemuck::pod_read_unaligned(&data[..PriceFeed::LEN]);
+        require_neq!(feed.price, 0, EscrowError::InvalidPriceFeed);
+
+        // A feed from the future is as malformed as one without a price
+        let slot = Clock::get()?.slot;
+        require_gte!(slot, feed.published_slot, EscrowError::InvalidPriceFeed);
+        require_gte!(
+            MAX_PRICE_AGE_SLOTS,
+            slot - feed.published_slot,
+            EscrowError::StalePriceFeed
+        );
+
+        Ok(feed)
+    }
+
+    /// Whether `receive` base units of `mint_b` for `amount` base units of `mint_a` is
+    /// within `max_deviation_bps` of the feed's price.
+    ///
+    /// Both prices are scaled by `amount * 10^(decimals_b + self.decimals)` so the
+    /// comparison stays in integers.
+    pub fn within(
+        &self,
+        receive: u64,
+        amount: u64,
+        decimals_a: u8,
+        decimals_b: u8,
+        max_deviation_bps: u16,
+    ) -> Result<bool> {
+        let offered = scale(receive, decimals_a as u32 + self.decimals as u32)?;
+        let quoted = (self.price as u128)
+            .checked_mul(scale(amount, decimals_b as u32)?)
+            .ok_or(EscrowError::PriceMathOverflow)?;
+
+        let deviation = offered
+            .abs_diff(quoted)
+            .checked_mul(10_000)
+            .ok_or(EscrowError::PriceMathOverflow)?;
+        let allowed = quoted
+            .checked_mul(max_deviation_bps as u128)
+            .ok_or(EscrowError::PriceMathOverflow)?;
+
+        Ok(deviation <= allowed)
+    }
+}
+
+fn scale(amount: u64, decimals: u32) -> Result<u128> {
+    10u128
+        .checked_pow(decimals)
+        .and_then(|factor| factor.checked_mul(amount as u128))
+        .ok_or(error!(EscrowError::PriceMathOverflow))
+}